@@ -1,15 +1,19 @@
-use std::fs::File;
+use std::{fs::File, path::Path};
 
 use clap::{Parser, ValueEnum};
 use raytacer::{
     colour::Colour,
+    environment::Environment,
     geometry::{Geometry, Material, Shape},
+    medium::{DensityGrid, GridMedium, Medium},
+    scene::SceneDescription,
     vec::Vec3,
 };
 
 #[derive(Clone, Debug, ValueEnum)]
 pub enum StockScene {
     RandomSpheres,
+    SmokeAndFire,
 }
 
 #[derive(Parser, Debug)]
@@ -34,12 +38,23 @@ struct CliArguments {
 fn main() -> anyhow::Result<()> {
     let args = CliArguments::parse();
 
-    let geometries = match args.scene {
-        StockScene::RandomSpheres => generate_random_spheres(),
+    let description = match args.scene {
+        StockScene::RandomSpheres => SceneDescription {
+            camera: None,
+            environment: Environment::default(),
+            medium: None,
+            geometries: generate_random_spheres(),
+        },
+        StockScene::SmokeAndFire => SceneDescription {
+            camera: None,
+            environment: Environment::default(),
+            medium: None,
+            geometries: generate_smoke_and_fire(&args.output_path)?,
+        },
     };
 
     let file = File::create(&args.output_path)?;
-    serde_yaml::to_writer(file, &geometries)?;
+    serde_yaml::to_writer(file, &description)?;
 
     Ok(())
 }
@@ -57,6 +72,7 @@ fn generate_random_spheres() -> Vec<Geometry> {
             colour: Colour::new(0.5, 0.5, 0.5),
             albedo: 0.3,
         },
+        medium: None,
     });
 
     // small spheres
@@ -87,7 +103,13 @@ fn generate_random_spheres() -> Vec<Geometry> {
                 Material::Metal { tint, scatter }
             } else {
                 // glass
-                Material::Dialectric { ior: 1.5 }
+                Material::Dialectric {
+                    ior: 1.5,
+                    dispersion: None,
+                    tint: Colour::white(),
+                    thin_walled: false,
+                    absorption: Colour::black(),
+                }
             };
 
             geometries.push(Geometry {
@@ -96,6 +118,7 @@ fn generate_random_spheres() -> Vec<Geometry> {
                     radius: 0.2,
                 },
                 material,
+                medium: None,
             })
         }
     }
@@ -106,7 +129,14 @@ fn generate_random_spheres() -> Vec<Geometry> {
             centre: Vec3::new((0.0, 1.0, 0.0)),
             radius: 1.0,
         },
-        material: Material::Dialectric { ior: 1.5 },
+        material: Material::Dialectric {
+            ior: 1.5,
+            dispersion: None,
+            tint: Colour::white(),
+            thin_walled: false,
+            absorption: Colour::black(),
+        },
+        medium: None,
     });
 
     geometries.push(Geometry {
@@ -118,6 +148,7 @@ fn generate_random_spheres() -> Vec<Geometry> {
             colour: Colour::new(0.4, 0.2, 0.1),
             albedo: 1.0,
         },
+        medium: None,
     });
 
     geometries.push(Geometry {
@@ -129,13 +160,87 @@ fn generate_random_spheres() -> Vec<Geometry> {
             tint: Colour::new(0.7, 0.6, 0.5),
             scatter: 0.0,
         },
-    });
-
-    // background
-    geometries.push(Geometry {
-        shape: Shape::Background,
-        material: Material::ScreenSpaceGradient,
+        medium: None,
     });
 
     geometries
 }
+
+/// A plume of smoke with a fire at its base. The voxel grids are written
+/// next to the scene file, with the same name and a `.density.vox` or
+/// `.temperature.vox` extension, and referred to relative to it.
+fn generate_smoke_and_fire(output_path: &str) -> anyhow::Result<Vec<Geometry>> {
+    const RESOLUTION: usize = 48;
+
+    let mut density = Vec::with_capacity(RESOLUTION.pow(3));
+    let mut temperature = Vec::with_capacity(RESOLUTION.pow(3));
+
+    for z in 0..RESOLUTION {
+        for y in 0..RESOLUTION {
+            for x in 0..RESOLUTION {
+                let p = |i: usize| (i as f64 + 0.5) / RESOLUTION as f64 * 2. - 1.;
+                let (px, py, pz) = (p(x), p(y), p(z));
+
+                // The plume widens and wobbles as it rises.
+                let height = (py + 1.) / 2.;
+                let radius = 0.25 + 0.45 * height;
+                let wobble = 0.15 * (py * 6.).sin();
+                let r = ((px - wobble).powi(2) + pz.powi(2)).sqrt();
+                let billows = 0.5 + 0.5 * (px * 9.).sin() * (py * 7.).cos() * (pz * 8.).sin();
+
+                let falloff = (1. - r / radius).clamp(0., 1.);
+                density.push((falloff * (0.6 + 0.4 * billows)) as f32);
+
+                let heat = (1. - height * 3.).clamp(0., 1.) * (1. - r / 0.3).clamp(0., 1.);
+                temperature.push(heat as f32);
+            }
+        }
+    }
+
+    let output_path = Path::new(output_path);
+    let scene_dir = output_path.parent().unwrap_or(Path::new(""));
+    let name = output_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("output");
+
+    let mut density = DensityGrid::new((RESOLUTION, RESOLUTION, RESOLUTION), density)?;
+    let mut temperature = DensityGrid::new((RESOLUTION, RESOLUTION, RESOLUTION), temperature)?;
+    density.save(scene_dir, &format!("{name}.density.vox"))?;
+    temperature.save(scene_dir, &format!("{name}.temperature.vox"))?;
+
+    // Keep the box clear of the ground, or rays crossing its bottom face
+    // would skip past the ground within the self-intersection tolerance.
+    let min = Vec3::new((-1., 0.01, -2.));
+    let max = Vec3::new((1., 2., 0.));
+
+    Ok(vec![
+        // ground
+        Geometry {
+            shape: Shape::Sphere {
+                centre: Vec3::new((0., -1000.0, 0.0)),
+                radius: 1000.0,
+            },
+            material: Material::Lambertian {
+                colour: Colour::new(0.5, 0.5, 0.5),
+                albedo: 1.0,
+            },
+            medium: None,
+        },
+        // plume
+        Geometry {
+            shape: Shape::Cuboid { min, max },
+            material: Material::Interface,
+            medium: Some(Medium::Grid(GridMedium {
+                grid: density,
+                min,
+                max,
+                density: 8.0,
+                albedo: Colour::new(0.8, 0.8, 0.8),
+                anisotropy: 0.3,
+                emission: Colour::new(40.0, 12.0, 2.0),
+                emission_grid: Some(temperature),
+            })),
+        },
+    ])
+}
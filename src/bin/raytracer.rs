@@ -1,23 +1,65 @@
 extern crate raytacer;
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
-use png::ScaledFloat;
 use rayon::prelude::*;
 use raytacer::{
-    camera::CameraConfig,
+    camera::{Camera, CameraConfig, Convergence, FisheyeMapping, LensPrescription, StereoLayout},
     colour::Colour,
-    geometry::Geometry,
+    film::{Film, Filter},
+    integrator::{
+        AmbientOcclusion, Bidirectional, DirectLighting, Integrator, IterativePathTracer,
+        Metropolis, PathTracer, PhotonMapper, ProgressivePhotonMapper, Whitted,
+    },
     pixel::{Pixel, RGB},
-    scene::Scene,
+    sampling::{BlueNoise, Halton, Independent, Sampler, Sobol, Stratified},
+    scene::{Scene, SceneDescription},
     vec::Vec3,
 };
-use std::fs::File;
+use std::{
+    fs::File,
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Clone, Debug, ValueEnum)]
+enum IntegratorChoice {
+    Path,
+    IterativePath,
+    Whitted,
+    AmbientOcclusion,
+    DirectLighting,
+    Bidirectional,
+    PhotonMapping,
+    ProgressivePhotonMapping,
+    Metropolis,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum SamplerChoice {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum Projection {
+    Perspective,
+    Orthographic,
+    Equirectangular,
+    Fisheye,
+    Cubemap,
+    Physical,
+    Realistic,
+}
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct CliArguments {
-    geometry_path: String,
+    scene_path: String,
 
     #[arg(
         help_heading = "Image",
@@ -28,6 +70,14 @@ struct CliArguments {
     )]
     output_path: String,
 
+    #[arg(
+        help_heading = "Image",
+        long = "frames",
+        help = "Render these frames of the camera's animation, as a range such as 0..48 or 1..=48. The output path has the frame number put in place of {frame}, or {frame:04} to pad it with zeros",
+        value_parser = parse_frames
+    )]
+    frames: Option<Range<usize>>,
+
     #[arg(
         help_heading = "Image",
         short = 'w',
@@ -55,6 +105,29 @@ struct CliArguments {
     )]
     samples_per_pixel: usize,
 
+    #[arg(
+        help_heading = "Quality",
+        long = "filter",
+        help = "Reconstruction filter samples are splatted into pixels with. Photon mapping and Metropolis always use a box",
+        default_value = "box"
+    )]
+    filter: Filter,
+
+    #[arg(
+        help_heading = "Quality",
+        long = "filter-radius",
+        help = "Radius of the reconstruction filter in pixels. If not specified, it's the usual one for the filter."
+    )]
+    filter_radius: Option<f64>,
+
+    #[arg(
+        help_heading = "Quality",
+        long = "sampler",
+        help = "Where each pixel's samples take their numbers from. Photon mapping and Metropolis always choose them independently",
+        default_value = "independent"
+    )]
+    sampler: SamplerChoice,
+
     #[arg(
         help_heading = "Quality",
         long = "max-bounces",
@@ -63,6 +136,98 @@ struct CliArguments {
     )]
     max_bounces: usize,
 
+    #[arg(
+        help_heading = "Quality",
+        long = "roulette-depth",
+        help = "How many bounces before paths carrying little light may be ended early",
+        default_value = "3"
+    )]
+    roulette_depth: usize,
+
+    #[arg(
+        help_heading = "Quality",
+        long = "light-sampling",
+        help = "Sample the environment directly at surfaces"
+    )]
+    light_sampling: bool,
+
+    #[arg(
+        help_heading = "Quality",
+        long = "integrator",
+        help = "How to compute the light reaching the camera",
+        default_value = "path"
+    )]
+    integrator: IntegratorChoice,
+
+    #[arg(
+        help_heading = "Quality",
+        long = "ao-distance",
+        help = "How far away surfaces occlude each other with the ambient occlusion integrator"
+    )]
+    ao_distance: Option<f64>,
+
+    #[arg(
+        help_heading = "Photon mapping",
+        long = "photons",
+        help = "How many photons to trace, per pass when progressive",
+        default_value = "200000"
+    )]
+    photons: usize,
+
+    #[arg(
+        help_heading = "Photon mapping",
+        long = "photon-neighbours",
+        help = "How many of the nearest photons to estimate light from",
+        default_value = "50"
+    )]
+    photon_neighbours: usize,
+
+    #[arg(
+        help_heading = "Photon mapping",
+        long = "photon-radius",
+        help = "How far away photons are gathered from, or the starting radius when progressive",
+        default_value = "0.1"
+    )]
+    photon_radius: f64,
+
+    #[arg(
+        help_heading = "Metropolis",
+        long = "mlt-bootstrap",
+        help = "How many paths to trace first to find where to start mutating from",
+        default_value = "100000"
+    )]
+    mlt_bootstrap: usize,
+
+    #[arg(
+        help_heading = "Metropolis",
+        long = "mlt-chains",
+        help = "How many chains of mutations to run",
+        default_value = "1000"
+    )]
+    mlt_chains: usize,
+
+    #[arg(
+        help_heading = "Metropolis",
+        long = "mlt-large-step",
+        help = "Chance of each mutation being a fresh path",
+        default_value = "0.3"
+    )]
+    mlt_large_step: f64,
+
+    #[arg(
+        help_heading = "Quality",
+        long = "spectral",
+        help = "Give each sample wavelengths, so dispersive glass splits light into colours"
+    )]
+    spectral: bool,
+
+    #[arg(
+        help_heading = "Camera",
+        long = "projection",
+        help = "Camera projection. The scene file's camera is used instead if it has one and this isn't given"
+    )]
+    projection: Option<Projection>,
+
     #[arg(
         help_heading = "Camera",
         long = "fov",
@@ -109,63 +274,438 @@ struct CliArguments {
         help = "Fixed focus distance. If not specified, it focuses on 'look-at' point."
     )]
     camera_focus_distance: Option<f64>,
+
+    #[arg(
+        help_heading = "Camera",
+        long = "view-width",
+        help = "Width of the area in view with the orthographic projection",
+        default_value = "4"
+    )]
+    camera_view_width: f64,
+
+    #[arg(
+        help_heading = "Camera",
+        long = "fisheye-mapping",
+        help = "How angles map to the image with the fisheye projection",
+        default_value = "equidistant"
+    )]
+    fisheye_mapping: FisheyeMapping,
+
+    #[arg(
+        help_heading = "Physical camera",
+        long = "focal-length",
+        help = "Focal length of the lens in millimetres",
+        default_value = "50"
+    )]
+    focal_length: f64,
+
+    #[arg(
+        help_heading = "Physical camera",
+        long = "sensor-width",
+        help = "Width of the sensor in millimetres",
+        default_value = "36"
+    )]
+    sensor_width: f64,
+
+    #[arg(
+        help_heading = "Physical camera",
+        long = "f-stop",
+        help = "Focal length divided by the aperture's diameter",
+        default_value = "8"
+    )]
+    f_stop: f64,
+
+    #[arg(
+        help_heading = "Physical camera",
+        long = "aperture-blades",
+        help = "Number of blades making up the aperture. Fewer than three gives a round one",
+        default_value = "0"
+    )]
+    aperture_blades: usize,
+
+    #[arg(
+        help_heading = "Physical camera",
+        long = "aperture-rotation",
+        help = "Rotation of the aperture blades in degrees",
+        default_value = "0"
+    )]
+    aperture_rotation: f64,
+
+    #[arg(
+        help_heading = "Physical camera",
+        long = "shutter",
+        help = "Shutter speed in seconds. If not specified, the image isn't scaled by the exposure."
+    )]
+    shutter_speed: Option<f64>,
+
+    #[arg(
+        help_heading = "Physical camera",
+        long = "iso",
+        help = "Sensitivity of the sensor",
+        default_value = "100"
+    )]
+    iso: f64,
+
+    #[arg(
+        help_heading = "Realistic camera",
+        long = "lens",
+        help = "Lens prescription file for the realistic projection"
+    )]
+    lens: Option<String>,
+
+    #[arg(
+        help_heading = "Realistic camera",
+        long = "aperture-diameter",
+        help = "Diameter of the lens's aperture stop in millimetres. If not specified, it's the one in the prescription."
+    )]
+    aperture_diameter: Option<f64>,
+
+    #[arg(
+        help_heading = "Stereo",
+        long = "stereo",
+        help = "Render a view for each eye into one image, laid out like this"
+    )]
+    stereo: Option<StereoLayout>,
+
+    #[arg(
+        help_heading = "Stereo",
+        long = "ipd",
+        help = "Distance between the eyes",
+        default_value = "0.064"
+    )]
+    interpupillary_distance: f64,
+
+    #[arg(
+        help_heading = "Stereo",
+        long = "convergence",
+        help = "How the eyes' views are made to meet",
+        default_value = "off-axis"
+    )]
+    convergence: Convergence,
+
+    #[arg(
+        help_heading = "Stereo",
+        long = "convergence-distance",
+        help = "Distance at which things appear at screen depth. If not specified, it's the 'look-at' point."
+    )]
+    convergence_distance: Option<f64>,
 }
 
 fn main() {
     let args = CliArguments::parse();
 
-    let focus_dist = args
-        .camera_focus_distance
-        .unwrap_or_else(|| (args.camera_look_at - args.camera_origin).length());
-
-    let camera = CameraConfig::Orthogonal {
-        look_from: args.camera_origin,
-        look_at: args.camera_look_at,
-        up: args.camera_up,
-        fov_degrees: args.camera_fov,
-        defocus_angle: args.camera_defocus_angle,
-        focus_dist,
-        image_width: args.width,
-        image_height: args.height,
+    let description = load_scene(&args.scene_path)
+        .unwrap_or_else(|err| panic!("failed to load scene from '{}': {err}", args.scene_path));
+
+    let camera_config = match (&args.projection, description.camera) {
+        (None, Some(camera)) => camera,
+        (Some(Projection::Orthographic), _) => CameraConfig::Orthographic {
+            look_from: args.camera_origin,
+            look_at: args.camera_look_at,
+            up: args.camera_up,
+            view_width: args.camera_view_width,
+        },
+        (Some(Projection::Equirectangular), _) => CameraConfig::Equirectangular {
+            look_from: args.camera_origin,
+            look_at: args.camera_look_at,
+            up: args.camera_up,
+        },
+        (Some(Projection::Fisheye), _) => CameraConfig::Fisheye {
+            look_from: args.camera_origin,
+            look_at: args.camera_look_at,
+            up: args.camera_up,
+            fov_degrees: args.camera_fov,
+            mapping: args.fisheye_mapping,
+        },
+        (Some(Projection::Cubemap), _) => CameraConfig::Cubemap {
+            look_from: args.camera_origin,
+            look_at: args.camera_look_at,
+            up: args.camera_up,
+        },
+        (Some(Projection::Physical), _) => CameraConfig::Physical {
+            look_from: args.camera_origin,
+            look_at: args.camera_look_at,
+            up: args.camera_up,
+            focal_length_mm: args.focal_length,
+            sensor_width_mm: args.sensor_width,
+            f_stop: args.f_stop,
+            aperture_blades: args.aperture_blades,
+            aperture_rotation_degrees: args.aperture_rotation,
+            focus_dist: args.camera_focus_distance,
+            shutter_speed: args.shutter_speed,
+            iso: args.iso,
+        },
+        (Some(Projection::Realistic), _) => {
+            let path = args
+                .lens
+                .as_deref()
+                .expect("the realistic projection needs a lens given with --lens");
+            let lens = LensPrescription::load(path)
+                .unwrap_or_else(|err| panic!("failed to load lens from '{path}': {err}"));
+
+            CameraConfig::Realistic {
+                look_from: args.camera_origin,
+                look_at: args.camera_look_at,
+                up: args.camera_up,
+                lens,
+                sensor_width_mm: args.sensor_width,
+                aperture_diameter_mm: args.aperture_diameter,
+                focus_dist: args.camera_focus_distance,
+            }
+        }
+        _ => CameraConfig::Perspective {
+            look_from: args.camera_origin,
+            look_at: args.camera_look_at,
+            up: args.camera_up,
+            fov_degrees: args.camera_fov,
+            defocus_angle: args.camera_defocus_angle,
+            focus_dist: args.camera_focus_distance,
+        },
+    };
+    let camera_config = match args.stereo {
+        Some(layout) => CameraConfig::Stereo {
+            camera: Box::new(camera_config),
+            interpupillary_distance: args.interpupillary_distance,
+            convergence: args.convergence,
+            convergence_distance: args.convergence_distance,
+            layout,
+        },
+        None => camera_config,
+    };
+    let frames = match &args.frames {
+        Some(_) if !args.output_path.contains("{frame") => CliArguments::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the output path needs {frame} in it to render a sequence of --frames",
+            )
+            .exit(),
+        Some(frames) => frames.clone(),
+        None => 0..1,
+    };
+    let camera_at = |frame: usize| {
+        Camera::new(
+            camera_config.at_frame(frame as f64),
+            (args.width, args.height),
+        )
+        .unwrap_or_else(|err| panic!("failed to set up the camera: {err}"))
+    };
+
+    let mut scene = Scene {
+        camera: camera_at(frames.start),
+        environment: description.environment,
+        medium: description.medium,
+        geometries: description.geometries,
+        light_sampling: args.light_sampling,
+        spectral: args.spectral,
+    };
+    for frame in frames.clone() {
+        if frame != frames.start {
+            scene.camera = camera_at(frame);
+        }
+
+        let pixels = match args.integrator {
+            IntegratorChoice::ProgressivePhotonMapping => render_progressive(&scene, &args),
+            IntegratorChoice::Metropolis => render_metropolis(&scene, &args),
+            _ => render(&scene, integrator(&scene, &args).as_ref(), &args),
+        };
+
+        let path = frame_path(&args.output_path, frame);
+        if path.to_lowercase().ends_with(".exr") {
+            write_to_exr(&path, &pixels, (args.width, args.height));
+        } else {
+            write_to_png::<RGB>(&path, &pixels, (args.width, args.height));
+        }
+    }
+}
+
+/// Parses a range of frames written like Rust's, either `a..b` or `a..=b`,
+/// which has to hold at least one frame.
+fn parse_frames(range: &str) -> Result<Range<usize>, String> {
+    let error = || format!("'{range}' isn't a range of frames such as 0..48 or 1..=48");
+
+    let (start, end) = range.split_once("..").ok_or_else(error)?;
+    let start: usize = start.trim().parse().map_err(|_| error())?;
+    let end: usize = match end.strip_prefix('=') {
+        Some(end) => end.trim().parse::<usize>().map_err(|_| error())? + 1,
+        None => end.trim().parse().map_err(|_| error())?,
+    };
+    if start >= end {
+        return Err(format!("'{range}' has no frames in it"));
+    }
+
+    Ok(start..end)
+}
+
+/// `path` with `frame` in place of every `{frame}`, and of every
+/// `{frame:0N}` padded with zeros to `N` digits.
+fn frame_path(path: &str, frame: usize) -> String {
+    let mut path = path.replace("{frame}", &frame.to_string());
+
+    let mut from = 0;
+    while let Some(offset) = path[from..].find("{frame:0") {
+        let start = from + offset;
+        let Some(length) = path[start..].find('}') else {
+            break;
+        };
+
+        let placeholder = path[start..=start + length].to_string();
+        match placeholder["{frame:0".len()..length].parse() {
+            Ok(width) => path = path.replace(&placeholder, &format!("{frame:0width$}")),
+            Err(_) => from = start + placeholder.len(),
+        }
+    }
+
+    path
+}
+
+fn integrator(scene: &Scene, args: &CliArguments) -> Box<dyn Integrator> {
+    match args.integrator {
+        IntegratorChoice::Path => Box::new(PathTracer {
+            max_bounces: args.max_bounces,
+            roulette_depth: args.roulette_depth,
+        }),
+        IntegratorChoice::IterativePath => Box::new(IterativePathTracer {
+            max_bounces: args.max_bounces,
+            roulette_depth: args.roulette_depth,
+        }),
+        IntegratorChoice::Whitted => Box::new(Whitted {
+            max_depth: args.max_bounces,
+        }),
+        IntegratorChoice::AmbientOcclusion => Box::new(AmbientOcclusion {
+            max_distance: args.ao_distance.unwrap_or(f64::INFINITY),
+        }),
+        IntegratorChoice::DirectLighting => Box::new(DirectLighting {
+            max_bounces: args.max_bounces,
+        }),
+        IntegratorChoice::Bidirectional => Box::new(Bidirectional {
+            max_bounces: args.max_bounces,
+        }),
+        IntegratorChoice::PhotonMapping => Box::new(PhotonMapper::new(
+            scene,
+            args.photons,
+            args.max_bounces,
+            args.photon_neighbours,
+            args.photon_radius,
+        )),
+        IntegratorChoice::ProgressivePhotonMapping | IntegratorChoice::Metropolis => {
+            unreachable!("renders the whole image at once")
+        }
     }
-    .into();
+}
 
-    let geometries = load_geometries(&args.geometry_path).expect(&format!(
-        "failed to load geometries from '{}'",
-        args.geometry_path
+fn render(scene: &Scene, integrator: &dyn Integrator, args: &CliArguments) -> Vec<Colour> {
+    let filter_radius = args
+        .filter_radius
+        .unwrap_or_else(|| args.filter.default_radius());
+    let film = Mutex::new(Film::new(
+        (args.width, args.height),
+        args.filter,
+        filter_radius,
     ));
+    let sampler = sampler(args);
+
+    // Each row is rendered into a film of its own, as its samples spread
+    // into the rows around it, and then added to the image.
+    (0..args.height)
+        .into_par_iter()
+        .progress_with(progress_bar(args.height))
+        .for_each(|y| {
+            let mut tile = film.lock().unwrap().tile(y..y + 1);
+            for x in 0..args.width {
+                scene.render_pixel(
+                    (x, y),
+                    args.samples_per_pixel,
+                    &sampler,
+                    integrator,
+                    &mut tile,
+                );
+            }
+            film.lock().unwrap().merge(&tile);
+        });
+
+    film.into_inner().unwrap().into_image()
+}
+
+fn sampler(args: &CliArguments) -> Arc<dyn Sampler> {
+    match args.sampler {
+        SamplerChoice::Independent => Arc::new(Independent),
+        SamplerChoice::Stratified => Arc::new(Stratified::new(args.samples_per_pixel)),
+        SamplerChoice::Halton => Arc::new(Halton::new()),
+        SamplerChoice::Sobol => Arc::new(Sobol::new()),
+        SamplerChoice::BlueNoise => Arc::new(BlueNoise::new()),
+    }
+}
 
-    let scene = Scene { camera, geometries };
-    let image_size = args.width * args.height;
+/// Renders with progressive photon mapping, running one pass per sample.
+fn render_progressive(scene: &Scene, args: &CliArguments) -> Vec<Colour> {
+    let integrator = ProgressivePhotonMapper {
+        max_bounces: args.max_bounces,
+        photons_per_pass: args.photons,
+        initial_radius: args.photon_radius,
+    };
 
-    let mut pixels = Vec::new();
-    pixels.resize(image_size, Colour::default());
+    let mut pixels = integrator.start(args.width * args.height);
+    let progress = progress_bar(args.samples_per_pixel);
+    for _ in 0..args.samples_per_pixel {
+        integrator.pass(scene, &mut pixels, args.width);
+        progress.inc(1);
+    }
+    progress.finish();
+
+    pixels
+        .iter()
+        .map(|pixel| integrator.radiance(pixel))
+        .collect()
+}
+
+/// Renders with Metropolis light transport over the path tracer, making as
+/// many mutations as samples per pixel.
+fn render_metropolis(scene: &Scene, args: &CliArguments) -> Vec<Colour> {
+    let metropolis = Metropolis {
+        bootstrap_samples: args.mlt_bootstrap,
+        chains: args.mlt_chains,
+        mutations_per_pixel: args.samples_per_pixel,
+        large_step_probability: args.mlt_large_step,
+        sigma: 0.01,
+    };
+    let integrator = PathTracer {
+        max_bounces: args.max_bounces,
+        roulette_depth: args.roulette_depth,
+    };
 
-    let progress = ProgressBar::new(image_size as u64).with_style(
+    metropolis.render(scene, &integrator, (args.width, args.height))
+}
+
+fn progress_bar(length: usize) -> ProgressBar {
+    ProgressBar::new(length as u64).with_style(
         ProgressStyle::with_template(
             "[{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>7}/{len:7} {eta} left {msg}",
         )
         .unwrap(),
-    );
-    pixels
-        .par_iter_mut()
-        .enumerate()
-        .progress_with(progress)
-        .for_each(|(index, pixel)| {
-            let x = index % args.width;
-            let y = index / args.width;
-
-            *pixel = scene.render_pixel((x, y), args.samples_per_pixel, args.max_bounces);
-        });
-
-    write_to_png::<RGB>(&args.output_path, &pixels, (args.width, args.height));
+    )
 }
 
-fn load_geometries(path: &str) -> anyhow::Result<Vec<Geometry>> {
+/// Reads a scene file, finding the files it refers to relative to it.
+fn load_scene(path: &str) -> anyhow::Result<SceneDescription> {
     let file = File::open(path)?;
-    let geometries = serde_yaml::from_reader(file)?;
+    let scene_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut description: SceneDescription = serde_yaml::from_reader(file)?;
+    description.load_grids(scene_dir)?;
 
-    Ok(geometries)
+    Ok(description)
+}
+
+/// Writes each pixel's radiance as it is, without the gamma or clipping of
+/// a PNG.
+fn write_to_exr(path: &str, pixels: &[Colour], dimensions: (usize, usize)) {
+    let data = pixels
+        .iter()
+        .flat_map(|colour| [colour.r() as f32, colour.g() as f32, colour.b() as f32])
+        .collect();
+    let image = image::Rgb32FImage::from_raw(dimensions.0 as u32, dimensions.1 as u32, data)
+        .expect("image size matches its pixels");
+
+    image.save(path).unwrap();
 }
 
 fn write_to_png<P: Pixel>(path: &str, pixels: &[Colour], dimensions: (usize, usize)) {
@@ -177,8 +717,7 @@ fn write_to_png<P: Pixel>(path: &str, pixels: &[Colour], dimensions: (usize, usi
     encoder.set_depth(P::png_bit_depth());
     encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
 
-    let mut data = Vec::new();
-    data.resize(pixels.len() * P::WIDTH, 0);
+    let mut data = vec![0; pixels.len() * P::WIDTH];
 
     pixels.iter().enumerate().for_each(|(index, colour)| {
         let pixel = P::from(*colour);
@@ -189,3 +728,29 @@ fn write_to_png<P: Pixel>(path: &str, pixels: &[Colour], dimensions: (usize, usi
     writer.write_image_data(&data).unwrap();
     writer.finish().unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_ranges_like_rusts() {
+        assert_eq!(parse_frames("0..48"), Ok(0..48));
+        assert_eq!(parse_frames("1..=48"), Ok(1..49));
+        assert_eq!(parse_frames(" 5 ..= 5 "), Ok(5..6));
+
+        for range in ["5..5", "5..=3", "7..2", "1-48", "a..b", "..3"] {
+            assert!(parse_frames(range).is_err(), "{range}");
+        }
+    }
+
+    #[test]
+    fn frame_numbers_go_into_the_output_path() {
+        assert_eq!(frame_path("out/{frame}.png", 7), "out/7.png");
+        assert_eq!(frame_path("out/{frame:04}.png", 7), "out/0007.png");
+        assert_eq!(frame_path("{frame:02}-{frame}", 123), "123-123");
+        assert_eq!(frame_path("{frame:03}/{frame:03}", 7), "007/007");
+        assert_eq!(frame_path("{frame:0x}-{frame:02}", 7), "{frame:0x}-07");
+        assert_eq!(frame_path("out.png", 7), "out.png");
+    }
+}
@@ -0,0 +1,271 @@
+use std::ops::{Add, Mul, Sub};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::CameraConfig;
+use crate::vec::Vec3;
+
+/// The camera's settings at one frame of an animation. Settings left out
+/// are interpolated between the keyframes that give them, or kept from the
+/// animated camera if none do.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub frame: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub look_from: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub look_at: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fov_degrees: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_dist: Option<f64>,
+}
+
+/// Reads keyframes in whatever order they're written, putting them in order
+/// of frame.
+pub(super) fn sorted_keyframes<'de, D>(deserializer: D) -> Result<Vec<Keyframe>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut keyframes = Vec::<Keyframe>::deserialize(deserializer)?;
+    keyframes.sort_by(|a, b| a.frame.total_cmp(&b.frame));
+    Ok(keyframes)
+}
+
+/// How settings change between keyframes.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// At a steady rate from one keyframe to the next, turning sharply at
+    /// each.
+    #[default]
+    Linear,
+    /// Along a Catmull-Rom spline through the keyframes, which moves
+    /// smoothly through each of them.
+    CatmullRom,
+}
+
+/// `camera` with its settings at `frame` taken from `keyframes`, which are
+/// in order of frame. Any animation within `camera` must already be
+/// resolved with `CameraConfig::at_frame`.
+pub(super) fn animate(
+    mut camera: CameraConfig,
+    keyframes: &[Keyframe],
+    interpolation: Interpolation,
+    frame: f64,
+) -> CameraConfig {
+    let look_from = track(keyframes, |key| key.look_from, frame, interpolation);
+    let look_at = track(keyframes, |key| key.look_at, frame, interpolation);
+    let fov = track(keyframes, |key| key.fov_degrees, frame, interpolation);
+    let focus = track(keyframes, |key| key.focus_dist, frame, interpolation);
+
+    match &mut camera {
+        CameraConfig::Perspective {
+            look_from: from,
+            look_at: at,
+            fov_degrees,
+            focus_dist,
+            ..
+        } => {
+            *fov_degrees = fov.unwrap_or(*fov_degrees);
+            *focus_dist = focus.or(*focus_dist);
+            set_view(from, at, look_from, look_at);
+        }
+        CameraConfig::Fisheye {
+            look_from: from,
+            look_at: at,
+            fov_degrees,
+            ..
+        } => {
+            *fov_degrees = fov.unwrap_or(*fov_degrees);
+            set_view(from, at, look_from, look_at);
+        }
+        CameraConfig::Physical {
+            look_from: from,
+            look_at: at,
+            focus_dist,
+            ..
+        }
+        | CameraConfig::Realistic {
+            look_from: from,
+            look_at: at,
+            focus_dist,
+            ..
+        } => {
+            *focus_dist = focus.or(*focus_dist);
+            set_view(from, at, look_from, look_at);
+        }
+        CameraConfig::Orthographic {
+            look_from: from,
+            look_at: at,
+            ..
+        }
+        | CameraConfig::Equirectangular {
+            look_from: from,
+            look_at: at,
+            ..
+        }
+        | CameraConfig::Cubemap {
+            look_from: from,
+            look_at: at,
+            ..
+        } => set_view(from, at, look_from, look_at),
+        CameraConfig::Stereo { camera, .. } => {
+            **camera = animate((**camera).clone(), keyframes, interpolation, frame);
+        }
+        CameraConfig::Animated { .. } => unreachable!("animations are resolved before animating"),
+    }
+
+    camera
+}
+
+/// The value at `frame` of the setting picked out of keyframes by
+/// `setting`, if any keyframes give it.
+fn track<T>(
+    keyframes: &[Keyframe],
+    setting: fn(&Keyframe) -> Option<T>,
+    frame: f64,
+    interpolation: Interpolation,
+) -> Option<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    let keys: Vec<(f64, T)> = keyframes
+        .iter()
+        .filter_map(|key| Some((key.frame, setting(key)?)))
+        .collect();
+    interpolate(&keys, frame, interpolation)
+}
+
+fn set_view(from: &mut Vec3, at: &mut Vec3, look_from: Option<Vec3>, look_at: Option<Vec3>) {
+    *from = look_from.unwrap_or(*from);
+    *at = look_at.unwrap_or(*at);
+}
+
+/// The value at `frame` of a setting given at each of `keys`. Before the
+/// first key and after the last, the setting is held still.
+fn interpolate<T>(keys: &[(f64, T)], frame: f64, interpolation: Interpolation) -> Option<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    let (first, last) = (keys.first()?, keys.last()?);
+    if frame <= first.0 {
+        return Some(first.1);
+    }
+    if frame >= last.0 {
+        return Some(last.1);
+    }
+
+    // The keys either side of the frame.
+    let i = keys.iter().rposition(|(key, _)| *key <= frame)?;
+    let ((t0, p0), (t1, p1)) = (keys[i], keys[i + 1]);
+    let span = t1 - t0;
+    if span <= 0. {
+        return Some(p1);
+    }
+    let s = (frame - t0) / span;
+
+    match interpolation {
+        Interpolation::Linear => Some(p0 + (p1 - p0) * s),
+        Interpolation::CatmullRom => {
+            // The rate of change at each key, from the keys either side of
+            // it, which is one-sided at the ends.
+            let tangent = |j: usize| {
+                let before = keys[j.saturating_sub(1)];
+                let after = keys[(j + 1).min(keys.len() - 1)];
+                let frames = after.0 - before.0;
+                let rate = if frames > 0. { 1. / frames } else { 0. };
+                (after.1 - before.1) * rate
+            };
+            let (m0, m1) = (tangent(i), tangent(i + 1));
+
+            // The cubic Hermite basis.
+            let s2 = s * s;
+            let s3 = s2 * s;
+            let h00 = 2. * s3 - 3. * s2 + 1.;
+            let h10 = s3 - 2. * s2 + s;
+            let h01 = -2. * s3 + 3. * s2;
+            let h11 = s3 - s2;
+
+            Some(p0 * h00 + m0 * (h10 * span) + p1 * h01 + m1 * (h11 * span))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: [(f64, f64); 4] = [(0., 1.), (10., 3.), (20., 2.), (40., 6.)];
+
+    fn assert_close(a: Option<f64>, b: f64) {
+        let a = a.unwrap();
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn settings_pass_through_each_key() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            for (frame, value) in KEYS {
+                assert_close(interpolate(&KEYS, frame, interpolation), value);
+            }
+        }
+    }
+
+    #[test]
+    fn settings_are_held_before_the_first_key_and_after_the_last() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            assert_close(interpolate(&KEYS, -5., interpolation), 1.);
+            assert_close(interpolate(&KEYS, 100., interpolation), 6.);
+        }
+        assert!(interpolate::<f64>(&[], 3., Interpolation::Linear).is_none());
+    }
+
+    #[test]
+    fn linear_interpolation_moves_steadily() {
+        assert_close(interpolate(&KEYS, 2.5, Interpolation::Linear), 1.5);
+        assert_close(interpolate(&KEYS, 30., Interpolation::Linear), 4.);
+    }
+
+    #[test]
+    fn catmull_rom_follows_the_neighbouring_keys() {
+        // Each key's slope is from the keys either side of it, one-sided at
+        // the ends, so it leaves the first key heading for the second.
+        let slope = |frame: f64| {
+            let step = 1e-6;
+            let at = |frame| interpolate(&KEYS, frame, Interpolation::CatmullRom).unwrap();
+            (at(frame + step) - at(frame - step)) / (2. * step)
+        };
+        assert!((slope(10.) - (2. - 1.) / 20.).abs() < 1e-5);
+        assert!((slope(1e-3) - (3. - 1.) / 10.).abs() < 1e-3);
+        assert!((slope(40. - 1e-3) - (6. - 2.) / 20.).abs() < 1e-3);
+
+        // Halfway between two keys with matching slopes, it's halfway.
+        let even = [(0., 0.), (1., 1.), (2., 2.), (3., 3.)];
+        assert_close(interpolate(&even, 1.5, Interpolation::CatmullRom), 1.5);
+    }
+
+    #[test]
+    fn keyframes_are_sorted_when_loaded() {
+        let yaml = "
+            !Animated
+            camera: !Equirectangular
+              look_from: [0, 0, 0]
+              look_at: [0, 0, -1]
+            keyframes:
+              - { frame: 20, look_from: [2, 0, 0] }
+              - { frame: 0, look_from: [0, 0, 0] }
+              - { frame: 10, look_from: [1, 0, 0] }
+        ";
+        let config: CameraConfig = serde_yaml::from_str(yaml).unwrap();
+        let CameraConfig::Animated { keyframes, .. } = &config else {
+            panic!("not animated");
+        };
+        let frames: Vec<f64> = keyframes.iter().map(|key| key.frame).collect();
+        assert_eq!(frames, [0., 10., 20.]);
+
+        let CameraConfig::Equirectangular { look_from, .. } = config.at_frame(15.) else {
+            panic!("not equirectangular");
+        };
+        assert!((look_from.x() - 1.5).abs() < 1e-9);
+    }
+}
@@ -1,13 +1,221 @@
+use std::f64::consts::PI;
+
 use serde::{Deserialize, Serialize};
 
-use crate::vec::Vec3;
+use super::{Camera, LensPrescription, StereoLayout};
+use crate::{
+    sampling,
+    vec::{Frame, Vec3},
+};
 
-#[derive(Serialize, Deserialize)]
 pub enum CameraLens {
-    Orthogonal {
+    Perspective {
         pixel0_loc: Vec3,
         pixel_delta_u: Vec3,
         pixel_delta_v: Vec3,
         defocus_disc_uv: Option<(Vec3, Vec3)>,
+        aperture: Aperture,
+        image_size: (usize, usize),
+    },
+    Orthographic {
+        pixel0_loc: Vec3,
+        pixel_delta_u: Vec3,
+        pixel_delta_v: Vec3,
+    },
+    /// Rays traced from a film `film_size` across, at the eye, out through
+    /// each element of a lens in front of it. The frame is the lens's own
+    /// space.
+    Realistic {
+        frame: Frame,
+        image_size: (usize, usize),
+        film_size: (f64, f64),
+        lens: LensPrescription,
+    },
+
+    // Panoramic lenses map each point on the image to a direction from the
+    // eye. Their frames have `s` to the right, `t` up and `n` behind.
+    /// Longitude across the image and latitude down it, covering every
+    /// direction.
+    Equirectangular {
+        frame: Frame,
+        image_size: (usize, usize),
+    },
+    /// A circle in the middle of the image covering `fov` radians across.
+    /// Points outside it see nothing.
+    Fisheye {
+        frame: Frame,
+        image_size: (usize, usize),
+        fov: f64,
+        mapping: FisheyeMapping,
     },
+    /// Six square faces side by side, each a 90° view: right, left, up,
+    /// down, front and back.
+    Cubemap {
+        frame: Frame,
+        image_size: (usize, usize),
+    },
+
+    /// A camera for each eye, left then right, sharing the image.
+    Stereo {
+        eyes: Box<[Camera; 2]>,
+        layout: StereoLayout,
+        eye_size: (usize, usize),
+        image_size: (usize, usize),
+    },
+}
+
+/// The shape of a thin lens's aperture, which out of focus highlights take
+/// on.
+#[derive(Clone, Copy)]
+pub enum Aperture {
+    Circle,
+    /// A regular polygon made by this many blades, turned by `rotation`
+    /// radians.
+    Polygon {
+        blades: usize,
+        rotation: f64,
+    },
+}
+
+impl Aperture {
+    /// A uniformly chosen point on the aperture, which fits in the unit
+    /// circle.
+    pub(super) fn sample(&self) -> (f64, f64) {
+        match *self {
+            // Shirley and Chiu's concentric mapping, which keeps
+            // neighbouring numbers close together on the disc.
+            Aperture::Circle => {
+                let (u, v) = sampling::uniform_pair();
+                let (a, b) = (2. * u - 1., 2. * v - 1.);
+                if a == 0. && b == 0. {
+                    return (0., 0.);
+                }
+
+                let (radius, theta) = if a.abs() > b.abs() {
+                    (a, PI / 4. * (b / a))
+                } else {
+                    (b, PI / 2. - PI / 4. * (a / b))
+                };
+                (radius * theta.cos(), radius * theta.sin())
+            }
+
+            // The polygon is made of equal triangles fanning out from the
+            // middle, so pick one and then a point within it.
+            Aperture::Polygon { blades, rotation } => {
+                let blade = sampling::index(blades);
+                let corner = |i: usize| {
+                    let angle = rotation + 2. * PI * i as f64 / blades as f64;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(blade), corner(blade + 1));
+
+                let (xi0, xi1) = sampling::uniform_pair();
+                let scale = xi0.sqrt();
+                (
+                    scale * (a.0 + (b.0 - a.0) * xi1),
+                    scale * (a.1 + (b.1 - a.1) * xi1),
+                )
+            }
+        }
+    }
+
+    /// The area of the aperture, in the units of the points from `sample`.
+    pub(super) fn area(&self) -> f64 {
+        match *self {
+            Aperture::Circle => PI,
+            Aperture::Polygon { blades, .. } => {
+                blades as f64 / 2. * (2. * PI / blades as f64).sin()
+            }
+        }
+    }
+}
+
+/// How distance from the middle of a fisheye image relates to the angle
+/// from the view direction.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, clap::ValueEnum)]
+pub enum FisheyeMapping {
+    /// Distance proportional to angle, as in most fisheye lenses.
+    #[default]
+    Equidistant,
+    /// Equal areas of the image covering equal solid angles.
+    Equisolid,
+}
+
+impl CameraLens {
+    /// The direction from the eye seen at `point`, where the image spans
+    /// `[0, 1]` in both directions from the top left.
+    pub(super) fn panoramic_direction(&self, (x, y): (f64, f64)) -> Option<Vec3> {
+        match *self {
+            CameraLens::Perspective { .. }
+            | CameraLens::Orthographic { .. }
+            | CameraLens::Realistic { .. }
+            | CameraLens::Stereo { .. } => None,
+
+            CameraLens::Equirectangular { frame, .. } => {
+                let longitude = (x - 0.5) * 2. * PI;
+                let latitude = (0.5 - y) * PI;
+
+                let local = Vec3::new((
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                ));
+                Some(frame.to_world(&local))
+            }
+
+            CameraLens::Fisheye {
+                frame,
+                image_size: (width, height),
+                fov,
+                mapping,
+            } => {
+                // Measured in units of the circle's radius, which fits the
+                // shorter side of the image.
+                let scale = width.min(height) as f64;
+                let dx = (x - 0.5) * width as f64 * 2. / scale;
+                let dy = (0.5 - y) * height as f64 * 2. / scale;
+                let radius = (dx * dx + dy * dy).sqrt();
+                if radius > 1. {
+                    return None;
+                }
+
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => radius * fov / 2.,
+                    FisheyeMapping::Equisolid => 2. * (radius * (fov / 4.).sin()).asin(),
+                };
+                let phi = dy.atan2(dx);
+
+                let local = Vec3::new((
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                ));
+                Some(frame.to_world(&local))
+            }
+
+            CameraLens::Cubemap { frame, .. } => {
+                let face = ((x * 6.) as usize).min(5);
+                let a = 2. * (x * 6. - face as f64) - 1.;
+                let b = 1. - 2. * y;
+
+                // Each face's view direction, right and up in the camera's
+                // frame, where the camera looks along -Z.
+                let (s, t, n) = (
+                    Vec3::new((1., 0., 0.)),
+                    Vec3::new((0., 1., 0.)),
+                    Vec3::new((0., 0., 1.)),
+                );
+                let (forward, right, up) = match face {
+                    0 => (s, n, t),
+                    1 => (-s, -n, t),
+                    2 => (t, s, n),
+                    3 => (-t, s, -n),
+                    4 => (-n, s, t),
+                    _ => (n, -s, t),
+                };
+
+                Some(frame.to_world(&(forward + right * a + up * b)).unit())
+            }
+        }
+    }
 }
@@ -1,102 +1,591 @@
+mod animation;
 mod lens;
+mod realistic;
+mod stereo;
 
-use crate::{ray::Ray, vec::Vec3};
-use rand::{random, Rng};
+use std::ops::Range;
 
-pub use self::lens::CameraLens;
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    ray::Ray,
+    sampling,
+    vec::{Frame, Vec3},
+};
+
+pub use self::{
+    animation::{Interpolation, Keyframe},
+    lens::{Aperture, CameraLens, FisheyeMapping},
+    realistic::LensPrescription,
+    stereo::{Convergence, StereoLayout},
+};
+
+/// How the camera is set up, either given on the command line or in a scene
+/// file.
+#[derive(Clone, Serialize, Deserialize)]
 pub enum CameraConfig {
-    Orthogonal {
+    /// A pinhole camera, or a thin lens when `defocus_angle` is set, where
+    /// things further away look smaller.
+    Perspective {
         look_from: Vec3,
         look_at: Vec3,
+        #[serde(default = "default_up")]
         up: Vec3,
         fov_degrees: f64,
+        /// Angle of the cone of rays through each pixel, blurring things
+        /// away from the focus distance.
+        #[serde(default)]
         defocus_angle: f64,
-        focus_dist: f64,
-        image_width: usize,
-        image_height: usize,
+        /// Distance to the plane in focus, defaulting to `look_at`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        focus_dist: Option<f64>,
+    },
+
+    /// A thin lens camera described the way a photographer would, in
+    /// millimetres and stops. Scene units are taken to be metres, and
+    /// radiance to be in cd/m² when working out the exposure.
+    Physical {
+        look_from: Vec3,
+        look_at: Vec3,
+        #[serde(default = "default_up")]
+        up: Vec3,
+        focal_length_mm: f64,
+        /// Defaults to a full frame sensor.
+        #[serde(default = "default_sensor_width")]
+        sensor_width_mm: f64,
+        f_stop: f64,
+        /// How many blades make up the aperture, giving polygonal bokeh.
+        /// Fewer than three gives a round aperture.
+        #[serde(default)]
+        aperture_blades: usize,
+        #[serde(default)]
+        aperture_rotation_degrees: f64,
+        /// Distance to the plane in focus, defaulting to `look_at`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        focus_dist: Option<f64>,
+        /// Exposure time in seconds. Without it, the image isn't scaled by
+        /// the exposure at all.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shutter_speed: Option<f64>,
+        #[serde(default = "default_iso")]
+        iso: f64,
+    },
+
+    /// A film behind a lens made of several elements, traced through each
+    /// of them, which gives the vignetting, distortion and aberrations of a
+    /// real lens. The field of view follows from the lens and the film's
+    /// size.
+    ///
+    /// The image is scaled so that its middle is as bright as through a
+    /// pinhole, leaving it to darken towards the edges as the lens vignettes.
+    Realistic {
+        look_from: Vec3,
+        look_at: Vec3,
+        #[serde(default = "default_up")]
+        up: Vec3,
+        lens: LensPrescription,
+        #[serde(default = "default_sensor_width")]
+        sensor_width_mm: f64,
+        /// Opens or closes the lens's aperture stop to this diameter.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        aperture_diameter_mm: Option<f64>,
+        /// Distance from the film to the plane in focus, defaulting to
+        /// `look_at`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        focus_dist: Option<f64>,
+    },
+
+    /// Parallel rays with no perspective, so things are the same size
+    /// however far away they are, for technical and isometric renders.
+    Orthographic {
+        look_from: Vec3,
+        look_at: Vec3,
+        #[serde(default = "default_up")]
+        up: Vec3,
+        /// Width of the area in view, in scene units.
+        view_width: f64,
+    },
+
+    /// Every direction around `look_from`, for environment probes and VR.
+    Equirectangular {
+        look_from: Vec3,
+        look_at: Vec3,
+        #[serde(default = "default_up")]
+        up: Vec3,
+    },
+
+    Fisheye {
+        look_from: Vec3,
+        look_at: Vec3,
+        #[serde(default = "default_up")]
+        up: Vec3,
+        /// Angle covered across the image circle, which can be more than
+        /// 180°.
+        fov_degrees: f64,
+        #[serde(default)]
+        mapping: FisheyeMapping,
+    },
+
+    /// The six faces of a cube around `look_from`, side by side.
+    Cubemap {
+        look_from: Vec3,
+        look_at: Vec3,
+        #[serde(default = "default_up")]
+        up: Vec3,
+    },
+
+    /// `camera` moving through `keyframes`, which are in order of frame and
+    /// put in order when loaded. Rendered on its own rather than as a
+    /// sequence, it's at frame zero.
+    Animated {
+        camera: Box<CameraConfig>,
+        #[serde(deserialize_with = "animation::sorted_keyframes")]
+        keyframes: Vec<Keyframe>,
+        #[serde(default)]
+        interpolation: Interpolation,
+    },
+
+    /// A view for each eye from either side of `camera`, both in one image.
+    Stereo {
+        camera: Box<CameraConfig>,
+        /// Distance between the eyes, in scene units.
+        #[serde(default = "default_interpupillary_distance")]
+        interpupillary_distance: f64,
+        #[serde(default)]
+        convergence: Convergence,
+        /// Distance at which things appear at the depth of the screen,
+        /// defaulting to `look_at`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        convergence_distance: Option<f64>,
+        #[serde(default)]
+        layout: StereoLayout,
     },
 }
 
-impl Into<Camera> for CameraConfig {
-    fn into(self) -> Camera {
+impl CameraConfig {
+    /// The camera at `frame` of any animation in it.
+    pub fn at_frame(&self, frame: f64) -> CameraConfig {
         match self {
-            CameraConfig::Orthogonal {
+            CameraConfig::Animated {
+                camera,
+                keyframes,
+                interpolation,
+            } => animation::animate(camera.at_frame(frame), keyframes, *interpolation, frame),
+            CameraConfig::Stereo {
+                camera,
+                interpupillary_distance,
+                convergence,
+                convergence_distance,
+                layout,
+            } => CameraConfig::Stereo {
+                camera: Box::new(camera.at_frame(frame)),
+                interpupillary_distance: *interpupillary_distance,
+                convergence: *convergence,
+                convergence_distance: *convergence_distance,
+                layout: *layout,
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+fn default_up() -> Vec3 {
+    Vec3::new((0., 1., 0.))
+}
+
+fn default_sensor_width() -> f64 {
+    36.
+}
+
+fn default_iso() -> f64 {
+    100.
+}
+
+/// The average distance between people's eyes, in metres.
+fn default_interpupillary_distance() -> f64 {
+    0.064
+}
+
+pub struct Camera {
+    eye: Ray,
+    lens: CameraLens,
+    /// How far to the right of `eye` a panoramic lens's rays start, turning
+    /// with each ray's direction, for one eye of an omni-directional stereo
+    /// pair.
+    eye_offset: f64,
+    /// What the light reaching the camera is multiplied by to give the
+    /// image's brightness.
+    exposure: f64,
+}
+
+/// A point on the lens that light from somewhere in the scene reaches the
+/// camera through, for tracing light from its sources to the camera.
+pub struct LensSample {
+    pub point: Vec3,
+    /// Where the light lands on the image, in pixels from its top left
+    /// corner.
+    pub position: (f64, f64),
+    /// How much light arriving at `point` from there counts towards the
+    /// image.
+    pub importance: f64,
+    /// The density of choosing `point`, per unit solid angle as seen from
+    /// where the light comes from.
+    pub pdf: f64,
+}
+
+impl Camera {
+    /// The camera `config` describes, for an image of the given size. Fails
+    /// if the settings don't make sense for each other or the image.
+    pub fn new(
+        config: CameraConfig,
+        (image_width, image_height): (usize, usize),
+    ) -> anyhow::Result<Camera> {
+        let aspect_ratio = (image_width as f64) / (image_height as f64);
+
+        let camera = match config {
+            CameraConfig::Perspective {
                 look_from,
                 look_at,
                 up,
                 fov_degrees,
                 defocus_angle,
                 focus_dist,
-                image_width,
-                image_height,
             } => {
+                let focus_dist = focus_dist.unwrap_or_else(|| (look_at - look_from).length());
+
                 let theta = fov_degrees.to_radians();
                 let h = (theta / 2.).tan();
-
                 let viewport_height = 2. * h * focus_dist;
-                let viewport_width =
-                    viewport_height * ((image_width as f64) / (image_height as f64));
 
+                let defocus = (defocus_angle > 0.).then(|| {
+                    let defocus_radius = focus_dist * (defocus_angle / 2.).to_radians().tan();
+                    (defocus_radius, Aperture::Circle)
+                });
+
+                Camera::thin_lens(
+                    look_from,
+                    look_at,
+                    up,
+                    (image_width, image_height),
+                    viewport_height,
+                    focus_dist,
+                    defocus,
+                )
+            }
+
+            CameraConfig::Physical {
+                look_from,
+                look_at,
+                up,
+                focal_length_mm,
+                sensor_width_mm,
+                f_stop,
+                aperture_blades,
+                aperture_rotation_degrees,
+                focus_dist,
+                shutter_speed,
+                iso,
+            } => {
+                let focus_dist = focus_dist.unwrap_or_else(|| (look_at - look_from).length());
+                let focal_length = focal_length_mm / 1000.;
+                ensure!(f_stop > 0., "the f-stop has to be above zero, not {f_stop}");
+                ensure!(
+                    sensor_width_mm > 0.,
+                    "the sensor has to be wider than zero, not {sensor_width_mm} mm"
+                );
+                // Nearer than its focal length, a lens can't focus an image
+                // behind it.
+                ensure!(
+                    focus_dist > focal_length,
+                    "a {focal_length_mm} mm lens can't focus {focus_dist} m away"
+                );
+
+                // The sensor sits where the lens focuses the focus plane, so
+                // the area in focus is the sensor scaled up by the ratio of
+                // their distances from the lens.
+                let magnification = (focus_dist - focal_length) / focal_length;
+                let viewport_width = sensor_width_mm / 1000. * magnification;
+                let viewport_height = viewport_width / aspect_ratio;
+
+                let aperture = if aperture_blades >= 3 {
+                    Aperture::Polygon {
+                        blades: aperture_blades,
+                        rotation: aperture_rotation_degrees.to_radians(),
+                    }
+                } else {
+                    Aperture::Circle
+                };
+                let aperture_radius = focal_length / f_stop / 2.;
+
+                let mut camera = Camera::thin_lens(
+                    look_from,
+                    look_at,
+                    up,
+                    (image_width, image_height),
+                    viewport_height,
+                    focus_dist,
+                    Some((aperture_radius, aperture)),
+                );
+
+                // Scales the brightest light that doesn't overexpose to one,
+                // using the saturation-based sensitivity of ISO 12232.
+                if let Some(shutter_speed) = shutter_speed {
+                    camera.exposure = shutter_speed * iso / (f_stop * f_stop * 100. * 1.2);
+                }
+
+                camera
+            }
+
+            CameraConfig::Realistic {
+                look_from,
+                look_at,
+                up,
+                mut lens,
+                sensor_width_mm,
+                aperture_diameter_mm,
+                focus_dist,
+            } => {
+                let focus_dist = focus_dist.unwrap_or_else(|| (look_at - look_from).length());
+                let film_width = sensor_width_mm / 1000.;
+                let film_size = (film_width, film_width / aspect_ratio);
+
+                if let Some(diameter) = aperture_diameter_mm {
+                    lens.stop_down(diameter);
+                }
+                let diagonal = (film_size.0 * film_size.0 + film_size.1 * film_size.1).sqrt();
+                lens.focus(focus_dist, diagonal / 1000.)?;
+                lens.fit_pupil(diagonal / 2.);
+
+                let transmittance = lens.transmittance();
+                let mut camera =
+                    Camera::framed(look_from, look_at, up, |frame| CameraLens::Realistic {
+                        frame,
+                        image_size: (image_width, image_height),
+                        film_size,
+                        lens,
+                    });
+                if transmittance > 0. {
+                    camera.exposure = 1. / transmittance;
+                }
+
+                camera
+            }
+
+            CameraConfig::Orthographic {
+                look_from,
+                look_at,
+                up,
+                view_width,
+            } => {
                 let w = (look_from - look_at).unit();
                 let u = up.cross(&w).unit();
                 let v = w.cross(&u);
 
-                let viewport_u = u * viewport_width;
-                let viewport_v = -v * viewport_height;
+                let viewport_u = u * view_width;
+                let viewport_v = -v * (view_width / aspect_ratio);
 
-                let viewport_upper_left =
-                    look_from - (w * focus_dist) - (viewport_u / 2.) - (viewport_v / 2.);
+                // The rays start on a plane through `look_from`, so anything
+                // behind the camera is out of view as usual.
+                let viewport_upper_left = look_from - (viewport_u / 2.) - (viewport_v / 2.);
 
                 let pixel_delta_u = viewport_u / (image_width as f64);
                 let pixel_delta_v = viewport_v / (image_height as f64);
                 let pixel0_loc = viewport_upper_left + (pixel_delta_u + pixel_delta_v) * 0.5;
 
-                let defocus_disc_uv = if defocus_angle > 0. {
-                    let defocus_radius = focus_dist * (defocus_angle / 2.).to_radians().tan();
-                    let defocus_disc_u = u * defocus_radius;
-                    let defocus_disc_v = v * defocus_radius;
-
-                    Some((defocus_disc_u, defocus_disc_v))
-                } else {
-                    None
-                };
-
                 let eye = Ray {
                     origin: look_from,
-                    direction: (look_at - look_from).unit(),
+                    direction: -w,
                 };
 
-                let lens = CameraLens::Orthogonal {
+                let lens = CameraLens::Orthographic {
                     pixel0_loc,
                     pixel_delta_u,
                     pixel_delta_v,
-                    defocus_disc_uv,
                 };
 
-                Camera { eye, lens }
+                Camera {
+                    eye,
+                    lens,
+                    eye_offset: 0.,
+                    exposure: 1.,
+                }
             }
+
+            CameraConfig::Equirectangular {
+                look_from,
+                look_at,
+                up,
+            } => Camera::framed(look_from, look_at, up, |frame| {
+                CameraLens::Equirectangular {
+                    frame,
+                    image_size: (image_width, image_height),
+                }
+            }),
+
+            CameraConfig::Fisheye {
+                look_from,
+                look_at,
+                up,
+                fov_degrees,
+                mapping,
+            } => Camera::framed(look_from, look_at, up, |frame| CameraLens::Fisheye {
+                frame,
+                image_size: (image_width, image_height),
+                fov: fov_degrees.to_radians(),
+                mapping,
+            }),
+
+            CameraConfig::Cubemap {
+                look_from,
+                look_at,
+                up,
+            } => Camera::framed(look_from, look_at, up, |frame| CameraLens::Cubemap {
+                frame,
+                image_size: (image_width, image_height),
+            }),
+
+            CameraConfig::Stereo {
+                camera,
+                interpupillary_distance,
+                convergence,
+                convergence_distance,
+                layout,
+            } => {
+                let eye_size = layout.eye_size((image_width, image_height))?;
+                let offset = interpupillary_distance / 2.;
+                let eye = |offset| {
+                    stereo::eye_camera(&camera, offset, convergence, convergence_distance, eye_size)
+                };
+                let eyes = [eye(-offset)?, eye(offset)?];
+
+                Camera {
+                    eye: eyes[0].eye,
+                    exposure: eyes[0].exposure,
+                    lens: CameraLens::Stereo {
+                        eyes: Box::new(eyes),
+                        layout,
+                        eye_size,
+                        image_size: (image_width, image_height),
+                    },
+                    eye_offset: 0.,
+                }
+            }
+
+            CameraConfig::Animated { .. } => {
+                Camera::new(config.at_frame(0.), (image_width, image_height))?
+            }
+        };
+
+        Ok(camera)
+    }
+
+    /// A camera with a viewport `viewport_height` tall at `focus_dist`,
+    /// and a lens of the given radius and aperture if it's to blur things
+    /// out of focus.
+    fn thin_lens(
+        look_from: Vec3,
+        look_at: Vec3,
+        up: Vec3,
+        (image_width, image_height): (usize, usize),
+        viewport_height: f64,
+        focus_dist: f64,
+        defocus: Option<(f64, Aperture)>,
+    ) -> Camera {
+        let viewport_width = viewport_height * ((image_width as f64) / (image_height as f64));
+
+        let w = (look_from - look_at).unit();
+        let u = up.cross(&w).unit();
+        let v = w.cross(&u);
+
+        let viewport_u = u * viewport_width;
+        let viewport_v = -v * viewport_height;
+
+        let viewport_upper_left =
+            look_from - (w * focus_dist) - (viewport_u / 2.) - (viewport_v / 2.);
+
+        let pixel_delta_u = viewport_u / (image_width as f64);
+        let pixel_delta_v = viewport_v / (image_height as f64);
+        let pixel0_loc = viewport_upper_left + (pixel_delta_u + pixel_delta_v) * 0.5;
+
+        let defocus_disc_uv = defocus.map(|(radius, _)| (u * radius, v * radius));
+        let aperture = defocus.map_or(Aperture::Circle, |(_, aperture)| aperture);
+
+        let eye = Ray {
+            origin: look_from,
+            direction: (look_at - look_from).unit(),
+        };
+
+        let lens = CameraLens::Perspective {
+            pixel0_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+            defocus_disc_uv,
+            aperture,
+            image_size: (image_width, image_height),
+        };
+
+        Camera {
+            eye,
+            lens,
+            eye_offset: 0.,
+            exposure: 1.,
         }
     }
-}
 
-pub struct Camera {
-    eye: Ray,
-    lens: CameraLens,
-}
+    /// What the light reaching the camera is multiplied by to give the
+    /// image's brightness.
+    pub fn exposure(&self) -> f64 {
+        self.exposure
+    }
 
-impl Camera {
-    pub fn screen_to_world(&self, coord: (usize, usize)) -> Ray {
+    /// Moves a perspective camera's viewport sideways without moving the
+    /// eye, so that it looks off-axis.
+    fn shift_viewport(&mut self, shift: Vec3) {
+        if let CameraLens::Perspective { pixel0_loc, .. } = &mut self.lens {
+            *pixel0_loc = *pixel0_loc + shift;
+        }
+    }
+
+    /// A camera at `look_from` whose lens works in the frame facing
+    /// `look_at`, with `s` to the right, `t` up and `n` behind.
+    fn framed(
+        look_from: Vec3,
+        look_at: Vec3,
+        up: Vec3,
+        lens: impl FnOnce(Frame) -> CameraLens,
+    ) -> Camera {
+        let w = (look_from - look_at).unit();
+        let u = up.cross(&w).unit();
+
+        let eye = Ray {
+            origin: look_from,
+            direction: -w,
+        };
+
+        Camera {
+            eye,
+            lens: lens(Frame::new(w, u)),
+            eye_offset: 0.,
+            exposure: 1.,
+        }
+    }
+
+    /// The ray through the middle of a pixel, or `None` if the lens sees
+    /// nothing there.
+    pub fn screen_to_world(&self, coord: (usize, usize)) -> Option<Ray> {
         match self.lens {
-            CameraLens::Orthogonal {
+            CameraLens::Perspective {
                 pixel0_loc,
                 pixel_delta_u,
                 pixel_delta_v,
                 defocus_disc_uv,
+                aperture,
+                ..
             } => {
                 let origin = if let Some((defocus_disc_u, defocus_disc_v)) = defocus_disc_uv {
-                    let p = random_in_unit_disk();
-                    self.eye.origin + (defocus_disc_u * p.x()) + (defocus_disc_v * p.y())
+                    let p = aperture.sample();
+                    self.eye.origin + (defocus_disc_u * p.0) + (defocus_disc_v * p.1)
                 } else {
                     self.eye.origin
                 };
@@ -107,46 +596,299 @@ impl Camera {
 
                 let direction = pixel_centre - origin;
 
-                Ray { origin, direction }
+                Some(Ray { origin, direction })
+            }
+            CameraLens::Orthographic {
+                pixel0_loc,
+                pixel_delta_u,
+                pixel_delta_v,
+            } => {
+                let origin = pixel0_loc
+                    + (pixel_delta_u * coord.0 as f64)
+                    + (pixel_delta_v * coord.1 as f64);
+
+                Some(Ray {
+                    origin,
+                    direction: self.eye.direction,
+                })
             }
+            CameraLens::Realistic { .. } => self.realistic_ray(coord, (0.5, 0.5)),
+            CameraLens::Equirectangular { .. }
+            | CameraLens::Fisheye { .. }
+            | CameraLens::Cubemap { .. } => self.panoramic_ray(coord, (0.5, 0.5)),
+            CameraLens::Stereo {
+                ref eyes,
+                layout,
+                eye_size,
+                ..
+            } => {
+                let (eye, coord) = layout.split(coord, eye_size);
+                eyes[eye].screen_to_world(coord)
+            }
+        }
+    }
+
+    /// The columns and rows of the view `coord` is in, when the image is
+    /// split into views that samples shouldn't be filtered across: the eyes
+    /// of a stereo pair, or the faces of a cubemap. `None` when the image is
+    /// one view.
+    pub fn region(&self, coord: (usize, usize)) -> Option<(Range<usize>, Range<usize>)> {
+        match self.lens {
+            CameraLens::Cubemap {
+                image_size: (width, height),
+                ..
+            } => {
+                // The columns whose middles are on the same face.
+                let edge =
+                    |face: usize| (face as f64 * width as f64 / 6. - 0.5).ceil().max(0.) as usize;
+                let face = ((coord.0 as f64 + 0.5) * 6. / width as f64) as usize;
+                let face = face.min(5);
+                Some((edge(face)..edge(face + 1), 0..height))
+            }
+            CameraLens::Stereo {
+                ref eyes,
+                layout,
+                eye_size,
+                image_size,
+            } => {
+                let (eye, coord) = layout.split(coord, eye_size);
+                let within = eyes[eye].region(coord);
+                Some(layout.region(eye, eye_size, image_size, within))
+            }
+            _ => None,
         }
     }
 
-    pub fn screen_to_world_sampled(&self, coord: (usize, usize)) -> Ray {
-        let ray = self.screen_to_world(coord);
+    /// A ray through a random point in a pixel.
+    pub fn screen_to_world_sampled(&self, coord: (usize, usize)) -> Option<Ray> {
+        self.screen_to_world_at(coord, sampling::uniform_pair())
+    }
+
+    /// A ray through `offset` within a pixel, measured from its top left
+    /// corner in fractions of a pixel.
+    pub fn screen_to_world_at(&self, coord: (usize, usize), offset: (f64, f64)) -> Option<Ray> {
+        // The offset from the middle of the pixel.
+        let px = offset.0 - 0.5;
+        let py = offset.1 - 0.5;
 
         match self.lens {
-            CameraLens::Orthogonal {
+            CameraLens::Perspective {
                 pixel_delta_u,
                 pixel_delta_v,
                 ..
             } => {
-                // Returns a random point in the square surrounding a pixel at the origin.
-                let px = -0.5 + random::<f64>();
-                let py = -0.5 + random::<f64>();
+                let ray = self.screen_to_world(coord)?;
                 let sample = (pixel_delta_u * px) + (pixel_delta_v * py);
 
-                Ray {
+                Some(Ray {
                     origin: ray.origin,
                     direction: ray.direction + sample,
-                }
+                })
+            }
+            CameraLens::Orthographic {
+                pixel_delta_u,
+                pixel_delta_v,
+                ..
+            } => {
+                let ray = self.screen_to_world(coord)?;
+                let sample = (pixel_delta_u * px) + (pixel_delta_v * py);
+
+                Some(Ray {
+                    origin: ray.origin + sample,
+                    direction: ray.direction,
+                })
+            }
+            CameraLens::Realistic { .. } => self.realistic_ray(coord, offset),
+            CameraLens::Equirectangular { .. }
+            | CameraLens::Fisheye { .. }
+            | CameraLens::Cubemap { .. } => self.panoramic_ray(coord, offset),
+            CameraLens::Stereo {
+                ref eyes,
+                layout,
+                eye_size,
+                ..
+            } => {
+                let (eye, coord) = layout.split(coord, eye_size);
+                eyes[eye].screen_to_world_at(coord, offset)
             }
         }
     }
-}
 
-fn random_in_unit_disk() -> Vec3 {
-    let dist = rand::distributions::Uniform::new(-1., 1.);
+    /// Picks a point on the lens for light from `point` to reach the camera
+    /// through. This is `None` when `point` is out of view, or the camera
+    /// can't be reached this way: only perspective and thin lens cameras
+    /// can.
+    pub fn sample_lens(&self, point: &Vec3) -> Option<LensSample> {
+        let CameraLens::Perspective {
+            defocus_disc_uv,
+            aperture,
+            ..
+        } = self.lens
+        else {
+            return None;
+        };
 
-    loop {
-        let p = Vec3::new((
-            rand::thread_rng().sample(dist),
-            rand::thread_rng().sample(dist),
-            0.,
-        ));
+        let lens_point = match defocus_disc_uv {
+            Some((defocus_disc_u, defocus_disc_v)) => {
+                let p = aperture.sample();
+                self.eye.origin + (defocus_disc_u * p.0) + (defocus_disc_v * p.1)
+            }
+            None => self.eye.origin,
+        };
+
+        let offset = *point - lens_point;
+        let distance = offset.length();
+        let ray = Ray::new(lens_point, offset / distance);
+        let position = self.image_position(&ray)?;
+        let cos_theta = ray.direction.dot(&self.eye.direction);
+
+        Some(LensSample {
+            point: lens_point,
+            position,
+            importance: self.importance(&ray),
+            pdf: distance * distance / (cos_theta * self.lens_area()),
+        })
+    }
 
-        if p.length_squared() < 1. {
-            return p;
+    /// The density with which the camera chooses `ray`'s direction, per
+    /// unit solid angle, given where it crosses the lens. It's zero for rays
+    /// out of view and for cameras `sample_lens` can't reach.
+    pub fn pdf_direction(&self, ray: &Ray) -> f64 {
+        let ray = Ray::new(ray.origin, ray.direction.unit());
+        if self.image_position(&ray).is_none() {
+            return 0.;
         }
+
+        let cos_theta = ray.direction.dot(&self.eye.direction);
+        1. / (self.view_area() * cos_theta.powi(3))
+    }
+
+    /// The importance of light arriving along `ray` at a perspective
+    /// camera's lens, chosen so that light traced to the camera gives each
+    /// pixel the same value as the camera's own rays do.
+    fn importance(&self, ray: &Ray) -> f64 {
+        let cos_theta = ray.direction.dot(&self.eye.direction);
+        1. / (self.view_area() * self.lens_area() * cos_theta.powi(4))
+    }
+
+    /// Where a unit `ray` leaving a perspective camera's lens lands on the
+    /// image, in pixels from its top left corner, if it's in view.
+    fn image_position(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let CameraLens::Perspective {
+            pixel0_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+            image_size,
+            ..
+        } = self.lens
+        else {
+            return None;
+        };
+
+        let cos_theta = ray.direction.dot(&self.eye.direction);
+        if cos_theta <= 0. {
+            return None;
+        }
+
+        // Every ray through a point on the plane in focus came from the same
+        // point on the image, wherever it crossed the lens.
+        let focus_dist = (pixel0_loc - self.eye.origin).dot(&self.eye.direction);
+        let focused = ray.at(focus_dist / cos_theta) - pixel0_loc;
+        let x = focused.dot(&pixel_delta_u) / pixel_delta_u.length_squared() + 0.5;
+        let y = focused.dot(&pixel_delta_v) / pixel_delta_v.length_squared() + 0.5;
+
+        let in_view =
+            (0. ..image_size.0 as f64).contains(&x) && (0. ..image_size.1 as f64).contains(&y);
+        in_view.then_some((x, y))
+    }
+
+    /// The area of a perspective camera's image, on a plane a unit distance
+    /// in front of the lens.
+    fn view_area(&self) -> f64 {
+        let CameraLens::Perspective {
+            pixel0_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+            image_size,
+            ..
+        } = self.lens
+        else {
+            return 0.;
+        };
+
+        let focus_dist = (pixel0_loc - self.eye.origin).dot(&self.eye.direction);
+        let width = pixel_delta_u.length() * image_size.0 as f64 / focus_dist;
+        let height = pixel_delta_v.length() * image_size.1 as f64 / focus_dist;
+        width * height
+    }
+
+    /// The area of a thin lens, or one for a pinhole, whose single point is
+    /// always the one chosen.
+    fn lens_area(&self) -> f64 {
+        match self.lens {
+            CameraLens::Perspective {
+                defocus_disc_uv: Some((defocus_disc_u, defocus_disc_v)),
+                aperture,
+                ..
+            } => defocus_disc_u.length() * defocus_disc_v.length() * aperture.area(),
+            _ => 1.,
+        }
+    }
+
+    /// A ray from `offset` within a pixel on the film of a realistic lens,
+    /// through a random point on the lens.
+    fn realistic_ray(&self, coord: (usize, usize), offset: (f64, f64)) -> Option<Ray> {
+        let CameraLens::Realistic {
+            frame,
+            image_size,
+            film_size,
+            ref lens,
+        } = self.lens
+        else {
+            return None;
+        };
+
+        // The lens turns the image upside down and back to front, so the
+        // film is too.
+        let x = (coord.0 as f64 + offset.0) / image_size.0 as f64;
+        let y = (coord.1 as f64 + offset.1) / image_size.1 as f64;
+        let point = Vec3::new(((0.5 - x) * film_size.0, (y - 0.5) * film_size.1, 0.));
+
+        let ray = lens.sample_ray(point)?;
+        Some(Ray {
+            origin: self.eye.origin + frame.to_world(&ray.origin),
+            direction: frame.to_world(&ray.direction),
+        })
+    }
+
+    /// The ray from the eye through `offset` within a pixel of a panoramic
+    /// lens.
+    fn panoramic_ray(&self, coord: (usize, usize), offset: (f64, f64)) -> Option<Ray> {
+        let (CameraLens::Equirectangular { image_size, frame }
+        | CameraLens::Fisheye {
+            image_size, frame, ..
+        }
+        | CameraLens::Cubemap { image_size, frame }) = self.lens
+        else {
+            return None;
+        };
+
+        let point = (
+            (coord.0 as f64 + offset.0) / image_size.0 as f64,
+            (coord.1 as f64 + offset.1) / image_size.1 as f64,
+        );
+        let direction = self.lens.panoramic_direction(point)?;
+
+        // Omni-directional stereo eyes sit either side of the middle of the
+        // head, turning with it to face each direction.
+        let local = frame.to_local(&direction);
+        let sideways = Vec3::new((-local.z(), 0., local.x()));
+        let origin = if self.eye_offset != 0. && sideways.length_squared() > 0. {
+            self.eye.origin + frame.to_world(&sideways.unit()) * self.eye_offset
+        } else {
+            self.eye.origin
+        };
+
+        Some(Ray { origin, direction })
     }
 }
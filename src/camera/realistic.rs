@@ -0,0 +1,453 @@
+use std::fs;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use super::Aperture;
+use crate::{geometry::refract, ray::Ray, vec::Vec3};
+
+/// One spherical surface of a lens, in metres.
+#[derive(Clone, Copy)]
+struct LensElement {
+    /// Radius of curvature, positive when the surface bulges towards the
+    /// scene. Zero for the aperture stop, which is flat.
+    radius: f64,
+    /// Distance along the axis to the next surface towards the film.
+    thickness: f64,
+    /// Refractive index of what lies between this surface and the next.
+    ior: f64,
+    aperture_radius: f64,
+}
+
+/// A lens made of spherical surfaces, as read from a prescription file.
+///
+/// Each line of the file is one surface, from the front of the lens to the
+/// back, given by four numbers in millimetres: the radius of curvature, the
+/// thickness to the next surface, the refractive index after it and the
+/// aperture diameter. A radius of zero marks the aperture stop and an index
+/// of zero means air, and lines starting with `#` are comments. This is the
+/// format pbrt's realistic camera reads. Like textures, prescriptions are
+/// referred to by path in scene files.
+///
+/// The lens is traced in its own space, with the film at `z = 0` and the
+/// scene towards `-z`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LensPrescription {
+    path: String,
+    elements: Vec<LensElement>,
+    /// Where rays from the film are aimed on the back of the lens, which
+    /// depends on how far they start from the middle of the film.
+    pupil: Pupil,
+}
+
+/// Discs on the back of the lens that rays from the film are aimed at, one
+/// for each band of distance from the middle of the film. The discs are all
+/// the same size and only move, so every ray is aimed with the same density.
+#[derive(Clone)]
+struct Pupil {
+    radius: f64,
+    /// How far each band's disc is from the axis, towards the side of the
+    /// film the ray starts on.
+    offsets: Vec<f64>,
+    band_width: f64,
+}
+
+impl Pupil {
+    /// A point on the disc that rays from `point` on the film are aimed at,
+    /// where `(x, y)` is in the unit disc.
+    fn target(&self, point: Vec3, (x, y): (f64, f64)) -> (f64, f64) {
+        let distance = (point.x() * point.x() + point.y() * point.y()).sqrt();
+        let band = ((distance / self.band_width) as usize).min(self.offsets.len() - 1);
+        let (cos, sin) = if distance > 0. {
+            (point.x() / distance, point.y() / distance)
+        } else {
+            (1., 0.)
+        };
+
+        let offset = self.offsets[band];
+        (
+            offset * cos + x * self.radius,
+            offset * sin + y * self.radius,
+        )
+    }
+}
+
+impl LensPrescription {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)?;
+
+        let mut elements = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(str::parse::<f64>)
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("reading line {} of '{path}'", number + 1))?;
+            let &[radius, thickness, ior, aperture] = values.as_slice() else {
+                bail!(
+                    "line {} of '{path}' should have a radius, thickness, index and aperture",
+                    number + 1
+                );
+            };
+
+            elements.push(LensElement {
+                radius: radius / 1000.,
+                thickness: thickness / 1000.,
+                ior: if ior == 0. { 1. } else { ior },
+                aperture_radius: aperture / 2000.,
+            });
+        }
+
+        if elements.is_empty() {
+            bail!("'{path}' has no lens elements");
+        }
+
+        let pupil = Pupil {
+            radius: elements.last().unwrap().aperture_radius,
+            offsets: vec![0.],
+            band_width: f64::INFINITY,
+        };
+        Ok(Self {
+            path: path.to_string(),
+            elements,
+            pupil,
+        })
+    }
+
+    /// Sets the aperture stop's diameter, in millimetres, if the lens has
+    /// one.
+    pub(super) fn stop_down(&mut self, diameter_mm: f64) {
+        if let Some(stop) = self.elements.iter_mut().find(|e| e.radius == 0.) {
+            stop.aperture_radius = diameter_mm / 2000.;
+        }
+    }
+
+    /// Moves the lens along its axis so that things `focus_dist` from the
+    /// film are sharp, by approximating it as a thick lens. `height` is how
+    /// far from the axis to trace the rays that the approximation is taken
+    /// from, which should be small compared with the lens. Fails if those
+    /// rays don't make it through, or the lens can't focus that close.
+    pub(super) fn focus(&mut self, focus_dist: f64, height: f64) -> anyhow::Result<()> {
+        let front = self.front_z();
+        let rear = self.rear().thickness;
+
+        let from_scene = Ray::new(Vec3::new((height, 0., front - 1.)), Vec3::new((0., 0., 1.)));
+        let from_film = Ray::new(Vec3::new((height, 0., 1. - rear)), Vec3::new((0., 0., -1.)));
+        let blocked = || {
+            format!(
+                "light near the axis doesn't make it through '{}'",
+                self.path
+            )
+        };
+        let (film_principal, film_focal) = self
+            .trace_from_scene(from_scene)
+            .and_then(|out| cardinal_points(&from_scene, &out))
+            .with_context(blocked)?;
+        let (scene_principal, _) = self
+            .trace_from_film(from_film)
+            .and_then(|out| cardinal_points(&from_film, &out))
+            .with_context(blocked)?;
+
+        // The distance to move the lens away from the film so that the
+        // thick lens equation holds between the focus plane and the film.
+        let focal_length = film_focal - film_principal;
+        let z = -focus_dist;
+        let c = (scene_principal - z - film_principal)
+            * (scene_principal - z - 4. * focal_length - film_principal);
+        if c < 0. {
+            bail!("'{}' can't focus as close as {focus_dist} m", self.path);
+        }
+        let delta = 0.5 * (scene_principal - z + film_principal - c.sqrt());
+
+        self.elements.last_mut().unwrap().thickness = rear + delta;
+        Ok(())
+    }
+
+    /// Shrinks where rays are aimed to the parts of the back of the lens
+    /// that light gets through from each band of distance from the middle
+    /// of the film, out to `film_radius`, so that fewer rays are wasted on
+    /// the aperture stop. Every ray is still aimed with the same density, so
+    /// the image only gets evenly darker, which `transmittance` makes up for.
+    pub(super) fn fit_pupil(&mut self, film_radius: f64) {
+        const BANDS: usize = 16;
+        const POINTS_PER_BAND: usize = 4;
+
+        let rear = self.rear().aperture_radius;
+        let band_width = film_radius / BANDS as f64;
+
+        // Each band's disc sits in the middle of where light gets through
+        // from points across it, along the line from the middle of the film.
+        let bands: Vec<Vec<(f64, f64)>> = (0..BANDS)
+            .map(|band| {
+                let mut through = Vec::new();
+                for i in 0..=POINTS_PER_BAND {
+                    let distance = band_width * (band as f64 + i as f64 / POINTS_PER_BAND as f64);
+                    let point = Vec3::new((distance, 0., 0.));
+                    through.extend(
+                        pupil_grid()
+                            .map(|(x, y)| (x * rear, y * rear))
+                            .filter(|&target| self.passes(point, target)),
+                    );
+                }
+                through
+            })
+            .collect();
+
+        let offsets: Vec<f64> = bands
+            .iter()
+            .map(|through| {
+                let (min, max) = through
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &(x, _)| {
+                        (min.min(x), max.max(x))
+                    });
+                if through.is_empty() {
+                    0.
+                } else {
+                    (min + max) / 2.
+                }
+            })
+            .collect();
+
+        let radius = bands
+            .iter()
+            .zip(&offsets)
+            .flat_map(|(through, offset)| {
+                through
+                    .iter()
+                    .map(move |(x, y)| ((x - offset).powi(2) + y * y).sqrt())
+            })
+            .fold(0., f64::max);
+
+        // Leave room for the gaps between the points of the grid.
+        let margin = 2. * rear * 2. / PUPIL_STEPS as f64;
+        self.pupil = Pupil {
+            radius: (radius + margin).min(rear),
+            offsets,
+            band_width,
+        };
+    }
+
+    /// The fraction of rays from the middle of the film that make it through
+    /// the lens, from an even grid across the disc they're aimed at.
+    pub(super) fn transmittance(&self) -> f64 {
+        let origin = Vec3::new((0., 0., 0.));
+        let (through, total) = pupil_grid().fold((0, 0), |(through, total), target| {
+            let target = self.pupil.target(origin, target);
+            (
+                through + usize::from(self.passes(origin, target)),
+                total + 1,
+            )
+        });
+
+        through as f64 / total as f64
+    }
+
+    /// A ray from `point` on the film towards a random point on the back of
+    /// the lens, followed out into the scene. `None` if the lens blocks it.
+    pub(super) fn sample_ray(&self, point: Vec3) -> Option<Ray> {
+        let target = self.pupil.target(point, Aperture::Circle.sample());
+        self.trace_from_film(self.ray_to_pupil(point, target))
+    }
+
+    /// Whether light gets through the lens from `point` on the film via
+    /// `target` on the back of it.
+    fn passes(&self, point: Vec3, target: (f64, f64)) -> bool {
+        self.trace_from_film(self.ray_to_pupil(point, target))
+            .is_some()
+    }
+
+    fn ray_to_pupil(&self, point: Vec3, (x, y): (f64, f64)) -> Ray {
+        let target = Vec3::new((x, y, -self.rear().thickness));
+        Ray::new(point, target - point)
+    }
+
+    fn rear(&self) -> &LensElement {
+        self.elements.last().unwrap()
+    }
+
+    /// Where the front of the lens is along the axis.
+    fn front_z(&self) -> f64 {
+        -self.elements.iter().map(|e| e.thickness).sum::<f64>()
+    }
+
+    fn trace_from_film(&self, mut ray: Ray) -> Option<Ray> {
+        let mut z = 0.;
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z -= element.thickness;
+            let outside = if i > 0 { self.elements[i - 1].ior } else { 1. };
+            ray = interface(element, z, ray, element.ior / outside)?;
+        }
+
+        Some(ray)
+    }
+
+    fn trace_from_scene(&self, mut ray: Ray) -> Option<Ray> {
+        let mut z = self.front_z();
+        for (i, element) in self.elements.iter().enumerate() {
+            let outside = if i > 0 { self.elements[i - 1].ior } else { 1. };
+            ray = interface(element, z, ray, outside / element.ior)?;
+            z += element.thickness;
+        }
+
+        Some(ray)
+    }
+}
+
+const PUPIL_STEPS: usize = 64;
+
+/// An even grid of points across the unit disc.
+fn pupil_grid() -> impl Iterator<Item = (f64, f64)> {
+    let coordinate = |i: usize| 2. * (i as f64 + 0.5) / PUPIL_STEPS as f64 - 1.;
+
+    (0..PUPIL_STEPS)
+        .flat_map(move |i| (0..PUPIL_STEPS).map(move |j| (coordinate(i), coordinate(j))))
+        .filter(|(x, y)| x * x + y * y < 1.)
+}
+
+/// Passes `ray` through `element`, whose vertex is at `z` on the axis,
+/// where `eta` is the ratio of the refractive index it leaves over the one
+/// it enters. `None` if it misses the element or reflects off it.
+fn interface(element: &LensElement, z: f64, ray: Ray, eta: f64) -> Option<Ray> {
+    let (t, normal) = if element.radius == 0. {
+        let t = (z - ray.origin.z()) / ray.direction.z();
+        (t, None)
+    } else {
+        let (t, normal) = intersect_sphere(element.radius, z + element.radius, &ray)?;
+        (t, Some(normal))
+    };
+    if t.is_nan() || t <= 0. {
+        return None;
+    }
+
+    let point = ray.at(t);
+    if point.x() * point.x() + point.y() * point.y() > element.aperture_radius.powi(2) {
+        return None;
+    }
+
+    let direction = match normal {
+        Some(normal) => {
+            let wo = -ray.direction.unit();
+            let normal = if normal.dot(&wo) < 0. {
+                -normal
+            } else {
+                normal
+            };
+            refract(&wo, &normal, eta)?
+        }
+        None => ray.direction,
+    };
+
+    Some(Ray::new(point, direction))
+}
+
+/// Where `ray` meets the sphere of `radius` around `centre` on the axis, on
+/// the side facing it, along with the sphere's normal there.
+fn intersect_sphere(radius: f64, centre: f64, ray: &Ray) -> Option<(f64, Vec3)> {
+    let origin = ray.origin - Vec3::new((0., 0., centre));
+    let a = ray.direction.length_squared();
+    let half_b = origin.dot(&ray.direction);
+    let c = origin.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (near, far) = ((-half_b - root) / a, (-half_b + root) / a);
+
+    // A surface bulging towards the film is first met from the film side,
+    // and one bulging towards the scene from the scene side.
+    let closer = (ray.direction.z() > 0.) != (radius < 0.);
+    let t = if closer { near } else { far };
+
+    Some((t, (origin + ray.direction * t).unit()))
+}
+
+/// The principal plane and focal point, as distances along the axis, of a
+/// ray `ray_in` parallel to the axis coming out of the lens as `ray_out`.
+fn cardinal_points(ray_in: &Ray, ray_out: &Ray) -> Option<(f64, f64)> {
+    if ray_out.direction.x() == 0. {
+        return None;
+    }
+
+    let focal = -ray_out.origin.x() / ray_out.direction.x();
+    let principal = (ray_in.origin.x() - ray_out.origin.x()) / ray_out.direction.x();
+
+    Some((ray_out.at(principal).z(), ray_out.at(focal).z()))
+}
+
+impl TryFrom<String> for LensPrescription {
+    type Error = anyhow::Error;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        Self::load(&path)
+    }
+}
+
+impl From<LensPrescription> for String {
+    fn from(lens: LensPrescription) -> Self {
+        lens.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn double_gauss() -> LensPrescription {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/scenes/lenses/double_gauss_50mm.txt"
+        );
+        LensPrescription::load(path).unwrap()
+    }
+
+    /// The ray from the middle of the film through the back of `lens`,
+    /// `height` from the axis, out into the scene.
+    fn on_axis_ray(lens: &LensPrescription, height: f64) -> Ray {
+        let origin = Vec3::new((0., 0., 0.));
+        lens.trace_from_film(lens.ray_to_pupil(origin, (height, 0.)))
+            .unwrap()
+    }
+
+    #[test]
+    fn light_from_the_focus_distance_meets_on_the_film() {
+        for focus_dist in [0.5, 1., 3.] {
+            let mut lens = double_gauss();
+            lens.focus(focus_dist, 1e-5).unwrap();
+
+            // Followed back from the middle of the film, light crosses the
+            // axis where it came from.
+            let ray = on_axis_ray(&lens, 1e-4);
+            let crossing = ray.at(-ray.origin.x() / ray.direction.x()).z();
+            assert!(
+                (crossing + focus_dist).abs() < 1e-4 * focus_dist,
+                "focused at {focus_dist} m, but sharp at {} m",
+                -crossing
+            );
+        }
+    }
+
+    #[test]
+    fn light_from_far_away_arrives_parallel() {
+        let mut lens = double_gauss();
+        lens.focus(1e6, 1e-5).unwrap();
+
+        for height in [1e-4, 5e-4, 1e-3] {
+            let direction = on_axis_ray(&lens, height).direction.unit();
+            assert!(direction.x().abs() < 1e-5, "{direction:?}");
+        }
+    }
+
+    #[test]
+    fn lenses_cant_focus_closer_than_four_focal_lengths() {
+        assert!(double_gauss().focus(0.15, 1e-5).is_err());
+    }
+}
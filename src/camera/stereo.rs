@@ -0,0 +1,267 @@
+use std::ops::Range;
+
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
+
+use super::{Camera, CameraConfig};
+use crate::vec::Vec3;
+
+/// Where each eye's view goes in a stereo image.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, clap::ValueEnum)]
+pub enum StereoLayout {
+    /// The left eye on the left half and the right eye on the right.
+    #[default]
+    SideBySide,
+    /// The left eye on the top half and the right eye on the bottom.
+    TopBottom,
+}
+
+/// How a perspective stereo rig makes the eyes' views meet at the
+/// convergence distance, where things appear at the depth of the screen.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Convergence {
+    /// Parallel eyes whose views are shifted sideways to overlap, which
+    /// avoids the vertical mismatch of toe-in.
+    #[default]
+    OffAxis,
+    /// Each eye turned inwards to look at the convergence point.
+    ToeIn,
+}
+
+impl StereoLayout {
+    /// The size of each eye's view within an image of `image_size`, which
+    /// has to leave each eye at least a pixel.
+    pub(super) fn eye_size(
+        self,
+        (width, height): (usize, usize),
+    ) -> anyhow::Result<(usize, usize)> {
+        let eye_size = match self {
+            StereoLayout::SideBySide => (width / 2, height),
+            StereoLayout::TopBottom => (width, height / 2),
+        };
+        ensure!(
+            eye_size.0 > 0 && eye_size.1 > 0,
+            "a {width}x{height} image is too small to split between two eyes"
+        );
+
+        Ok(eye_size)
+    }
+
+    /// Which eye sees `coord` in the stereo image, and where in that eye's
+    /// view it is.
+    pub(super) fn split(
+        self,
+        (x, y): (usize, usize),
+        eye_size: (usize, usize),
+    ) -> (usize, (usize, usize)) {
+        match self {
+            StereoLayout::SideBySide => {
+                let eye = (x / eye_size.0).min(1);
+                (eye, ((x - eye * eye_size.0).min(eye_size.0 - 1), y))
+            }
+            StereoLayout::TopBottom => {
+                let eye = (y / eye_size.1).min(1);
+                (eye, (x, (y - eye * eye_size.1).min(eye_size.1 - 1)))
+            }
+        }
+    }
+
+    /// The columns and rows of `eye`'s view in an image of `image_size`,
+    /// including any left over when the image doesn't split evenly. If the
+    /// eye's own view is split, this is narrowed to the part `within` it.
+    pub(super) fn region(
+        self,
+        eye: usize,
+        eye_size: (usize, usize),
+        (width, height): (usize, usize),
+        within: Option<(Range<usize>, Range<usize>)>,
+    ) -> (Range<usize>, Range<usize>) {
+        let (columns, rows) = match self {
+            StereoLayout::SideBySide if eye == 0 => (0..eye_size.0, 0..height),
+            StereoLayout::SideBySide => (eye_size.0..width, 0..height),
+            StereoLayout::TopBottom if eye == 0 => (0..width, 0..eye_size.1),
+            StereoLayout::TopBottom => (0..width, eye_size.1..height),
+        };
+        let Some((within_columns, within_rows)) = within else {
+            return (columns, rows);
+        };
+
+        // Whatever's left over goes with the part at the eye's far edge.
+        let narrow = |outer: Range<usize>, inner: Range<usize>, size: usize| {
+            let end = if inner.end >= size {
+                outer.end
+            } else {
+                outer.start + inner.end
+            };
+            outer.start + inner.start..end
+        };
+        (
+            narrow(columns, within_columns, eye_size.0),
+            narrow(rows, within_rows, eye_size.1),
+        )
+    }
+}
+
+/// The camera for one eye of a stereo rig around `config`, `offset` to the
+/// right of it (negative for the left eye).
+pub(super) fn eye_camera(
+    config: &CameraConfig,
+    offset: f64,
+    convergence: Convergence,
+    convergence_distance: Option<f64>,
+    eye_size: (usize, usize),
+) -> anyhow::Result<Camera> {
+    let camera = match *config {
+        CameraConfig::Perspective {
+            look_from,
+            look_at,
+            up,
+            focus_dist,
+            ..
+        }
+        | CameraConfig::Physical {
+            look_from,
+            look_at,
+            up,
+            focus_dist,
+            ..
+        } => {
+            let (forward, right) = forward_and_right(look_from, look_at, up);
+            let distance = (look_at - look_from).length();
+            let convergence_distance = convergence_distance.unwrap_or(distance);
+            let focus_dist = focus_dist.unwrap_or(distance);
+            let eye = look_from + right * offset;
+
+            match convergence {
+                Convergence::ToeIn => Camera::new(
+                    moved(
+                        config,
+                        eye,
+                        look_from + forward * convergence_distance,
+                        focus_dist,
+                    ),
+                    eye_size,
+                )?,
+                Convergence::OffAxis => {
+                    let mut camera = Camera::new(
+                        moved(config, eye, look_at + right * offset, focus_dist),
+                        eye_size,
+                    )?;
+
+                    // Shift the view back towards the middle, so that both
+                    // eyes' views line up at the convergence distance.
+                    camera.shift_viewport(right * (-offset * focus_dist / convergence_distance));
+                    camera
+                }
+            }
+        }
+
+        // Parallel rays can't be shifted to converge, so orthographic eyes
+        // are always turned in.
+        CameraConfig::Orthographic {
+            look_from,
+            look_at,
+            up,
+            view_width,
+        } => {
+            let (forward, right) = forward_and_right(look_from, look_at, up);
+            let convergence_distance =
+                convergence_distance.unwrap_or_else(|| (look_at - look_from).length());
+
+            Camera::new(
+                CameraConfig::Orthographic {
+                    look_from: look_from + right * offset,
+                    look_at: look_from + forward * convergence_distance,
+                    up,
+                    view_width,
+                },
+                eye_size,
+            )?
+        }
+
+        // The film can't be moved off the lens's axis without the image
+        // falling off the edge of the lens, so realistic eyes are always
+        // turned in.
+        CameraConfig::Realistic {
+            look_from,
+            look_at,
+            up,
+            focus_dist,
+            ..
+        } => {
+            let (forward, right) = forward_and_right(look_from, look_at, up);
+            let distance = (look_at - look_from).length();
+            let convergence_distance = convergence_distance.unwrap_or(distance);
+
+            Camera::new(
+                moved(
+                    config,
+                    look_from + right * offset,
+                    look_from + forward * convergence_distance,
+                    focus_dist.unwrap_or(distance),
+                ),
+                eye_size,
+            )?
+        }
+
+        // Panoramic views see all around, so there's no one direction to
+        // offset the eyes in. Instead each ray starts from where an eye
+        // would be when turned to face it, for omni-directional stereo.
+        CameraConfig::Equirectangular { .. }
+        | CameraConfig::Fisheye { .. }
+        | CameraConfig::Cubemap { .. } => {
+            let mut camera = Camera::new(config.clone(), eye_size)?;
+            camera.eye_offset = offset;
+            camera
+        }
+
+        CameraConfig::Stereo { .. } => Camera::new(config.clone(), eye_size)?,
+
+        CameraConfig::Animated { .. } => eye_camera(
+            &config.at_frame(0.),
+            offset,
+            convergence,
+            convergence_distance,
+            eye_size,
+        )?,
+    };
+
+    Ok(camera)
+}
+
+/// A copy of a lens camera's `config` looking from `look_from` to
+/// `look_at`, still focused `focus_dist` away.
+fn moved(config: &CameraConfig, look_from: Vec3, look_at: Vec3, focus_dist: f64) -> CameraConfig {
+    let mut config = config.clone();
+    match &mut config {
+        CameraConfig::Perspective {
+            look_from: from,
+            look_at: at,
+            focus_dist: focus,
+            ..
+        }
+        | CameraConfig::Physical {
+            look_from: from,
+            look_at: at,
+            focus_dist: focus,
+            ..
+        }
+        | CameraConfig::Realistic {
+            look_from: from,
+            look_at: at,
+            focus_dist: focus,
+            ..
+        } => {
+            *from = look_from;
+            *at = look_at;
+            *focus = Some(focus_dist);
+        }
+        _ => unreachable!("only lens cameras are moved"),
+    }
+    config
+}
+
+fn forward_and_right(look_from: Vec3, look_at: Vec3, up: Vec3) -> (Vec3, Vec3) {
+    let forward = (look_at - look_from).unit();
+    (forward, forward.cross(&up).unit())
+}
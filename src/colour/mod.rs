@@ -22,6 +22,21 @@ impl Colour {
         self.0.z()
     }
 
+    /// Relative luminance using the Rec. 709 primaries.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+
+    pub fn max_component(&self) -> f64 {
+        self.r().max(self.g()).max(self.b())
+    }
+
+    /// Raises e to the power of each channel, e.g. for Beer-Lambert
+    /// attenuation.
+    pub fn exp(&self) -> Colour {
+        Colour::new(self.r().exp(), self.g().exp(), self.b().exp())
+    }
+
     #[allow(unused)]
     pub fn white() -> Colour {
         Colour::new(1., 1., 1.)
@@ -74,6 +89,14 @@ impl std::ops::AddAssign<Colour> for Colour {
     }
 }
 
+impl std::ops::Sub<Colour> for Colour {
+    type Output = Colour;
+
+    fn sub(self, rhs: Colour) -> Self::Output {
+        Colour(self.0 - rhs.0)
+    }
+}
+
 impl std::ops::Mul<f64> for Colour {
     type Output = Colour;
 
@@ -0,0 +1,119 @@
+/// A piecewise-constant 1D distribution over `[0, 1)`, built from
+/// non-negative weights.
+#[derive(Clone)]
+pub struct Distribution1D {
+    weights: Vec<f64>,
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution1D {
+    pub fn new(weights: Vec<f64>) -> Self {
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        let mut total = 0.;
+        cdf.push(0.);
+        for w in &weights {
+            total += w;
+            cdf.push(total);
+        }
+
+        if total > 0. {
+            for c in cdf.iter_mut() {
+                *c /= total;
+            }
+        } else {
+            // All weights are zero, so fall back to uniform.
+            let n = weights.len() as f64;
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n;
+            }
+        }
+
+        Self {
+            weights,
+            cdf,
+            total,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    /// Picks a bucket for `xi` in `[0, 1)`, returning the bucket index and
+    /// the offset of `xi` within that bucket, also in `[0, 1)`.
+    pub fn sample(&self, xi: f64) -> (usize, f64) {
+        let index = self
+            .cdf
+            .partition_point(|c| *c <= xi)
+            .saturating_sub(1)
+            .min(self.len() - 1);
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0. {
+            (xi - self.cdf[index]) / width
+        } else {
+            0.
+        };
+
+        (index, offset.clamp(0., 1. - f64::EPSILON))
+    }
+
+    /// Probability density of the bucket at `index`, with respect to the
+    /// unit interval.
+    pub fn pdf(&self, index: usize) -> f64 {
+        (self.cdf[index + 1] - self.cdf[index]) * self.len() as f64
+    }
+}
+
+/// A piecewise-constant 2D distribution over the unit square, sampled by
+/// choosing a row from the marginal and then a column within that row.
+#[derive(Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Builds the distribution from a row-major grid of weights.
+    pub fn new(weights: &[f64], width: usize, height: usize) -> Self {
+        let rows: Vec<_> = weights
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.total()).collect());
+
+        Self { rows, marginal }
+    }
+
+    /// Maps two uniform numbers to a point in the unit square, returning it
+    /// with its density.
+    pub fn sample(&self, xi: (f64, f64)) -> ((f64, f64), f64) {
+        let (y, dy) = self.marginal.sample(xi.1);
+        let (x, dx) = self.rows[y].sample(xi.0);
+
+        let point = (
+            (x as f64 + dx) / self.rows[y].len() as f64,
+            (y as f64 + dy) / self.marginal.len() as f64,
+        );
+
+        (point, self.marginal.pdf(y) * self.rows[y].pdf(x))
+    }
+
+    pub fn pdf(&self, point: (f64, f64)) -> f64 {
+        let y = ((point.1 * self.marginal.len() as f64) as usize).min(self.marginal.len() - 1);
+        let row = &self.rows[y];
+        let x = ((point.0 * row.len() as f64) as usize).min(row.len() - 1);
+
+        self.marginal.pdf(y) * row.pdf(x)
+    }
+}
@@ -0,0 +1,233 @@
+mod distribution;
+mod sky;
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Colour, texture::ImageTexture, vec::Vec3};
+
+pub use self::{
+    distribution::{Distribution1D, Distribution2D},
+    sky::Sky,
+};
+
+/// Light arriving from infinitely far away, seen by any ray that escapes the
+/// scene.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Environment {
+    /// Blends vertically between two colours by ray direction.
+    Gradient { bottom: Colour, top: Colour },
+
+    /// The same radiance in every direction.
+    Constant { colour: Colour },
+
+    /// An equirectangular (latitude/longitude) image, such as an `.hdr` or
+    /// `.exr` light probe.
+    Map {
+        map: EnvironmentMap,
+        /// Rotation about the Y axis in degrees.
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+
+    /// A physically based daylight sky with a sun disc.
+    Sky(Sky),
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::Gradient {
+            bottom: Colour::new(1.0, 1.0, 1.0),
+            top: Colour::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Environment {
+    /// Radiance arriving along a ray travelling in `direction`.
+    pub fn radiance(&self, direction: &Vec3) -> Colour {
+        match self {
+            Environment::Gradient { bottom, top } => {
+                let a = (direction.unit().y() + 1.0) * 0.5;
+                *bottom * (1.0 - a) + *top * a
+            }
+
+            Environment::Constant { colour } => *colour,
+
+            Environment::Map {
+                map,
+                rotation,
+                intensity,
+            } => {
+                let uv = direction_to_uv(&direction.unit(), rotation.to_radians());
+                map.texture.sample((uv.0, 1. - uv.1)) * *intensity
+            }
+
+            Environment::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    /// Picks a direction towards the environment, returning it with the
+    /// radiance arriving from it and its solid-angle density. Maps are
+    /// importance-sampled by luminance; everything else is sampled uniformly.
+    pub fn sample(&self, xi: (f64, f64)) -> (Vec3, Colour, f64) {
+        match self {
+            Environment::Map { map, rotation, .. } => {
+                let (uv, pdf_uv) = map.distribution.sample(xi);
+                let direction = uv_to_direction(uv, rotation.to_radians());
+
+                let sin_theta = (uv.1 * PI).sin();
+                let pdf = if sin_theta > 0. {
+                    pdf_uv / (2. * PI * PI * sin_theta)
+                } else {
+                    0.
+                };
+
+                (direction, self.radiance(&direction), pdf)
+            }
+
+            Environment::Sky(sky) => sky.sample(xi),
+
+            _ => {
+                let direction = Vec3::on_unit_sphere(xi);
+                (direction, self.radiance(&direction), 1. / (4. * PI))
+            }
+        }
+    }
+
+    /// Solid-angle density with which `sample` would choose `direction`.
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        match self {
+            Environment::Map { map, rotation, .. } => {
+                let uv = direction_to_uv(&direction.unit(), rotation.to_radians());
+                let sin_theta = (uv.1 * PI).sin();
+                if sin_theta > 0. {
+                    map.distribution.pdf(uv) / (2. * PI * PI * sin_theta)
+                } else {
+                    0.
+                }
+            }
+
+            Environment::Sky(sky) => sky.pdf(direction),
+
+            _ => 1. / (4. * PI),
+        }
+    }
+}
+
+/// Maps a unit direction to equirectangular coordinates, where `u` wraps
+/// around the Y axis with -Z at the centre of the image and `v` runs from
+/// straight up (0) to straight down (1).
+fn direction_to_uv(direction: &Vec3, rotation: f64) -> (f64, f64) {
+    let phi = f64::atan2(direction.x(), -direction.z()) - rotation;
+    let theta = direction.y().clamp(-1., 1.).acos();
+
+    ((0.5 + phi / (2. * PI)).rem_euclid(1.), theta / PI)
+}
+
+fn uv_to_direction(uv: (f64, f64), rotation: f64) -> Vec3 {
+    let phi = (uv.0 - 0.5) * 2. * PI + rotation;
+    let theta = uv.1 * PI;
+
+    Vec3::new((
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    ))
+}
+
+/// An equirectangular image along with a luminance-weighted distribution for
+/// importance sampling it. Like `ImageTexture`, it is referred to by path in
+/// scene files.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EnvironmentMap {
+    texture: ImageTexture,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let texture = ImageTexture::load(path)?;
+        let (width, height) = (texture.width(), texture.height());
+
+        // Rows near the poles cover less solid angle, so weight them down.
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                weights.push(texture.texel(x, y).luminance() * sin_theta);
+            }
+        }
+
+        let distribution = Distribution2D::new(&weights, width, height);
+
+        Ok(Self {
+            texture,
+            distribution,
+        })
+    }
+}
+
+impl TryFrom<String> for EnvironmentMap {
+    type Error = anyhow::Error;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        Self::load(&path)
+    }
+}
+
+impl From<EnvironmentMap> for String {
+    fn from(map: EnvironmentMap) -> Self {
+        map.texture.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_direction(a: &Vec3, b: &Vec3) {
+        assert!((*a - *b).length() < 1e-9, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn directions_round_trip_through_uv() {
+        for rotation in [0., 0.7, -2.5, PI] {
+            for i in 1..12 {
+                for j in 0..16 {
+                    let theta = PI * i as f64 / 12.;
+                    let phi = 2. * PI * j as f64 / 16.;
+                    let direction = Vec3::new((
+                        theta.sin() * phi.cos(),
+                        theta.cos(),
+                        theta.sin() * phi.sin(),
+                    ));
+
+                    let uv = direction_to_uv(&direction, rotation);
+                    assert!((0. ..1.).contains(&uv.0) && (0. ..=1.).contains(&uv.1));
+                    assert_same_direction(&uv_to_direction(uv, rotation), &direction);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn centre_of_the_image_looks_down_minus_z() {
+        assert_same_direction(&uv_to_direction((0.5, 0.5), 0.), &Vec3::new((0., 0., -1.)));
+        assert_same_direction(&uv_to_direction((0.5, 0.), 0.), &Vec3::new((0., 1., 0.)));
+
+        // Rotating the map turns what it shows about the Y axis.
+        let turned = uv_to_direction((0.5, 0.5), PI / 2.);
+        assert_same_direction(&turned, &Vec3::new((1., 0., 0.)));
+        let uv = direction_to_uv(&turned, PI / 2.);
+        assert!((uv.0 - 0.5).abs() < 1e-9 && (uv.1 - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,283 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Colour, vec::Vec3};
+
+/// Angular radius of the sun as seen from the ground.
+const SUN_ANGULAR_RADIUS: f64 = 0.2665 * PI / 180.;
+
+/// Luminance of the sun's disc above the atmosphere, in kcd/m² to match the
+/// units of the sky model.
+const SUN_LUMINANCE: f64 = 1.6e6;
+
+/// Chance of pointing a light sample at the sun rather than the whole sky.
+const SUN_SAMPLE_PROBABILITY: f64 = 0.5;
+
+/// The Preetham analytic daylight model: a clear sky lit by the sun, with a
+/// flat ground below the horizon.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sky {
+    /// Angle of the sun above the horizon, in degrees.
+    pub sun_elevation: f64,
+
+    /// Compass direction of the sun in degrees, clockwise from -Z towards +X.
+    #[serde(default)]
+    pub sun_azimuth: f64,
+
+    /// Haziness of the atmosphere, from 2 (very clear) to about 10 (hazy).
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f64,
+
+    #[serde(default = "default_ground_albedo")]
+    pub ground_albedo: Colour,
+
+    /// Scale applied to the model's radiance, which is in kcd/m².
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+
+    /// Whether the sun's disc is visible and sampled, rather than only its
+    /// effect on the sky.
+    #[serde(default = "default_sun_disc")]
+    pub sun_disc: bool,
+}
+
+fn default_turbidity() -> f64 {
+    3.0
+}
+
+fn default_ground_albedo() -> Colour {
+    Colour::new(0.3, 0.3, 0.3)
+}
+
+fn default_intensity() -> f64 {
+    0.1
+}
+
+fn default_sun_disc() -> bool {
+    true
+}
+
+impl Sky {
+    pub fn sun_direction(&self) -> Vec3 {
+        let elevation = self.sun_elevation.to_radians();
+        let azimuth = self.sun_azimuth.to_radians();
+
+        Vec3::new((
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        ))
+    }
+
+    /// Whether the sun's disc is shown and at least partly above the
+    /// horizon.
+    fn sun_visible(&self) -> bool {
+        self.sun_disc && self.sun_elevation.to_radians() > -SUN_ANGULAR_RADIUS
+    }
+
+    pub fn radiance(&self, direction: &Vec3) -> Colour {
+        let direction = direction.unit();
+        let sun = self.sun_direction();
+
+        let colour = if direction.y() >= 0. {
+            let mut colour = self.sky_radiance(&direction);
+            if self.sun_visible() && direction.dot(&sun) >= SUN_ANGULAR_RADIUS.cos() {
+                colour += self.sun_radiance();
+            }
+            colour
+        } else {
+            self.ground_radiance()
+        };
+
+        colour * self.intensity
+    }
+
+    /// Picks a direction, favouring the sun's disc when it is visible. See
+    /// `Environment::sample`.
+    pub fn sample(&self, xi: (f64, f64)) -> (Vec3, Colour, f64) {
+        let direction = if !self.sun_visible() {
+            Vec3::on_unit_sphere(xi)
+        } else if xi.0 < SUN_SAMPLE_PROBABILITY {
+            let xi = (xi.0 / SUN_SAMPLE_PROBABILITY, xi.1);
+            sample_cone(&self.sun_direction(), SUN_ANGULAR_RADIUS.cos(), xi)
+        } else {
+            let xi = (
+                (xi.0 - SUN_SAMPLE_PROBABILITY) / (1. - SUN_SAMPLE_PROBABILITY),
+                xi.1,
+            );
+            Vec3::on_unit_sphere(xi)
+        };
+
+        (direction, self.radiance(&direction), self.pdf(&direction))
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let uniform = 1. / (4. * PI);
+        if !self.sun_visible() {
+            return uniform;
+        }
+
+        let cos_max = SUN_ANGULAR_RADIUS.cos();
+        let cone = if direction.unit().dot(&self.sun_direction()) >= cos_max {
+            1. / (2. * PI * (1. - cos_max))
+        } else {
+            0.
+        };
+
+        SUN_SAMPLE_PROBABILITY * cone + (1. - SUN_SAMPLE_PROBABILITY) * uniform
+    }
+
+    fn sky_radiance(&self, direction: &Vec3) -> Colour {
+        let t = self.turbidity;
+        let theta_s = self.sun_zenith_angle();
+
+        // Keep rays just above the horizon from blowing up the Perez terms.
+        let cos_theta = direction.y().max(0.01);
+        let gamma = direction.dot(&self.sun_direction()).clamp(-1., 1.).acos();
+
+        let (zenith_y, zenith_x, zenith_yc) = zenith(t, theta_s);
+
+        let [perez_y, perez_x, perez_yc] = perez_coefficients(t);
+        let luminance = zenith_y * perez(&perez_y, cos_theta, gamma) / perez(&perez_y, 1., theta_s);
+        let x = zenith_x * perez(&perez_x, cos_theta, gamma) / perez(&perez_x, 1., theta_s);
+        let y = zenith_yc * perez(&perez_yc, cos_theta, gamma) / perez(&perez_yc, 1., theta_s);
+
+        xy_luminance_to_rgb(x, y, luminance)
+    }
+
+    /// The sun's disc after attenuation by Rayleigh and aerosol scattering
+    /// along its path through the atmosphere.
+    fn sun_radiance(&self) -> Colour {
+        let theta_s = self.sun_zenith_angle();
+
+        // Relative optical air mass, per Kasten.
+        let m = 1. / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |wavelength_um: f64| {
+            let rayleigh = 0.008735 * wavelength_um.powf(-4.08);
+            let aerosol = beta * wavelength_um.powf(-1.3);
+            (-m * (rayleigh + aerosol)).exp()
+        };
+
+        Colour::new(
+            transmittance(0.68),
+            transmittance(0.55),
+            transmittance(0.44),
+        ) * SUN_LUMINANCE
+    }
+
+    /// A diffuse ground lit by the sun and, approximately, by a sky as bright
+    /// everywhere as it is at the zenith.
+    fn ground_radiance(&self) -> Colour {
+        let sun = self.sun_direction();
+        let mut irradiance = self.sky_radiance(&Vec3::new((0., 1., 0.))) * PI;
+
+        if self.sun_disc && sun.y() > 0. {
+            let solid_angle = 2. * PI * (1. - SUN_ANGULAR_RADIUS.cos());
+            irradiance += self.sun_radiance() * (solid_angle * sun.y());
+        }
+
+        self.ground_albedo * irradiance / PI
+    }
+
+    fn sun_zenith_angle(&self) -> f64 {
+        (PI / 2. - self.sun_elevation.to_radians()).clamp(0., PI / 2.)
+    }
+}
+
+/// The Perez sky luminance distribution function.
+fn perez(coefficients: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = *coefficients;
+    (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+/// Perez coefficients for luminance and the two chromaticity channels, as
+/// fitted by Preetham et al.
+fn perez_coefficients(t: f64) -> [[f64; 5]; 3] {
+    [
+        [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ],
+        [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ],
+        [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ],
+    ]
+}
+
+/// Luminance (kcd/m²) and xy chromaticity at the zenith.
+fn zenith(t: f64, theta_s: f64) -> (f64, f64, f64) {
+    let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+    let t_vec = [t * t, t, 1.];
+    let theta_vec = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.];
+
+    let x_matrix = [
+        [0.00166, -0.00375, 0.00209, 0.],
+        [-0.02903, 0.06377, -0.03202, 0.00394],
+        [0.11693, -0.21196, 0.06052, 0.25886],
+    ];
+    let y_matrix = [
+        [0.00275, -0.00610, 0.00317, 0.],
+        [-0.04214, 0.08970, -0.04153, 0.00516],
+        [0.15346, -0.26756, 0.06670, 0.26688],
+    ];
+
+    let apply = |matrix: &[[f64; 4]; 3]| {
+        (0..3)
+            .map(|i| t_vec[i] * (0..4).map(|j| matrix[i][j] * theta_vec[j]).sum::<f64>())
+            .sum::<f64>()
+    };
+
+    (luminance.max(0.), apply(&x_matrix), apply(&y_matrix))
+}
+
+/// Converts CIE xyY to linear sRGB.
+fn xy_luminance_to_rgb(x: f64, y: f64, luminance: f64) -> Colour {
+    if y <= 0. {
+        return Colour::black();
+    }
+
+    let big_x = x / y * luminance;
+    let big_z = (1. - x - y) / y * luminance;
+
+    Colour::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.),
+    )
+}
+
+/// Uniformly samples directions within `acos(cos_max)` of `axis`.
+fn sample_cone(axis: &Vec3, cos_max: f64, xi: (f64, f64)) -> Vec3 {
+    let cos_theta = 1. - xi.0 * (1. - cos_max);
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let phi = 2. * PI * xi.1;
+
+    let helper = if axis.x().abs() > 0.9 {
+        Vec3::new((0., 1., 0.))
+    } else {
+        Vec3::new((1., 0., 0.))
+    };
+    let u = helper.cross(axis).unit();
+    let v = axis.cross(&u);
+
+    u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + *axis * cos_theta
+}
@@ -0,0 +1,85 @@
+use std::f64::consts::PI;
+
+/// How much a sample counts towards a pixel, by its distance from the
+/// pixel's centre. Filters are separable, applied along each axis in turn,
+/// and are zero beyond their radius.
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum Filter {
+    /// Every sample within the radius counts equally. With a radius of half
+    /// a pixel, each pixel is the average of the samples inside it.
+    #[default]
+    Box,
+    /// Falls off linearly to the radius.
+    Tent,
+    /// A Gaussian with a standard deviation of a third of the radius,
+    /// shifted down to meet zero there. Smooth, but slightly blurry.
+    Gaussian,
+    /// The Mitchell-Netravali cubic with B = C = 1/3, which sharpens edges
+    /// with small negative lobes while keeping ringing low.
+    Mitchell,
+    /// A sinc windowed by a wider sinc, which is sharp but rings around
+    /// hard edges.
+    Lanczos,
+}
+
+impl Filter {
+    /// The radius, in pixels, the filter is usually used with.
+    pub fn default_radius(self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.,
+            Filter::Lanczos => 3.,
+        }
+    }
+
+    /// The filter's weight for a sample `(x, y)` pixels from a pixel's centre.
+    pub fn evaluate(self, (x, y): (f64, f64), radius: f64) -> f64 {
+        self.evaluate_1d(x, radius) * self.evaluate_1d(y, radius)
+    }
+
+    fn evaluate_1d(self, x: f64, radius: f64) -> f64 {
+        let x = x.abs();
+        if x >= radius {
+            return 0.;
+        }
+
+        match self {
+            Filter::Box => 1.,
+            Filter::Tent => 1. - x / radius,
+            Filter::Gaussian => {
+                let sigma = radius / 3.;
+                let gaussian = |x: f64| (-x * x / (2. * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.)
+            }
+            Filter::Mitchell => mitchell(2. * x / radius, 1. / 3., 1. / 3.),
+            Filter::Lanczos => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+/// The Mitchell-Netravali cubic, which spans `[-2, 2]`.
+fn mitchell(x: f64, b: f64, c: f64) -> f64 {
+    let x = x.abs();
+    let value = if x < 1. {
+        (12. - 9. * b - 6. * c) * x.powi(3) + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b)
+    } else if x < 2. {
+        (-b - 6. * c) * x.powi(3)
+            + (6. * b + 30. * c) * x * x
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c)
+    } else {
+        0.
+    };
+
+    value / 6.
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
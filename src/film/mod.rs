@@ -0,0 +1,156 @@
+mod filter;
+
+use std::{collections::HashMap, ops::Range};
+
+use crate::colour::Colour;
+
+pub use self::filter::Filter;
+
+#[derive(Clone, Copy, Default)]
+struct FilmPixel {
+    /// Samples added so far, each scaled by its weight.
+    weighted: Colour,
+    weight: f64,
+}
+
+/// An image built up from samples at any point on it. Each sample is
+/// splatted into every pixel whose centre is within the filter's radius and
+/// in the same view, weighted by the filter, and each pixel ends up as the
+/// weighted average of its samples.
+///
+/// Light traced from its sources to the camera can land anywhere on the
+/// image, and is splatted into the pixel it lands in without filtering or
+/// averaging, on top of the samples.
+///
+/// A film can hold only some of the image's rows, so that parts of it can be
+/// rendered separately and merged together.
+pub struct Film {
+    image_size: (usize, usize),
+    filter: Filter,
+    radius: f64,
+    rows: Range<usize>,
+    pixels: Vec<FilmPixel>,
+    /// Light splatted into each pixel, by its index in the whole image,
+    /// whichever rows the film holds.
+    splats: HashMap<usize, Colour>,
+}
+
+impl Film {
+    pub fn new(image_size: (usize, usize), filter: Filter, radius: f64) -> Self {
+        Self::with_rows(image_size, filter, radius, 0..image_size.1)
+    }
+
+    fn with_rows(
+        image_size: (usize, usize),
+        filter: Filter,
+        radius: f64,
+        rows: Range<usize>,
+    ) -> Self {
+        Self {
+            image_size,
+            filter,
+            radius,
+            pixels: vec![FilmPixel::default(); image_size.0 * rows.len()],
+            rows,
+            splats: HashMap::new(),
+        }
+    }
+
+    /// An empty film for the samples taken in `rows`, which also holds the
+    /// rows either side that they splat into.
+    pub fn tile(&self, rows: Range<usize>) -> Film {
+        let reach = (self.radius - 0.5).ceil().max(0.) as usize;
+        let rows = rows.start.saturating_sub(reach)..(rows.end + reach).min(self.image_size.1);
+
+        Film::with_rows(self.image_size, self.filter, self.radius, rows)
+    }
+
+    /// Adds the colour seen at `position`, measured in pixels from the top
+    /// left corner of the image. If the image is split into separate views,
+    /// `region` is the columns and rows of the one the sample is in, which
+    /// it isn't splatted beyond.
+    pub fn add_sample(
+        &mut self,
+        (x, y): (f64, f64),
+        colour: Colour,
+        region: Option<(Range<usize>, Range<usize>)>,
+    ) {
+        let (columns, rows) = region.unwrap_or((0..self.image_size.0, 0..self.image_size.1));
+        let rows = rows.start.max(self.rows.start)..rows.end.min(self.rows.end);
+
+        // The pixels whose centres are within the radius of the sample.
+        let span = |centre: f64, range: Range<usize>| {
+            let start = (centre - 0.5 - self.radius).ceil().max(range.start as f64) as usize;
+            let end = ((centre - 0.5 + self.radius).floor() + 1.).min(range.end as f64);
+            start..(end.max(start as f64) as usize)
+        };
+
+        for row in span(y, rows) {
+            for column in span(x, columns.clone()) {
+                let offset = (x - (column as f64 + 0.5), y - (row as f64 + 0.5));
+                let weight = self.filter.evaluate(offset, self.radius);
+                if weight == 0. {
+                    continue;
+                }
+
+                let pixel = &mut self.pixels[(row - self.rows.start) * self.image_size.0 + column];
+                pixel.weighted += colour * weight;
+                pixel.weight += weight;
+            }
+        }
+    }
+
+    /// Adds light landing at `position` to the pixel there, as it is.
+    pub fn add_splat(&mut self, (x, y): (f64, f64), colour: Colour) {
+        let (width, height) = self.image_size;
+        if !(0. ..width as f64).contains(&x) || !(0. ..height as f64).contains(&y) {
+            return;
+        }
+
+        let index = y as usize * width + x as usize;
+        *self.splats.entry(index).or_insert_with(Colour::black) += colour;
+    }
+
+    /// Adds the samples and light splatted into `tile`.
+    pub fn merge(&mut self, tile: &Film) {
+        for (&index, &colour) in &tile.splats {
+            *self.splats.entry(index).or_insert_with(Colour::black) += colour;
+        }
+
+        let width = self.image_size.0;
+        for row in tile.rows.clone() {
+            if !self.rows.contains(&row) {
+                continue;
+            }
+
+            let source = &tile.pixels[(row - tile.rows.start) * width..][..width];
+            let target = &mut self.pixels[(row - self.rows.start) * width..][..width];
+            for (target, source) in target.iter_mut().zip(source) {
+                target.weighted += source.weighted;
+                target.weight += source.weight;
+            }
+        }
+    }
+
+    /// The finished image, with pixels no samples reached left black.
+    pub fn into_image(self) -> Vec<Colour> {
+        let first = self.rows.start * self.image_size.0;
+        self.pixels
+            .into_iter()
+            .enumerate()
+            .map(|(index, pixel)| {
+                let splatted = self
+                    .splats
+                    .get(&(first + index))
+                    .copied()
+                    .unwrap_or_else(Colour::black);
+
+                if pixel.weight > 0. {
+                    pixel.weighted / pixel.weight + splatted
+                } else {
+                    splatted
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,114 @@
+use std::f64::consts::PI;
+
+use crate::{colour::Colour, hit::Hit, spectrum::SampledWavelengths, vec::Vec3};
+
+/// How light scatters at a surface. Directions are in world space, unit
+/// length and point away from the surface: `wo` towards the viewer and `wi`
+/// towards where light arrives from.
+///
+/// Implement this for your own type and wrap it in `Material::Custom` to
+/// render with a material the crate doesn't provide.
+pub trait Bsdf {
+    /// Chooses a direction to continue a path leaving towards `wo`, or
+    /// `None` if the light is absorbed.
+    fn sample(
+        &self,
+        hit: &Hit,
+        wo: &Vec3,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Option<BsdfSample>;
+
+    /// The BSDF value for light arriving from `wi` and leaving towards `wo`,
+    /// not including the cosine term. Zero for delta lobes.
+    fn eval(&self, hit: &Hit, wo: &Vec3, wi: &Vec3) -> Colour;
+
+    /// Solid-angle density with which `sample` chooses `wi`, over the lobes
+    /// that `eval` accounts for.
+    fn pdf(&self, hit: &Hit, wo: &Vec3, wi: &Vec3) -> f64;
+
+    /// The kinds of lobe `sample` may choose from.
+    fn flags(&self) -> BsdfFlags;
+
+    /// Light given off by the surface itself towards `wo`.
+    fn emitted(&self, _hit: &Hit, _wo: &Vec3) -> Colour {
+        Colour::black()
+    }
+}
+
+pub struct BsdfSample {
+    pub wi: Vec3,
+    /// The BSDF times the cosine term, divided by the density of choosing
+    /// `wi`.
+    pub weight: Colour,
+    /// What `Bsdf::pdf` gives for `wi`, or zero when `flags.delta` is set.
+    pub pdf: f64,
+    pub flags: BsdfFlags,
+    /// Set when `wi` refracts into a scattering interior, which the
+    /// integrator follows until the light leaves the surface again.
+    pub interior: Option<Interior>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BsdfFlags {
+    /// Set for perfectly specular lobes, and any others that `eval` and
+    /// `pdf` don't account for, which light sampling can't reach. On a
+    /// material, set only when every lobe is like this.
+    pub delta: bool,
+    /// Set when light passes through the surface rather than reflecting.
+    pub transmission: bool,
+}
+
+impl BsdfFlags {
+    pub const SPECULAR: BsdfFlags = BsdfFlags {
+        delta: true,
+        transmission: false,
+    };
+    pub const SPECULAR_TRANSMISSION: BsdfFlags = BsdfFlags {
+        delta: true,
+        transmission: true,
+    };
+}
+
+impl BsdfSample {
+    /// A sample from a delta lobe, which has no density to report.
+    pub fn delta(wi: Vec3, weight: Colour, transmission: bool) -> Self {
+        BsdfSample {
+            wi,
+            weight,
+            pdf: 0.,
+            flags: BsdfFlags {
+                delta: true,
+                transmission,
+            },
+            interior: None,
+        }
+    }
+}
+
+/// Scattering properties of the inside of a subsurface material.
+pub struct Interior {
+    pub extinction: [f64; 3],
+    pub albedo: [f64; 3],
+    pub anisotropy: f64,
+    pub ior: f64,
+}
+
+impl Interior {
+    pub fn new(mean_free_path: Colour, albedo: Colour, anisotropy: f64, ior: f64) -> Self {
+        let channels = |c: Colour| [c.r(), c.g(), c.b()];
+
+        Self {
+            extinction: channels(mean_free_path).map(|mfp| 1. / mfp.max(1e-6)),
+            albedo: channels(albedo).map(|a| a.clamp(0., 1.)),
+            anisotropy,
+            ior,
+        }
+    }
+}
+
+/// A direction in the local shading frame with density `cos(theta) / pi`.
+pub fn sample_cosine_hemisphere(xi: (f64, f64)) -> Vec3 {
+    let r = xi.0.sqrt();
+    let phi = 2. * PI * xi.1;
+    Vec3::new((r * phi.cos(), r * phi.sin(), (1. - xi.0).max(0.).sqrt()))
+}
@@ -1,22 +1,764 @@
+use std::{f64::consts::PI, sync::Arc};
+
 use serde::{Deserialize, Serialize};
 
-use crate::colour::Colour;
+use super::{
+    bsdf::{sample_cosine_hemisphere, Bsdf, BsdfFlags, BsdfSample, Interior},
+    microfacet::{fresnel_dielectric, fresnel_schlick, refract, Fresnel, Ggx},
+    principled::Principled,
+};
+use crate::{
+    colour::Colour,
+    hit::Hit,
+    sampling,
+    spectrum::{Dispersion, SampledWavelengths},
+    texture::{Factor, ImageTexture},
+    vec::{Frame, Vec3},
+};
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[allow(unused)]
 pub enum Material {
     // debugging
-    ScreenSpaceGradient,
+    DirectionGradient,
     NormalSpaceGradient,
-    SolidColour { colour: Colour },
+    SolidColour {
+        colour: Colour,
+    },
 
     // diffuse models
-    Diffuse { colour: Colour, albedo: f64 },
-    Lambertian { colour: Colour, albedo: f64 },
+    Diffuse {
+        colour: Colour,
+        albedo: f64,
+    },
+    Lambertian {
+        colour: Colour,
+        albedo: f64,
+    },
 
     // reflective models
-    Metal { tint: Colour, scatter: f64 },
+    Metal {
+        tint: Colour,
+        scatter: f64,
+    },
+    /// A rough metal using the GGX microfacet model. Anisotropy stretches
+    /// highlights along the surface's `dpdu` direction.
+    Conductor {
+        fresnel: Fresnel,
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64,
+    },
 
     // transmissive models
-    Dialectric { ior: f64 },
+    /// Glass and other clear materials, reflecting or refracting according to
+    /// the Fresnel equations. `tint` filters light as it enters; thin-walled
+    /// dielectrics model a sheet such as a window, which reflects from both
+    /// faces but doesn't bend light passing through it. When rendering
+    /// spectrally, `dispersion` replaces `ior`.
+    Dialectric {
+        ior: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dispersion: Option<Dispersion>,
+        #[serde(default = "Colour::white")]
+        tint: Colour,
+        #[serde(default)]
+        thin_walled: bool,
+        /// Absorption coefficient of each colour per unit distance travelled
+        /// inside, attenuating light following the Beer-Lambert law.
+        #[serde(default = "Colour::black")]
+        absorption: Colour,
+    },
+    /// Translucent materials like skin, wax and marble, where light refracts
+    /// into the surface and scatters around inside before leaving it again.
+    /// The shape must be closed.
+    Subsurface {
+        /// Fraction of light surviving each scattering event inside.
+        albedo: Colour,
+        /// Average distance light travels inside between scattering events,
+        /// per colour.
+        mean_free_path: Colour,
+        #[serde(default = "default_subsurface_ior")]
+        ior: f64,
+        #[serde(default)]
+        anisotropy: f64,
+    },
+
+    /// An invisible boundary that light passes straight through, used to
+    /// enclose a participating medium.
+    Interface,
+
+    // light sources
+    /// A surface giving off the same radiance in every direction from its
+    /// front side, and reflecting nothing.
+    Emissive {
+        radiance: Colour,
+    },
+
+    // layered models
+    /// Base colour, metallic, roughness, specular, transmission, clearcoat and
+    /// sheen, as used by glTF and most PBR authoring tools.
+    Principled(Principled),
+    /// Blends two materials, choosing `b` with probability `factor`.
+    Mix {
+        a: Box<Material>,
+        b: Box<Material>,
+        factor: Factor,
+    },
+    /// A clear dielectric coat, like varnish or car paint lacquer, over any
+    /// other material.
+    Coated {
+        base: Box<Material>,
+        #[serde(default = "default_coat_ior")]
+        coat_ior: f64,
+        #[serde(default)]
+        coat_roughness: f64,
+    },
+
+    // surface detail, perturbing the shading normal of another material
+    NormalMapped {
+        base: Box<Material>,
+        normal_map: ImageTexture,
+        #[serde(default = "default_strength")]
+        strength: f64,
+    },
+    BumpMapped {
+        base: Box<Material>,
+        bump_map: ImageTexture,
+        /// World-space height of a white texel.
+        scale: f64,
+    },
+
+    /// A material implemented outside this crate. It can't be saved to or
+    /// loaded from a scene file.
+    #[serde(skip)]
+    Custom(Arc<dyn Bsdf + Send + Sync>),
+}
+
+fn default_subsurface_ior() -> f64 {
+    1.4
+}
+
+fn default_coat_ior() -> f64 {
+    1.5
+}
+
+fn default_strength() -> f64 {
+    1.0
+}
+
+impl Bsdf for Material {
+    fn sample(
+        &self,
+        hit: &Hit,
+        wo: &Vec3,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Option<BsdfSample> {
+        match *self {
+            Material::DirectionGradient
+            | Material::NormalSpaceGradient
+            | Material::SolidColour { .. }
+            | Material::Emissive { .. } => None,
+
+            Material::Diffuse { colour, albedo } => Some(BsdfSample {
+                wi: Vec3::random_on_hemisphere(&hit.normal),
+                weight: colour * albedo,
+                pdf: 1. / (2. * PI),
+                flags: BsdfFlags::default(),
+                interior: None,
+            }),
+            Material::Lambertian { colour, albedo } => {
+                let frame = Frame::from_normal(hit.normal);
+                let wi = sample_cosine_hemisphere(sampling::uniform_pair());
+                Some(BsdfSample {
+                    wi: frame.to_world(&wi),
+                    weight: colour * albedo,
+                    pdf: wi.z() / PI,
+                    flags: BsdfFlags::default(),
+                    interior: None,
+                })
+            }
+
+            Material::Metal { tint, scatter } => {
+                let reflected = (-*wo).reflect(&hit.normal);
+                let wi = reflected + Vec3::random_unit_vector() * scatter;
+
+                // absorb rays that get scattered into the material
+                if wi.dot(&hit.normal) <= 0. {
+                    return None;
+                }
+
+                if scatter <= 0. {
+                    return Some(BsdfSample::delta(reflected, tint, false));
+                }
+
+                let wi = wi.unit();
+                Some(BsdfSample {
+                    wi,
+                    weight: tint,
+                    pdf: fuzzy_reflection_pdf(&reflected, scatter, &wi),
+                    flags: BsdfFlags::default(),
+                    interior: None,
+                })
+            }
+            Material::Conductor {
+                ref fresnel,
+                roughness,
+                anisotropy,
+            } => {
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                let wo = frame.to_local(wo);
+
+                let ggx = Ggx::new(roughness, anisotropy);
+                let (wi, weight) = ggx.sample_reflection(fresnel, &wo, sampling::uniform_pair())?;
+
+                Some(BsdfSample {
+                    wi: frame.to_world(&wi),
+                    weight,
+                    pdf: ggx.pdf_reflection(&wo, &wi),
+                    flags: BsdfFlags::default(),
+                    interior: None,
+                })
+            }
+
+            Material::Dialectric {
+                ior,
+                dispersion,
+                tint,
+                thin_walled,
+                ..
+            } => {
+                let ior = match (dispersion, wavelengths) {
+                    (Some(dispersion), Some(wavelengths)) => {
+                        // Each wavelength would bend a different way, so
+                        // only the hero can carry on along this path.
+                        wavelengths.terminate_secondaries();
+                        dispersion.ior(wavelengths.hero())
+                    }
+                    _ => ior,
+                };
+
+                let refraction_ratio = if hit.front_face { 1.0 / ior } else { ior };
+                let cos_theta = f64::min(wo.dot(&hit.normal), 1.0);
+
+                let mut reflectance = fresnel_schlick(cos_theta, refraction_ratio);
+                if thin_walled {
+                    // Sum the light bouncing back and forth inside the sheet.
+                    reflectance = 2. * reflectance / (1. + reflectance);
+                }
+
+                // A thin sheet's two surfaces cancel out each other's bending.
+                let refracted = if thin_walled {
+                    Some(-*wo)
+                } else {
+                    refract(wo, &hit.normal, refraction_ratio)
+                };
+
+                match refracted.filter(|_| sampling::uniform() >= reflectance) {
+                    Some(wi) => {
+                        let tint = if hit.front_face || thin_walled {
+                            tint
+                        } else {
+                            Colour::white()
+                        };
+                        Some(BsdfSample::delta(wi, tint, true))
+                    }
+                    None => Some(BsdfSample::delta(
+                        (-*wo).reflect(&hit.normal),
+                        Colour::white(),
+                        false,
+                    )),
+                }
+            }
+            Material::Subsurface {
+                albedo,
+                mean_free_path,
+                ior,
+                anisotropy,
+            } => {
+                let cos_theta = f64::min(wo.dot(&hit.normal), 1.0);
+
+                let reflected =
+                    !hit.front_face || sampling::uniform() < fresnel_schlick(cos_theta, 1.0 / ior);
+                if reflected {
+                    let wi = (-*wo).reflect(&hit.normal);
+                    return Some(BsdfSample::delta(wi, Colour::white(), false));
+                }
+
+                let wi = refract(wo, &hit.normal, 1.0 / ior)?;
+                Some(BsdfSample {
+                    interior: Some(Interior::new(mean_free_path, albedo, anisotropy, ior)),
+                    ..BsdfSample::delta(wi, Colour::white(), true)
+                })
+            }
+            Material::Interface => Some(BsdfSample::delta(-*wo, Colour::white(), true)),
+
+            Material::Principled(ref principled) => {
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                let wo = frame.to_local(wo);
+
+                let (wi, weight) = principled.sample(&wo, hit.front_face)?;
+                let pdf = principled.pdf(&wo, &wi, hit.front_face);
+
+                Some(BsdfSample {
+                    wi: frame.to_world(&wi),
+                    weight,
+                    pdf,
+                    flags: BsdfFlags {
+                        delta: pdf <= 0.,
+                        transmission: wi.z() < 0.,
+                    },
+                    interior: None,
+                })
+            }
+            Material::Mix {
+                ref a,
+                ref b,
+                ref factor,
+            } => {
+                let chosen = if sampling::uniform() < factor.evaluate(hit.uv) {
+                    b
+                } else {
+                    a
+                };
+
+                self.reweighted(hit, wo, chosen.sample(hit, wo, wavelengths)?)
+            }
+            Material::Coated {
+                ref base,
+                coat_ior,
+                coat_roughness,
+            } => {
+                if !hit.front_face {
+                    return base.sample(hit, wo, wavelengths);
+                }
+
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                let wo_local = frame.to_local(wo);
+
+                // Reflect off the coat as often as it would reflect light
+                // head-on to the surface, and otherwise let the light through
+                // to the base.
+                let coat_probability = fresnel_dielectric(wo_local.z(), 1. / coat_ior);
+                if sampling::uniform() >= coat_probability {
+                    return self.reweighted(hit, wo, base.sample(hit, wo, wavelengths)?);
+                }
+
+                let ggx = Ggx::new(coat_roughness, 0.);
+                let h = ggx.sample_h(&wo_local, sampling::uniform_pair());
+                let wi = (-wo_local).reflect(&h);
+                if wi.z() <= 0. {
+                    return None;
+                }
+
+                self.reweighted(
+                    hit,
+                    wo,
+                    BsdfSample {
+                        wi: frame.to_world(&wi),
+                        weight: Colour::black(),
+                        pdf: 0.,
+                        flags: BsdfFlags::default(),
+                        interior: None,
+                    },
+                )
+            }
+
+            Material::NormalMapped {
+                ref base,
+                ref normal_map,
+                strength,
+            } => {
+                let normal = normal_mapped_normal(hit, normal_map, strength);
+                let sample = base.sample(&Hit { normal, ..*hit }, wo, wavelengths)?;
+                self.reweighted(hit, wo, sample)
+            }
+            Material::BumpMapped {
+                ref base,
+                ref bump_map,
+                scale,
+            } => {
+                let normal = bump_mapped_normal(hit, bump_map, scale);
+                let sample = base.sample(&Hit { normal, ..*hit }, wo, wavelengths)?;
+                self.reweighted(hit, wo, sample)
+            }
+
+            Material::Custom(ref bsdf) => bsdf.sample(hit, wo, wavelengths),
+        }
+    }
+
+    fn eval(&self, hit: &Hit, wo: &Vec3, wi: &Vec3) -> Colour {
+        let cos_i = wi.dot(&hit.normal);
+
+        match *self {
+            Material::DirectionGradient
+            | Material::NormalSpaceGradient
+            | Material::SolidColour { .. }
+            | Material::Dialectric { .. }
+            | Material::Subsurface { .. }
+            | Material::Interface
+            | Material::Emissive { .. } => Colour::black(),
+
+            // Reflects as much light towards every direction as it receives,
+            // rather than falling off with the cosine.
+            Material::Diffuse { colour, albedo } if cos_i > 0. => {
+                colour * (albedo / (2. * PI * cos_i))
+            }
+            Material::Lambertian { colour, albedo } if cos_i > 0. => colour * (albedo / PI),
+            Material::Diffuse { .. } | Material::Lambertian { .. } => Colour::black(),
+
+            Material::Metal { tint, scatter } => {
+                if scatter <= 0. || cos_i <= 0. {
+                    return Colour::black();
+                }
+                let reflected = (-*wo).reflect(&hit.normal);
+                tint * (fuzzy_reflection_pdf(&reflected, scatter, wi) / cos_i)
+            }
+            Material::Conductor {
+                ref fresnel,
+                roughness,
+                anisotropy,
+            } => {
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                Ggx::new(roughness, anisotropy).eval_reflection(
+                    fresnel,
+                    &frame.to_local(wo),
+                    &frame.to_local(wi),
+                )
+            }
+
+            Material::Principled(ref principled) => {
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                principled.eval(&frame.to_local(wo), &frame.to_local(wi), hit.front_face)
+            }
+            Material::Mix {
+                ref a,
+                ref b,
+                ref factor,
+            } => {
+                let factor = factor.evaluate(hit.uv);
+                a.eval(hit, wo, wi) * (1. - factor) + b.eval(hit, wo, wi) * factor
+            }
+            Material::Coated {
+                ref base,
+                coat_ior,
+                coat_roughness,
+            } => {
+                if !hit.front_face {
+                    return base.eval(hit, wo, wi);
+                }
+
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                let (wo_local, wi_local) = (frame.to_local(wo), frame.to_local(wi));
+
+                let coat = Fresnel::Dielectric { eta: 1. / coat_ior };
+                let reflected =
+                    Ggx::new(coat_roughness, 0.).eval_reflection(&coat, &wo_local, &wi_local);
+                let transmitted = 1. - fresnel_dielectric(wo_local.z(), 1. / coat_ior);
+
+                reflected + base.eval(hit, wo, wi) * transmitted
+            }
+
+            Material::NormalMapped {
+                ref base,
+                ref normal_map,
+                strength,
+            } => {
+                let normal = normal_mapped_normal(hit, normal_map, strength);
+                base.eval(&Hit { normal, ..*hit }, wo, wi)
+            }
+            Material::BumpMapped {
+                ref base,
+                ref bump_map,
+                scale,
+            } => {
+                let normal = bump_mapped_normal(hit, bump_map, scale);
+                base.eval(&Hit { normal, ..*hit }, wo, wi)
+            }
+
+            Material::Custom(ref bsdf) => bsdf.eval(hit, wo, wi),
+        }
+    }
+
+    fn pdf(&self, hit: &Hit, wo: &Vec3, wi: &Vec3) -> f64 {
+        let cos_i = wi.dot(&hit.normal);
+
+        match *self {
+            Material::DirectionGradient
+            | Material::NormalSpaceGradient
+            | Material::SolidColour { .. }
+            | Material::Dialectric { .. }
+            | Material::Subsurface { .. }
+            | Material::Interface
+            | Material::Emissive { .. } => 0.,
+
+            Material::Diffuse { .. } if cos_i > 0. => 1. / (2. * PI),
+            Material::Lambertian { .. } if cos_i > 0. => cos_i / PI,
+            Material::Diffuse { .. } | Material::Lambertian { .. } => 0.,
+
+            Material::Metal { scatter, .. } => {
+                if scatter <= 0. || cos_i <= 0. {
+                    return 0.;
+                }
+                let reflected = (-*wo).reflect(&hit.normal);
+                fuzzy_reflection_pdf(&reflected, scatter, wi)
+            }
+            Material::Conductor {
+                roughness,
+                anisotropy,
+                ..
+            } => {
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+                if wi.z() <= 0. {
+                    return 0.;
+                }
+                Ggx::new(roughness, anisotropy).pdf_reflection(&wo, &wi)
+            }
+
+            Material::Principled(ref principled) => {
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                principled.pdf(&frame.to_local(wo), &frame.to_local(wi), hit.front_face)
+            }
+            Material::Mix {
+                ref a,
+                ref b,
+                ref factor,
+            } => {
+                let factor = factor.evaluate(hit.uv);
+                a.pdf(hit, wo, wi) * (1. - factor) + b.pdf(hit, wo, wi) * factor
+            }
+            Material::Coated {
+                ref base,
+                coat_ior,
+                coat_roughness,
+            } => {
+                if !hit.front_face {
+                    return base.pdf(hit, wo, wi);
+                }
+
+                let frame = Frame::new(hit.normal, hit.dpdu);
+                let (wo_local, wi_local) = (frame.to_local(wo), frame.to_local(wi));
+
+                let coat_probability = fresnel_dielectric(wo_local.z(), 1. / coat_ior);
+                let coat = if wi_local.z() > 0. {
+                    Ggx::new(coat_roughness, 0.).pdf_reflection(&wo_local, &wi_local)
+                } else {
+                    0.
+                };
+
+                coat * coat_probability + base.pdf(hit, wo, wi) * (1. - coat_probability)
+            }
+
+            Material::NormalMapped {
+                ref base,
+                ref normal_map,
+                strength,
+            } => {
+                let normal = normal_mapped_normal(hit, normal_map, strength);
+                base.pdf(&Hit { normal, ..*hit }, wo, wi)
+            }
+            Material::BumpMapped {
+                ref base,
+                ref bump_map,
+                scale,
+            } => {
+                let normal = bump_mapped_normal(hit, bump_map, scale);
+                base.pdf(&Hit { normal, ..*hit }, wo, wi)
+            }
+
+            Material::Custom(ref bsdf) => bsdf.pdf(hit, wo, wi),
+        }
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        match *self {
+            // These only give off light, so there is nothing to sample.
+            Material::DirectionGradient
+            | Material::NormalSpaceGradient
+            | Material::SolidColour { .. }
+            | Material::Emissive { .. } => BsdfFlags::SPECULAR,
+
+            Material::Diffuse { .. } | Material::Lambertian { .. } | Material::Conductor { .. } => {
+                BsdfFlags::default()
+            }
+            Material::Metal { scatter, .. } => BsdfFlags {
+                delta: scatter <= 0.,
+                transmission: false,
+            },
+
+            Material::Dialectric { .. } | Material::Subsurface { .. } | Material::Interface => {
+                BsdfFlags::SPECULAR_TRANSMISSION
+            }
+
+            Material::Principled(ref principled) => BsdfFlags {
+                delta: false,
+                transmission: principled.transmission > 0.,
+            },
+            Material::Mix { ref a, ref b, .. } => {
+                let (a, b) = (a.flags(), b.flags());
+                BsdfFlags {
+                    delta: a.delta && b.delta,
+                    transmission: a.transmission || b.transmission,
+                }
+            }
+            Material::Coated { ref base, .. } => BsdfFlags {
+                delta: false,
+                ..base.flags()
+            },
+
+            Material::NormalMapped { ref base, .. } | Material::BumpMapped { ref base, .. } => {
+                base.flags()
+            }
+
+            Material::Custom(ref bsdf) => bsdf.flags(),
+        }
+    }
+
+    fn emitted(&self, hit: &Hit, wo: &Vec3) -> Colour {
+        match *self {
+            Material::DirectionGradient => {
+                let a = (-wo.y() + 1.0) * 0.5;
+                Colour::new(1.0, 1.0, 1.0) * (1.0 - a) + Colour::new(0.5, 0.7, 1.0) * a
+            }
+            Material::NormalSpaceGradient => {
+                Colour::new(
+                    hit.normal.x() + 1.,
+                    hit.normal.y() + 1.,
+                    hit.normal.z() + 1.,
+                ) * 0.5
+            }
+            Material::SolidColour { colour } => colour,
+            Material::Emissive { radiance } if hit.front_face => radiance,
+
+            Material::Mix {
+                ref a,
+                ref b,
+                ref factor,
+            } => {
+                let factor = factor.evaluate(hit.uv);
+                a.emitted(hit, wo) * (1. - factor) + b.emitted(hit, wo) * factor
+            }
+            Material::Coated { ref base, .. }
+            | Material::NormalMapped { ref base, .. }
+            | Material::BumpMapped { ref base, .. } => base.emitted(hit, wo),
+
+            Material::Custom(ref bsdf) => bsdf.emitted(hit, wo),
+
+            _ => Colour::black(),
+        }
+    }
+}
+
+impl Material {
+    /// Weights a sample chosen from one part of a layered or mixed material
+    /// by the whole material, so that it agrees with `eval` and `pdf`. The
+    /// cosine is taken against the hit's normal, as integrators do for light
+    /// samples, rather than any shading normal. Delta samples, which `eval`
+    /// and `pdf` don't account for, are kept as they are.
+    fn reweighted(&self, hit: &Hit, wo: &Vec3, sample: BsdfSample) -> Option<BsdfSample> {
+        if sample.flags.delta {
+            return Some(sample);
+        }
+
+        let pdf = self.pdf(hit, wo, &sample.wi);
+        if pdf <= 0. {
+            return None;
+        }
+        let cos_theta = sample.wi.dot(&hit.normal).abs();
+
+        Some(BsdfSample {
+            weight: self.eval(hit, wo, &sample.wi) * (cos_theta / pdf),
+            pdf,
+            ..sample
+        })
+    }
+}
+
+/// Density of `Metal` scattering a mirror reflection `reflected` into `wi`.
+/// Its samples are uniform on a sphere of radius `scatter` around the tip of
+/// `reflected`, so this is that sphere's area density projected onto
+/// directions, over both points a direction passes through.
+fn fuzzy_reflection_pdf(reflected: &Vec3, scatter: f64, wi: &Vec3) -> f64 {
+    let b = wi.dot(reflected);
+    let discriminant = b * b - (1. - scatter * scatter);
+    if discriminant <= 0. {
+        return 0.;
+    }
+
+    let root = discriminant.sqrt();
+    let distances_squared: f64 = [b - root, b + root]
+        .iter()
+        .filter(|&&t| t > 0.)
+        .map(|t| t * t)
+        .sum();
+
+    distances_squared / (4. * PI * scatter * root)
+}
+
+/// The hit normal facing out of the shape, rather than towards the ray.
+fn outward_normal(hit: &Hit) -> Vec3 {
+    if hit.front_face {
+        hit.normal
+    } else {
+        -hit.normal
+    }
+}
+
+/// Re-orients a normal computed from the outward normal so it faces the ray
+/// again, matching the convention of `Hit::normal`.
+fn facing_ray(hit: &Hit, normal: Vec3) -> Vec3 {
+    if hit.front_face {
+        normal
+    } else {
+        -normal
+    }
+}
+
+/// Perturbs the shading normal by a tangent-space normal map, where the red
+/// and green channels follow `dpdu` and `dpdv` and blue follows the normal.
+fn normal_mapped_normal(hit: &Hit, normal_map: &ImageTexture, strength: f64) -> Vec3 {
+    let n = outward_normal(hit);
+
+    // Gram-Schmidt the tangent against the normal, since the map is authored
+    // in an orthonormal frame.
+    let tangent = hit.dpdu - n * n.dot(&hit.dpdu);
+    if tangent.length_squared() == 0. {
+        return hit.normal;
+    }
+    let tangent = tangent.unit();
+    let bitangent = n.cross(&tangent);
+
+    let texel = normal_map.sample(hit.uv);
+    let x = (texel.r() * 2. - 1.) * strength;
+    let y = (texel.g() * 2. - 1.) * strength;
+    let z = texel.b() * 2. - 1.;
+
+    let mapped = (tangent * x + bitangent * y + n * z).unit();
+    facing_ray(hit, mapped)
+}
+
+/// Perturbs the shading normal by the gradient of a height map, displacing
+/// the surface along its normal by `scale` times the texel brightness.
+fn bump_mapped_normal(hit: &Hit, bump_map: &ImageTexture, scale: f64) -> Vec3 {
+    let n = outward_normal(hit);
+
+    let du = 0.5 / bump_map.width() as f64;
+    let dv = 0.5 / bump_map.height() as f64;
+
+    let height = bump_map.sample_scalar(hit.uv);
+    let height_u = bump_map.sample_scalar((hit.uv.0 + du, hit.uv.1));
+    let height_v = bump_map.sample_scalar((hit.uv.0, hit.uv.1 + dv));
+
+    let dpdu = hit.dpdu + n * (scale * (height_u - height) / du);
+    let dpdv = hit.dpdv + n * (scale * (height_v - height) / dv);
+
+    let bumped = dpdu.cross(&dpdv);
+    if bumped.length_squared() == 0. {
+        return hit.normal;
+    }
+
+    let bumped = bumped.unit();
+    let bumped = if bumped.dot(&n) < 0. { -bumped } else { bumped };
+    facing_ray(hit, bumped)
 }
@@ -0,0 +1,264 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Colour, vec::Vec3};
+
+/// The anisotropic GGX (Trowbridge-Reitz) microfacet distribution with
+/// height-correlated Smith shadowing. All directions are in the local shading
+/// frame, where the normal is +Z.
+#[derive(Clone, Copy, Debug)]
+pub struct Ggx {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Ggx {
+    /// Maps perceptual roughness and anisotropy, both in [0, 1], to the
+    /// distribution's alphas, following the Disney convention.
+    pub fn new(roughness: f64, anisotropy: f64) -> Self {
+        let aspect = (1. - 0.9 * anisotropy.clamp(0., 1.)).sqrt();
+        let alpha = roughness.clamp(0., 1.).powi(2);
+
+        // Very small alphas make D a spike that floating point can't sample.
+        Self {
+            alpha_x: (alpha / aspect).max(1e-4),
+            alpha_y: (alpha * aspect).max(1e-4),
+        }
+    }
+
+    /// Density of microfacet normals `h`.
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z() <= 0. {
+            return 0.;
+        }
+
+        let e = (h.x() / self.alpha_x).powi(2) + (h.y() / self.alpha_y).powi(2) + h.z().powi(2);
+        1. / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    fn lambda(&self, w: &Vec3) -> f64 {
+        if w.z() == 0. {
+            return f64::INFINITY;
+        }
+
+        let a2 = ((self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)) / w.z().powi(2);
+        ((1. + a2).sqrt() - 1.) / 2.
+    }
+
+    /// Fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Fraction of microfacets visible from both `wo` and `wi`.
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal from the distribution of normals visible
+    /// from `wo` (Heitz 2018), which keeps the sample weights close to one.
+    pub fn sample_h(&self, wo: &Vec3, xi: (f64, f64)) -> Vec3 {
+        let vh = Vec3::new((self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z())).unit();
+
+        let len_sq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_sq > 0. {
+            Vec3::new((-vh.y(), vh.x(), 0.)) / len_sq.sqrt()
+        } else {
+            Vec3::new((1., 0., 0.))
+        };
+        let t2 = vh.cross(&t1);
+
+        let r = xi.0.sqrt();
+        let phi = 2. * PI * xi.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).max(0.).sqrt() + s * r * phi.sin();
+
+        let nh = t1 * p1 + t2 * p2 + vh * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+
+        Vec3::new((
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        ))
+        .unit()
+    }
+
+    /// Density of `sample_h` choosing `h` when viewed from `wo`.
+    pub fn pdf_h(&self, wo: &Vec3, h: &Vec3) -> f64 {
+        if wo.z() <= 0. {
+            return 0.;
+        }
+
+        self.g1(wo) * wo.dot(h).max(0.) * self.d(h) / wo.z()
+    }
+
+    /// The Cook-Torrance reflection BRDF for light arriving from `wi` and
+    /// leaving towards `wo`.
+    pub fn eval_reflection(&self, fresnel: &Fresnel, wo: &Vec3, wi: &Vec3) -> Colour {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return Colour::black();
+        }
+
+        let h = (*wo + *wi).unit();
+        fresnel.evaluate(wo.dot(&h)) * (self.d(&h) * self.g(wo, wi) / (4. * wo.z() * wi.z()))
+    }
+
+    /// Samples a reflected direction for `wo`, returning it with the BRDF
+    /// times cosine divided by the density, or `None` if it points below the
+    /// surface.
+    pub fn sample_reflection(
+        &self,
+        fresnel: &Fresnel,
+        wo: &Vec3,
+        xi: (f64, f64),
+    ) -> Option<(Vec3, Colour)> {
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let h = self.sample_h(wo, xi);
+        let wi = (-*wo).reflect(&h);
+        if wi.z() <= 0. {
+            return None;
+        }
+
+        // With visible-normal sampling, D and most of G cancel out.
+        let weight = fresnel.evaluate(wo.dot(&h)) * (self.g(wo, &wi) / self.g1(wo));
+        Some((wi, weight))
+    }
+
+    /// Density of reflecting `wo` into `wi`, with respect to solid angle.
+    pub fn pdf_reflection(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let h = *wo + *wi;
+        if h.length_squared() == 0. {
+            return 0.;
+        }
+        let h = h.unit();
+
+        self.pdf_h(wo, &h) / (4. * wo.dot(&h).abs())
+    }
+}
+
+/// How much light a surface reflects at a given angle of incidence.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Fresnel {
+    /// Schlick's approximation from the reflectance at normal incidence.
+    Schlick {
+        f0: Colour,
+    },
+
+    /// Exact Fresnel for a conductor with complex index of refraction
+    /// `eta + ik`, given per colour channel.
+    Conductor {
+        eta: Colour,
+        k: Colour,
+    },
+
+    /// Exact Fresnel for a colourless dielectric, where `eta` is the ratio
+    /// of the indices of refraction outside over inside.
+    Dielectric {
+        eta: f64,
+    },
+
+    // measured conductors, at roughly 650, 550 and 450nm
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+impl Fresnel {
+    pub fn evaluate(&self, cos_theta: f64) -> Colour {
+        let cos_theta = cos_theta.clamp(0., 1.);
+
+        match *self {
+            Fresnel::Schlick { f0 } => f0 + (Colour::white() - f0) * (1. - cos_theta).powi(5),
+            Fresnel::Conductor { eta, k } => Colour::new(
+                fresnel_conductor(cos_theta, eta.r(), k.r()),
+                fresnel_conductor(cos_theta, eta.g(), k.g()),
+                fresnel_conductor(cos_theta, eta.b(), k.b()),
+            ),
+            Fresnel::Dielectric { eta } => Colour::white() * fresnel_dielectric(cos_theta, eta),
+            Fresnel::Gold => Fresnel::Conductor {
+                eta: Colour::new(0.143, 0.374, 1.442),
+                k: Colour::new(3.983, 2.385, 1.603),
+            }
+            .evaluate(cos_theta),
+            Fresnel::Copper => Fresnel::Conductor {
+                eta: Colour::new(0.200, 0.924, 1.102),
+                k: Colour::new(3.912, 2.452, 2.142),
+            }
+            .evaluate(cos_theta),
+            Fresnel::Aluminium => Fresnel::Conductor {
+                eta: Colour::new(1.657, 0.880, 0.521),
+                k: Colour::new(9.224, 6.270, 4.837),
+            }
+            .evaluate(cos_theta),
+            Fresnel::Silver => Fresnel::Conductor {
+                eta: Colour::new(0.155, 0.116, 0.138),
+                k: Colour::new(4.828, 3.122, 2.147),
+            }
+            .evaluate(cos_theta),
+        }
+    }
+}
+
+/// Unpolarised reflectance of a conductor, from pbrt.
+fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta * cos_theta;
+    let sin2 = 1. - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+    let t2 = 2. * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+/// Unpolarised reflectance at a boundary between two dielectrics, where
+/// `eta` is the ratio of the indices of refraction on the incident side over
+/// the transmitted side. Returns 1 for total internal reflection.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(0., 1.);
+    let sin2_theta_t = eta * eta * (1. - cos_theta_i * cos_theta_i);
+    if sin2_theta_t >= 1. {
+        return 1.;
+    }
+
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+    let r_parallel = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    let r_perpendicular = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+/// Schlick's approximation to `fresnel_dielectric`.
+pub fn fresnel_schlick(cos_theta_i: f64, eta: f64) -> f64 {
+    let r0 = ((1. - eta) / (1. + eta)).powi(2);
+    r0 + (1. - r0) * (1. - cos_theta_i.clamp(0., 1.)).powi(5)
+}
+
+/// Refracts `wo` through a surface with normal `n` on the same side, where
+/// `eta` is the incident over transmitted index ratio. Returns `None` on
+/// total internal reflection.
+pub fn refract(wo: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta_i = wo.dot(n);
+    let sin2_theta_t = eta * eta * (1. - cos_theta_i * cos_theta_i).max(0.);
+    if sin2_theta_t >= 1. {
+        return None;
+    }
+
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+    Some(-*wo * eta + *n * (eta * cos_theta_i - cos_theta_t))
+}
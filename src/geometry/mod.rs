@@ -1,12 +1,28 @@
+mod bsdf;
 mod material;
+mod microfacet;
+mod principled;
 mod shape;
 
 use serde::{Deserialize, Serialize};
 
-pub use self::{material::Material, shape::Shape};
+use crate::medium::Medium;
 
-#[derive(Clone, Copy, Deserialize, Serialize)]
+pub use self::{
+    bsdf::{sample_cosine_hemisphere, Bsdf, BsdfFlags, BsdfSample, Interior},
+    material::Material,
+    microfacet::{fresnel_dielectric, fresnel_schlick, refract, Fresnel, Ggx},
+    principled::Principled,
+    shape::Shape,
+};
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Geometry {
     pub shape: Shape,
     pub material: Material,
+
+    /// Fills the inside of a closed shape. Give the shape an `Interface`
+    /// material for a volume with no visible surface, such as a fog bank.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub medium: Option<Medium>,
 }
@@ -0,0 +1,301 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    bsdf::sample_cosine_hemisphere,
+    microfacet::{fresnel_dielectric, refract, Fresnel, Ggx},
+};
+use crate::{colour::Colour, sampling, vec::Vec3};
+
+/// A Disney-style "uber" material, combining diffuse, sheen, specular,
+/// clearcoat and transmission lobes using the parameters common to PBR
+/// authoring tools and glTF.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Principled {
+    #[serde(default = "default_base_colour")]
+    pub base_colour: Colour,
+    #[serde(default)]
+    pub metallic: f64,
+    #[serde(default = "default_half")]
+    pub roughness: f64,
+    #[serde(default)]
+    pub anisotropy: f64,
+
+    /// Strength of dielectric reflections, where the default of 0.5 reflects
+    /// as much as a smooth surface with the material's `ior` would: 4% at
+    /// normal incidence for the default `ior`.
+    #[serde(default = "default_half")]
+    pub specular: f64,
+    #[serde(default = "default_ior")]
+    pub ior: f64,
+    #[serde(default)]
+    pub transmission: f64,
+
+    #[serde(default)]
+    pub clearcoat: f64,
+    #[serde(default = "default_clearcoat_roughness")]
+    pub clearcoat_roughness: f64,
+
+    #[serde(default)]
+    pub sheen: f64,
+    /// Blends the sheen colour from white to the base colour.
+    #[serde(default = "default_half")]
+    pub sheen_tint: f64,
+}
+
+fn default_base_colour() -> Colour {
+    Colour::new(0.8, 0.8, 0.8)
+}
+
+fn default_half() -> f64 {
+    0.5
+}
+
+fn default_ior() -> f64 {
+    1.5
+}
+
+fn default_clearcoat_roughness() -> f64 {
+    0.03
+}
+
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+impl Principled {
+    /// Samples an incoming direction for light leaving towards `wo`, both in
+    /// the local shading frame. Returns the direction with the BSDF times
+    /// cosine over the density, or `None` if the path is absorbed.
+    ///
+    /// One lobe is chosen at random in proportion to its estimated
+    /// contribution. Reflections are weighted by every lobe that could have
+    /// chosen them, as `eval` and `pdf` are, while transmission, which only
+    /// its own lobe chooses, is divided by the chance of choosing it.
+    pub fn sample(&self, wo: &Vec3, front_face: bool) -> Option<(Vec3, Colour)> {
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        if !front_face {
+            // Only light passing through the material reaches its inside.
+            return self.sample_transmission(wo, 1. / self.ior, front_face);
+        }
+
+        let lobes = self.lobe_weights(wo.z());
+        let (below_coat, dielectric) = self.layer_weights(wo.z());
+        let transmission = self.transmission.clamp(0., 1.);
+
+        let total: f64 = lobes.iter().map(|(_, w)| w).sum();
+        if total <= 0. {
+            return None;
+        }
+
+        let mut choice = sampling::uniform() * total;
+        let (lobe, probability) = lobes
+            .iter()
+            .find(|(_, w)| {
+                choice -= w;
+                choice < 0. && *w > 0.
+            })
+            .or(lobes.iter().rev().find(|(_, w)| *w > 0.))
+            .map(|(lobe, w)| (lobe, w / total))?;
+
+        let wi = match lobe {
+            Lobe::Clearcoat => self.clearcoat_ggx().sample_h(wo, sampling::uniform_pair()),
+            Lobe::Specular => self.specular_ggx().sample_h(wo, sampling::uniform_pair()),
+            Lobe::Diffuse => {
+                let wi = sample_cosine_hemisphere(sampling::uniform_pair());
+                return self.weighted_reflection(wo, wi);
+            }
+            Lobe::Transmission => {
+                let (wi, weight) = self.sample_transmission(wo, 1. / self.ior, front_face)?;
+                let weight = weight * (below_coat * dielectric * transmission);
+                return Some((wi, weight / probability));
+            }
+        };
+
+        // The microfacet lobes sampled a normal to reflect off.
+        self.weighted_reflection(wo, (-*wo).reflect(&wi))
+    }
+
+    /// `wi` with the reflective lobes' BSDF times cosine over the density of
+    /// any of them choosing it, or `None` if it points below the surface.
+    fn weighted_reflection(&self, wo: &Vec3, wi: Vec3) -> Option<(Vec3, Colour)> {
+        let pdf = self.pdf(wo, &wi, true);
+        if wi.z() <= 0. || pdf <= 0. {
+            return None;
+        }
+
+        Some((wi, self.eval(wo, &wi, true) * (wi.z() / pdf)))
+    }
+
+    /// The BSDF of the reflective lobes for light arriving from `wi` and
+    /// leaving towards `wo`, in the local shading frame. Transmission, and
+    /// anything seen from inside the material, is only ever sampled.
+    pub fn eval(&self, wo: &Vec3, wi: &Vec3, front_face: bool) -> Colour {
+        if !front_face || wo.z() <= 0. || wi.z() <= 0. {
+            return Colour::black();
+        }
+
+        let (below_coat, dielectric) = self.layer_weights(wo.z());
+        let transmission = self.transmission.clamp(0., 1.);
+
+        let clearcoat = self
+            .clearcoat_ggx()
+            .eval_reflection(&clearcoat_fresnel(), wo, wi)
+            * self.clearcoat;
+        let specular = self
+            .specular_ggx()
+            .eval_reflection(&self.specular_fresnel(), wo, wi);
+        let diffuse = self.diffuse(wo, wi) * (dielectric * (1. - transmission));
+
+        clearcoat + (specular + diffuse) * below_coat
+    }
+
+    /// Density with which `sample` chooses `wi` from one of the lobes that
+    /// `eval` covers.
+    pub fn pdf(&self, wo: &Vec3, wi: &Vec3, front_face: bool) -> f64 {
+        if !front_face || wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+
+        let lobes = self.lobe_weights(wo.z());
+        let total: f64 = lobes.iter().map(|(_, w)| w).sum();
+        if total <= 0. {
+            return 0.;
+        }
+
+        lobes
+            .iter()
+            .map(|(lobe, w)| {
+                let pdf = match lobe {
+                    Lobe::Clearcoat => self.clearcoat_ggx().pdf_reflection(wo, wi),
+                    Lobe::Specular => self.specular_ggx().pdf_reflection(wo, wi),
+                    Lobe::Diffuse => wi.z() / PI,
+                    Lobe::Transmission => 0.,
+                };
+                pdf * w / total
+            })
+            .sum()
+    }
+
+    /// How likely each lobe is to be sampled, before normalising, in
+    /// proportion to an estimate of its contribution.
+    fn lobe_weights(&self, cos_o: f64) -> [(Lobe, f64); 4] {
+        let (below_coat, dielectric) = self.layer_weights(cos_o);
+        let coat = 1. - below_coat;
+        let transmission = self.transmission.clamp(0., 1.);
+
+        let specular_weight = self.specular_fresnel().evaluate(cos_o).luminance();
+        let diffuse_weight = dielectric * (1. - transmission) * self.base_colour.luminance();
+        let transmission_weight = dielectric * transmission;
+
+        [
+            (Lobe::Clearcoat, coat),
+            (Lobe::Specular, below_coat * specular_weight),
+            (Lobe::Diffuse, below_coat * diffuse_weight),
+            (Lobe::Transmission, below_coat * transmission_weight),
+        ]
+    }
+
+    /// The fraction of light getting past the clearcoat, and the fraction of
+    /// that entering the dielectric base rather than reflecting off it.
+    fn layer_weights(&self, cos_o: f64) -> (f64, f64) {
+        let coat = self.clearcoat.clamp(0., 1.) * schlick(0.04, cos_o);
+        let dielectric =
+            (1. - self.metallic.clamp(0., 1.)) * (1. - schlick(self.dielectric_f0(), cos_o));
+        (1. - coat, dielectric)
+    }
+
+    /// The Lambertian base plus sheen, before weighting by how much light
+    /// reaches them.
+    fn diffuse(&self, wo: &Vec3, wi: &Vec3) -> Colour {
+        let h = (*wo + *wi).unit();
+        let sheen_colour =
+            Colour::white() * (1. - self.sheen_tint) + self.base_colour * self.sheen_tint;
+        let sheen = sheen_colour * (self.sheen * schlick_weight(wi.dot(&h)));
+
+        self.base_colour / PI + sheen
+    }
+
+    fn clearcoat_ggx(&self) -> Ggx {
+        Ggx::new(self.clearcoat_roughness, 0.)
+    }
+
+    fn specular_ggx(&self) -> Ggx {
+        Ggx::new(self.roughness, self.anisotropy)
+    }
+
+    /// Samples a rough dielectric boundary, refracting through a microfacet
+    /// or reflecting off it according to the Fresnel term. The caller is
+    /// responsible for any Fresnel weighting on the way in, so refraction is
+    /// unweighted except for the base colour tint.
+    fn sample_transmission(&self, wo: &Vec3, eta: f64, front_face: bool) -> Option<(Vec3, Colour)> {
+        let eta = if front_face { eta } else { 1. / eta };
+
+        let ggx = Ggx::new(self.roughness, self.anisotropy);
+        let h = ggx.sample_h(wo, sampling::uniform_pair());
+        let shadowing = |wi: &Vec3| ggx.g(wo, wi) / ggx.g1(wo);
+
+        let reflectance = if front_face {
+            0.
+        } else {
+            fresnel_dielectric(wo.dot(&h), eta)
+        };
+
+        let refracted = refract(wo, &h, eta).filter(|_| sampling::uniform() >= reflectance);
+        match refracted {
+            Some(wi) if wi.z() < 0. => {
+                let tint = if front_face {
+                    self.base_colour
+                } else {
+                    Colour::white()
+                };
+                Some((wi, tint * shadowing(&-wi)))
+            }
+            Some(_) => None,
+            None => {
+                let wi = (-*wo).reflect(&h);
+                (wi.z() > 0.).then(|| (wi, Colour::white() * shadowing(&wi)))
+            }
+        }
+    }
+
+    /// Reflectance of the dielectric base at normal incidence, from its index
+    /// of refraction so reflection agrees with transmission.
+    fn dielectric_f0(&self) -> f64 {
+        let f0 = ((self.ior - 1.) / (self.ior + 1.)).powi(2);
+        (f0 * self.specular.clamp(0., 1.) / 0.5).min(1.)
+    }
+
+    /// Specular reflectance, blending from a colourless dielectric to a metal
+    /// tinted by the base colour.
+    fn specular_fresnel(&self) -> Fresnel {
+        let dielectric = self.dielectric_f0();
+        let metallic = self.metallic.clamp(0., 1.);
+
+        Fresnel::Schlick {
+            f0: Colour::new(dielectric, dielectric, dielectric) * (1. - metallic)
+                + self.base_colour * metallic,
+        }
+    }
+}
+
+fn clearcoat_fresnel() -> Fresnel {
+    Fresnel::Schlick {
+        f0: Colour::new(0.04, 0.04, 0.04),
+    }
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1. - cos_theta.clamp(0., 1.)).powi(5)
+}
+
+fn schlick(f0: f64, cos_theta: f64) -> f64 {
+    f0 + (1. - f0) * schlick_weight(cos_theta)
+}
@@ -1,18 +1,24 @@
-use std::ops::Range;
+use std::{f64::consts::PI, ops::Range};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{ray::Ray, vec::Vec3};
+use crate::{hit::Intersection, ray::Ray, sampling, vec::Vec3};
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum Shape {
-    Background,
-    Sphere { centre: Vec3, radius: f64 },
+    Sphere {
+        centre: Vec3,
+        radius: f64,
+    },
+    /// An axis-aligned box between two opposite corners.
+    Cuboid {
+        min: Vec3,
+        max: Vec3,
+    },
 }
 impl Shape {
-    pub fn hit_test(&self, ray: &Ray, t_range: Range<f64>) -> Option<(f64, Vec3, bool)> {
+    pub fn hit_test(&self, ray: &Ray, t_range: Range<f64>) -> Option<Intersection> {
         match self {
-            Shape::Background => Some((f64::INFINITY, Vec3::new((0., 0., 0.)), true)),
             Shape::Sphere { centre, radius } => {
                 let oc = ray.origin - *centre;
                 let a = ray.direction.length_squared();
@@ -34,7 +40,10 @@ impl Shape {
                     }
                 }
 
-                let mut normal = (ray.at(t) - *centre) / *radius;
+                let outward_normal = (ray.at(t) - *centre) / *radius;
+                let (uv, dpdu, dpdv) = sphere_parameterisation(&outward_normal, *radius);
+
+                let mut normal = outward_normal;
                 let front_face;
 
                 if ray.direction.dot(&normal) > 0.0 {
@@ -44,8 +53,191 @@ impl Shape {
                     front_face = true;
                 }
 
-                Some((t, normal, front_face))
+                Some(Intersection {
+                    t,
+                    normal,
+                    front_face,
+                    uv,
+                    dpdu,
+                    dpdv,
+                })
+            }
+            Shape::Cuboid { min, max } => cuboid_hit_test(min, max, ray, t_range),
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        match self {
+            Shape::Sphere { radius, .. } => 4. * PI * radius * radius,
+            Shape::Cuboid { min, max } => {
+                let size = *max - *min;
+                2. * (size.x() * size.y() + size.y() * size.z() + size.z() * size.x())
+            }
+        }
+    }
+
+    /// Whether `point` lies inside the shape.
+    pub fn contains(&self, point: &Vec3) -> bool {
+        match self {
+            Shape::Sphere { centre, radius } => {
+                (*point - *centre).length_squared() < radius * radius
+            }
+            Shape::Cuboid { min, max } => {
+                (min.x()..max.x()).contains(&point.x())
+                    && (min.y()..max.y()).contains(&point.y())
+                    && (min.z()..max.z()).contains(&point.z())
+            }
+        }
+    }
+
+    /// Picks a point uniformly over the surface, returning it with the
+    /// outward normal there.
+    pub fn sample(&self) -> (Vec3, Vec3) {
+        match self {
+            Shape::Sphere { centre, radius } => {
+                let normal = Vec3::random_unit_vector();
+
+                (*centre + normal * *radius, normal)
+            }
+            Shape::Cuboid { min, max } => {
+                let size = *max - *min;
+                let (u, v) = sampling::uniform_pair();
+
+                // Pick an axis in proportion to the area of its pair of
+                // faces, then one of the two faces.
+                let areas = [
+                    size.y() * size.z(),
+                    size.z() * size.x(),
+                    size.x() * size.y(),
+                ];
+                let mut choice = sampling::uniform() * areas.iter().sum::<f64>();
+                let axis = (0..3)
+                    .find(|&axis| {
+                        choice -= areas[axis];
+                        choice < 0.
+                    })
+                    .unwrap_or(2);
+                let side = if sampling::uniform() < 0.5 { 1. } else { 0. };
+
+                let point = match axis {
+                    0 => (side, u, v),
+                    1 => (v, side, u),
+                    _ => (u, v, side),
+                };
+                let normal = match axis {
+                    0 => (side * 2. - 1., 0., 0.),
+                    1 => (0., side * 2. - 1., 0.),
+                    _ => (0., 0., side * 2. - 1.),
+                };
+
+                let point =
+                    *min + Vec3::new((point.0 * size.x(), point.1 * size.y(), point.2 * size.z()));
+                (point, Vec3::new(normal))
             }
         }
     }
 }
+
+/// Slab test against an axis-aligned box, returning whichever of the entry
+/// and exit points is first within `t_range`.
+fn cuboid_hit_test(min: &Vec3, max: &Vec3, ray: &Ray, t_range: Range<f64>) -> Option<Intersection> {
+    let origin = [ray.origin.x(), ray.origin.y(), ray.origin.z()];
+    let direction = [ray.direction.x(), ray.direction.y(), ray.direction.z()];
+    let min = [min.x(), min.y(), min.z()];
+    let max = [max.x(), max.y(), max.z()];
+
+    let mut entry = (f64::NEG_INFINITY, 0);
+    let mut exit = (f64::INFINITY, 0);
+
+    for axis in 0..3 {
+        let inverse = 1. / direction[axis];
+        let mut near = (min[axis] - origin[axis]) * inverse;
+        let mut far = (max[axis] - origin[axis]) * inverse;
+        if near > far {
+            std::mem::swap(&mut near, &mut far);
+        }
+
+        if near > entry.0 {
+            entry = (near, axis);
+        }
+        if far < exit.0 {
+            exit = (far, axis);
+        }
+    }
+
+    if entry.0 > exit.0 {
+        return None;
+    }
+
+    let (t, axis) = if t_range.contains(&entry.0) {
+        entry
+    } else if t_range.contains(&exit.0) {
+        exit
+    } else {
+        return None;
+    };
+
+    let point = ray.at(t);
+    let p = [point.x(), point.y(), point.z()];
+    let centre = (min[axis] + max[axis]) / 2.;
+
+    let mut outward = [0.; 3];
+    outward[axis] = if p[axis] > centre { 1. } else { -1. };
+    let outward_normal = Vec3::new((outward[0], outward[1], outward[2]));
+
+    // Parameterise each face by the two axes that run across it.
+    let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+    let extent = |a: usize| max[a] - min[a];
+    let uv = (
+        (p[u_axis] - min[u_axis]) / extent(u_axis),
+        (p[v_axis] - min[v_axis]) / extent(v_axis),
+    );
+
+    let mut dpdu = [0.; 3];
+    dpdu[u_axis] = extent(u_axis);
+    let mut dpdv = [0.; 3];
+    dpdv[v_axis] = extent(v_axis);
+
+    let front_face = ray.direction.dot(&outward_normal) < 0.;
+    let normal = if front_face {
+        outward_normal
+    } else {
+        -outward_normal
+    };
+
+    Some(Intersection {
+        t,
+        normal,
+        front_face,
+        uv,
+        dpdu: Vec3::new((dpdu[0], dpdu[1], dpdu[2])),
+        dpdv: Vec3::new((dpdv[0], dpdv[1], dpdv[2])),
+    })
+}
+
+/// Latitude/longitude mapping of a point on a sphere, given as its outward
+/// unit normal. `u` runs around the Y axis starting at -X, `v` from the south
+/// pole to the north pole.
+fn sphere_parameterisation(n: &Vec3, radius: f64) -> ((f64, f64), Vec3, Vec3) {
+    let theta = (-n.y()).clamp(-1., 1.).acos();
+    let phi = f64::atan2(-n.z(), n.x()) + PI;
+    let uv = (phi / (2. * PI), theta / PI);
+
+    let sin_theta = theta.sin();
+    if sin_theta <= 1e-8 {
+        // At the poles the parameterisation degenerates; any tangents
+        // at right angles will do.
+        let dpdv = Vec3::new((1., 0., 0.)).cross(n).unit();
+        let dpdu = dpdv.cross(n);
+        return (uv, dpdu * (2. * PI * radius), dpdv * (PI * radius));
+    }
+
+    let dpdu = Vec3::new((n.z(), 0., -n.x())) * (2. * PI * radius);
+    let dpdv = Vec3::new((
+        -n.x() * n.y() / sin_theta,
+        sin_theta,
+        -n.z() * n.y() / sin_theta,
+    )) * (PI * radius);
+
+    (uv, dpdu, dpdv)
+}
@@ -1,8 +1,35 @@
-use crate::{geometry::Material, vec::Vec3};
+use crate::{
+    geometry::{Material, Shape},
+    medium::Medium,
+    vec::Vec3,
+};
 
-pub struct Hit {
-    pub material: Material,
+/// Where a ray meets a shape, before any material is attached.
+pub struct Intersection {
+    pub t: f64,
+    pub normal: Vec3,
+    pub front_face: bool,
+
+    /// Surface parameterisation at the hit point, used for texture lookups.
+    pub uv: (f64, f64),
+
+    /// Partial derivatives of the surface position with respect to `u` and
+    /// `v`. Together with the normal these form the tangent frame used by
+    /// normal and bump maps.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+}
+
+#[derive(Clone, Copy)]
+pub struct Hit<'a> {
+    pub shape: &'a Shape,
+    pub material: &'a Material,
+    /// The medium inside the shape that was hit, if any.
+    pub medium: Option<&'a Medium>,
     pub point: Vec3,
     pub normal: Vec3,
     pub front_face: bool,
+    pub uv: (f64, f64),
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
 }
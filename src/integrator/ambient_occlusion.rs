@@ -0,0 +1,65 @@
+use super::Integrator;
+use crate::{
+    colour::Colour,
+    geometry::{sample_cosine_hemisphere, Material},
+    hit::Hit,
+    ray::Ray,
+    sampling,
+    scene::Scene,
+    spectrum::SampledWavelengths,
+    vec::Frame,
+};
+
+/// Shades surfaces by how much of the sky above them is blocked by nearby
+/// geometry, ignoring materials and lighting. Useful for checking a scene's
+/// shapes and layout.
+pub struct AmbientOcclusion {
+    /// Surfaces further away than this don't occlude.
+    pub max_distance: f64,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        _wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let Some(hit) = first_surface(scene, *ray) else {
+            return Colour::white();
+        };
+
+        sampling::start_bounce(0);
+        let frame = Frame::from_normal(hit.normal);
+        let direction = frame.to_world(&sample_cosine_hemisphere((
+            sampling::uniform(),
+            sampling::uniform(),
+        )));
+        let occlusion_ray = Ray {
+            origin: hit.point,
+            direction,
+        };
+
+        match first_surface(scene, occlusion_ray) {
+            Some(occluder) if (occluder.point - hit.point).length() < self.max_distance => {
+                Colour::black()
+            }
+            _ => Colour::white(),
+        }
+    }
+}
+
+/// The first surface along `ray`, passing through interfaces.
+fn first_surface(scene: &Scene, mut ray: Ray) -> Option<Hit<'_>> {
+    loop {
+        let hit = scene.hit_test(&ray)?;
+        if !matches!(hit.material, Material::Interface) {
+            return Some(hit);
+        }
+
+        ray = Ray {
+            origin: hit.point,
+            direction: ray.direction,
+        };
+    }
+}
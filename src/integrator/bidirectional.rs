@@ -0,0 +1,671 @@
+use std::f64::consts::PI;
+
+use super::{escaped_radiance, sample_environment, spectral, Integrator};
+use crate::{
+    camera::Camera,
+    colour::Colour,
+    geometry::{sample_cosine_hemisphere, Bsdf, Geometry, Material},
+    hit::Hit,
+    medium::Medium,
+    ray::Ray,
+    sampling,
+    scene::{interior_transmittance, Scene},
+    spectrum::SampledWavelengths,
+    vec::{Frame, Vec3},
+};
+
+/// A bidirectional path tracer. Each sample traces one subpath from the
+/// camera and another from a point on an `Emissive` surface, then joins
+/// every vertex of one to every vertex of the other, weighting each way of
+/// building a path by multiple importance sampling. This finds light
+/// focused through glass onto diffuse surfaces, which a camera path can only
+/// find by chance.
+///
+/// When rendering whole images with a perspective or thin lens camera, light
+/// subpaths are joined to the camera too, splatting light into whichever
+/// pixel it lands in. This finds caustics seen directly on diffuse surfaces.
+///
+/// Light from the environment is gathered by the camera subpath alone, as
+/// in the path tracer. Participating media dim light along the subpaths and
+/// the joins between them, but don't scatter it, and subsurface scattering
+/// isn't followed.
+pub struct Bidirectional {
+    pub max_bounces: usize,
+}
+
+#[derive(Clone, Copy)]
+enum VertexKind<'a> {
+    Camera,
+    /// A point sampled on an emissive surface, starting a light subpath.
+    Light,
+    Surface(Hit<'a>),
+}
+
+#[derive(Clone, Copy)]
+struct Vertex<'a> {
+    kind: VertexKind<'a>,
+    point: Vec3,
+    normal: Vec3,
+    /// Light carried from the subpath's start to this vertex, divided by
+    /// the density of sampling it.
+    beta: Colour,
+    /// Whether the material here can only scatter into mirror directions,
+    /// so it can't be joined to another subpath.
+    delta: bool,
+    /// Area density of sampling this vertex from its own subpath.
+    pdf_fwd: f64,
+    /// Area density of sampling this vertex from the other end of the path.
+    pdf_rev: f64,
+    /// The medium the subpath reached this vertex through, or starts in.
+    medium: Option<&'a Medium>,
+}
+
+/// A path made by joining a camera subpath to a light subpath.
+struct Connection<'a> {
+    /// The light carried, before MIS weighting.
+    contribution: Colour,
+    /// A vertex sampled afresh to make the join, standing in for the start
+    /// of the subpath it was joined to.
+    sampled: Option<Vertex<'a>>,
+    /// Where on the image the light lands, when the light subpath was
+    /// joined straight to the camera.
+    position: Option<(f64, f64)>,
+}
+
+/// How a subpath carries on from its last vertex.
+struct Walk {
+    ray: Ray,
+    /// The light carried along the ray.
+    beta: Colour,
+    /// The solid-angle density of choosing the ray's direction.
+    pdf_direction: f64,
+    /// How many more vertices the subpath may gain.
+    max_vertices: usize,
+    /// The bounce the next vertex makes, for choosing which of the
+    /// sampler's dimensions it takes its numbers from.
+    first_bounce: usize,
+}
+
+/// Where the camera subpath escaped to the environment.
+struct Escape {
+    direction: Vec3,
+    beta: Colour,
+    bsdf_pdf: Option<f64>,
+}
+
+impl Integrator for Bidirectional {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        self.trace(scene, ray, wavelengths, None)
+    }
+
+    fn radiance_splatting(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+        splat: &mut dyn FnMut((f64, f64), Colour),
+    ) -> Colour {
+        self.trace(scene, ray, wavelengths, Some(splat))
+    }
+}
+
+impl Bidirectional {
+    /// The light arriving along `ray`, while splatting light from subpaths
+    /// joined to the camera if `splat` is given.
+    fn trace(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+        mut splat: Option<&mut dyn FnMut((f64, f64), Colour)>,
+    ) -> Colour {
+        let lights: Vec<&Geometry> = scene
+            .geometries
+            .iter()
+            .filter(|geo| matches!(geo.material, Material::Emissive { .. }))
+            .collect();
+
+        let mut radiance = Colour::black();
+
+        // Light subpaths can only be joined to the camera when there's
+        // somewhere to splat their light, and the camera's lens can be
+        // sampled. Otherwise the camera is treated like a mirror, so that
+        // the weights leave those paths out.
+        let pdf_camera = match splat {
+            Some(_) => scene.camera.pdf_direction(ray),
+            None => 0.,
+        };
+        let mut camera_path = vec![Vertex {
+            kind: VertexKind::Camera,
+            point: ray.origin,
+            normal: ray.direction.unit(),
+            beta: Colour::white(),
+            delta: pdf_camera == 0.,
+            pdf_fwd: 0.,
+            pdf_rev: 0.,
+            medium: scene.medium_at(&ray.origin),
+        }];
+        let walk = Walk {
+            ray: *ray,
+            beta: Colour::white(),
+            pdf_direction: pdf_camera,
+            max_vertices: self.max_bounces + 1,
+            first_bounce: 0,
+        };
+        let escape = self.random_walk(scene, walk, wavelengths, &mut camera_path);
+
+        // The environment isn't a light subpath origin, so it's only ever
+        // reached from the camera.
+        if let Some(escape) = escape {
+            radiance += escape.beta
+                * escaped_radiance(scene, &escape.direction, escape.bsdf_pdf, wavelengths);
+        }
+
+        // The light subpath's bounces take their numbers after the camera
+        // subpath's, then sampling the environment and joining the
+        // subpaths after both.
+        let light_bounce = self.max_bounces + 1;
+        let environment_bounce = light_bounce + self.max_bounces + 1;
+        if scene.light_sampling {
+            sampling::start_bounce(environment_bounce);
+            for (previous, vertex) in camera_path.iter().zip(&camera_path[1..]) {
+                let VertexKind::Surface(hit) = vertex.kind else {
+                    continue;
+                };
+                if !hit.material.flags().delta {
+                    let wo = direction(vertex, previous);
+                    radiance += vertex.beta
+                        * sample_environment(scene, &hit, &wo, vertex.medium, true, wavelengths);
+                }
+            }
+        }
+
+        let mut light_path = Vec::new();
+        if !lights.is_empty() {
+            sampling::start_bounce(light_bounce);
+            let light = lights[sampling::index(lights.len())];
+            let Material::Emissive { radiance: emitted } = light.material else {
+                unreachable!()
+            };
+            let emitted = spectral(emitted, wavelengths);
+
+            let (point, normal) = light.shape.sample();
+            let pdf_position = 1. / (lights.len() as f64 * light.shape.area());
+
+            let local = sample_cosine_hemisphere(sampling::uniform_pair());
+            let direction = Frame::from_normal(normal).to_world(&local);
+            let pdf_direction = local.z() / PI;
+
+            light_path.push(Vertex {
+                kind: VertexKind::Light,
+                point,
+                normal,
+                beta: emitted / pdf_position,
+                delta: false,
+                pdf_fwd: pdf_position,
+                pdf_rev: 0.,
+                medium: scene.medium_around(&point, Some(&light.shape)),
+            });
+
+            // The cosine in the emitted light cancels with the density's.
+            let walk = Walk {
+                ray: Ray::new(point, direction),
+                beta: emitted * (PI / pdf_position),
+                pdf_direction,
+                max_vertices: self.max_bounces,
+                first_bounce: light_bounce + 1,
+            };
+            self.random_walk(scene, walk, wavelengths, &mut light_path);
+        }
+
+        sampling::start_bounce(environment_bounce + 1);
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                // A light seen directly is only found from the camera.
+                if s + t < 2 || (s == 1 && t == 1) || s + t - 2 > self.max_bounces {
+                    continue;
+                }
+
+                let paths = (camera_path.as_slice(), light_path.as_slice());
+                let Some(connection) = connect(scene, &lights, paths, s, t, wavelengths) else {
+                    continue;
+                };
+
+                let weight = mis_weight(
+                    scene,
+                    &lights,
+                    &camera_path,
+                    &light_path,
+                    connection.sampled,
+                    s,
+                    t,
+                );
+                let light = connection.contribution * weight;
+                match (connection.position, &mut splat) {
+                    (Some(position), Some(splat)) => splat(position, light),
+                    _ => radiance += light,
+                }
+            }
+        }
+
+        radiance
+    }
+
+    /// Extends `path` from its last vertex as `walk` describes. Returns
+    /// where the path escaped the scene, if it did.
+    fn random_walk<'a>(
+        &self,
+        scene: &'a Scene,
+        walk: Walk,
+        wavelengths: Option<&SampledWavelengths>,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Option<Escape> {
+        let Walk {
+            mut ray,
+            mut beta,
+            mut pdf_direction,
+            max_vertices,
+            first_bounce,
+        } = walk;
+        let mut bsdf_pdf = None;
+        let mut medium = path[path.len() - 1].medium;
+        let mut added = 0;
+
+        while added < max_vertices {
+            let Some(hit) = scene.hit_test(&ray) else {
+                return Some(Escape {
+                    direction: ray.direction,
+                    beta,
+                    bsdf_pdf,
+                });
+            };
+            let distance = (hit.point - ray.origin).length();
+            if let Some(medium) = medium {
+                let unit_ray = Ray::new(ray.origin, ray.direction.unit());
+                beta = beta * medium.transmittance(&unit_ray, distance);
+            }
+            beta = beta * spectral(interior_transmittance(&hit, distance), wavelengths);
+
+            if let Material::Interface = hit.material {
+                medium = scene.medium_after(&hit, medium, &ray.direction);
+                ray = Ray::new(hit.point, ray.direction);
+                continue;
+            }
+
+            let previous = path.len() - 1;
+            let mut vertex = Vertex {
+                kind: VertexKind::Surface(hit),
+                point: hit.point,
+                normal: hit.normal,
+                beta,
+                delta: hit.material.flags().delta,
+                pdf_fwd: 0.,
+                pdf_rev: 0.,
+                medium,
+            };
+            vertex.pdf_fwd = convert_density(&path[previous], pdf_direction, &vertex);
+            path.push(vertex);
+            added += 1;
+            if added >= max_vertices {
+                break;
+            }
+
+            sampling::start_bounce(first_bounce + added - 1);
+            let wo = -ray.direction.unit();
+            let Some(sample) = hit.material.sample(&hit, &wo, wavelengths) else {
+                break;
+            };
+            if sample.interior.is_some() {
+                break;
+            }
+
+            let current = path.len() - 1;
+            let (pdf_fwd, pdf_rev) = if sample.flags.delta {
+                path[current].delta = true;
+                (0., 0.)
+            } else {
+                (sample.pdf, hit.material.pdf(&hit, &sample.wi, &wo))
+            };
+
+            let reverse = convert_density(&path[current], pdf_rev, &path[previous]);
+            path[previous].pdf_rev = reverse;
+
+            beta = beta * spectral(sample.weight, wavelengths);
+            pdf_direction = pdf_fwd;
+            bsdf_pdf = (scene.light_sampling && !sample.flags.delta).then_some(sample.pdf);
+            medium = scene.medium_after(&hit, medium, &sample.wi);
+            ray = Ray::new(hit.point, sample.wi);
+        }
+
+        None
+    }
+}
+
+/// The path made of the first `t` camera vertices and the first `s` light
+/// vertices. With one light vertex, a fresh point is sampled on a light
+/// instead, and with one camera vertex a fresh point on the lens.
+fn connect<'a>(
+    scene: &'a Scene,
+    lights: &[&'a Geometry],
+    (camera_path, light_path): (&[Vertex<'a>], &[Vertex<'a>]),
+    s: usize,
+    t: usize,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Option<Connection<'a>> {
+    if t == 1 {
+        return connect_to_camera(scene, camera_path, light_path, s, wavelengths);
+    }
+
+    let pt = &camera_path[t - 1];
+    let VertexKind::Surface(camera_hit) = pt.kind else {
+        return None;
+    };
+
+    if s == 0 {
+        // The camera subpath found a light by itself.
+        let wo = direction(pt, &camera_path[t - 2]);
+        let emitted = spectral(camera_hit.material.emitted(&camera_hit, &wo), wavelengths);
+        return Some(Connection {
+            contribution: pt.beta * emitted,
+            sampled: None,
+            position: None,
+        });
+    }
+
+    if pt.delta {
+        return None;
+    }
+
+    if s == 1 {
+        let light = lights[sampling::index(lights.len())];
+        let Material::Emissive { radiance } = light.material else {
+            unreachable!()
+        };
+        let radiance = spectral(radiance, wavelengths);
+        let (point, normal) = light.shape.sample();
+        let pdf_position = 1. / (lights.len() as f64 * light.shape.area());
+
+        let sampled = Vertex {
+            kind: VertexKind::Light,
+            point,
+            normal,
+            beta: radiance / pdf_position,
+            delta: false,
+            pdf_fwd: pdf_position,
+            pdf_rev: 0.,
+            medium: scene.medium_around(&point, Some(&light.shape)),
+        };
+
+        if direction(&sampled, pt).dot(&normal) <= 0. {
+            return None;
+        }
+
+        let f = eval(pt, &camera_path[t - 2], &sampled, wavelengths);
+        let g = geometry_term(scene, pt, &sampled, wavelengths);
+        let contribution = pt.beta * f * sampled.beta * g;
+        return Some(Connection {
+            contribution,
+            sampled: Some(sampled),
+            position: None,
+        });
+    }
+
+    let qs = &light_path[s - 1];
+    if qs.delta {
+        return None;
+    }
+
+    let f_camera = eval(pt, &camera_path[t - 2], qs, wavelengths);
+    let f_light = eval(qs, pt, &light_path[s - 2], wavelengths);
+    let g = geometry_term(scene, qs, pt, wavelengths);
+    let contribution = qs.beta * f_light * f_camera * pt.beta * g;
+    Some(Connection {
+        contribution,
+        sampled: None,
+        position: None,
+    })
+}
+
+/// The path made of the first `s` light vertices joined to a point sampled
+/// on the camera's lens, along with where on the image its light lands.
+fn connect_to_camera<'a>(
+    scene: &'a Scene,
+    camera_path: &[Vertex<'a>],
+    light_path: &[Vertex<'a>],
+    s: usize,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Option<Connection<'a>> {
+    let qs = &light_path[s - 1];
+    if qs.delta || camera_path[0].delta {
+        return None;
+    }
+
+    let lens = scene.camera.sample_lens(&qs.point)?;
+    let camera = Vertex {
+        kind: VertexKind::Camera,
+        point: lens.point,
+        beta: Colour::white() * (lens.importance / lens.pdf),
+        ..camera_path[0]
+    };
+
+    // The lens's density is per solid angle at `qs`, so only the cosine
+    // there is left of the geometry term.
+    let towards = direction(qs, &camera);
+    let f = eval(qs, &camera, &light_path[s - 2], wavelengths);
+    let contribution = qs.beta
+        * f
+        * camera.beta
+        * qs.normal.dot(&towards).abs()
+        * transmittance(scene, qs, &camera, wavelengths);
+
+    Some(Connection {
+        contribution,
+        sampled: Some(camera),
+        position: Some(lens.position),
+    })
+}
+
+/// The power heuristic weight of building the path with `s` light and `t`
+/// camera vertices, against every other way of building it, found from the
+/// ratios of their densities.
+fn mis_weight<'a>(
+    scene: &Scene,
+    lights: &[&'a Geometry],
+    camera_path: &[Vertex<'a>],
+    light_path: &[Vertex<'a>],
+    sampled: Option<Vertex<'a>>,
+    s: usize,
+    t: usize,
+) -> f64 {
+    if s + t == 2 {
+        return 1.;
+    }
+
+    let mut camera: Vec<Vertex> = camera_path[..t].to_vec();
+    let mut light: Vec<Vertex> = light_path[..s].to_vec();
+    match sampled {
+        Some(sampled) if t == 1 => camera[0] = sampled,
+        Some(sampled) => light[0] = sampled,
+        None => {}
+    }
+
+    // The joined vertices are only delta if the path couldn't have been
+    // joined there, which was already ruled out.
+    camera[t - 1].delta = false;
+    if s > 0 {
+        light[s - 1].delta = false;
+    }
+
+    // Densities of sampling the vertices either side of the join from the
+    // other subpath.
+    let lens = &scene.camera;
+    let pt = camera[t - 1];
+    camera[t - 1].pdf_rev = if s > 0 {
+        pdf(
+            lens,
+            &light[s - 1],
+            s.checked_sub(2).map(|i| &light[i]),
+            &pt,
+        )
+    } else {
+        pdf_light_origin(lights, &pt)
+    };
+    if t > 1 {
+        camera[t - 2].pdf_rev = if s > 0 {
+            pdf(lens, &pt, Some(&light[s - 1]), &camera[t - 2])
+        } else {
+            pdf_emission(&pt, &camera[t - 2])
+        };
+    }
+    if s > 0 {
+        let qs = light[s - 1];
+        light[s - 1].pdf_rev = pdf(lens, &pt, t.checked_sub(2).map(|i| &camera[i]), &qs);
+        if s > 1 {
+            light[s - 2].pdf_rev = pdf(lens, &qs, Some(&pt), &light[s - 2]);
+        }
+    }
+
+    let remap = |pdf: f64| if pdf == 0. { 1. } else { pdf };
+    let mut sum = 0.;
+
+    // Strategies with fewer camera vertices, down to joining light subpaths
+    // to the camera itself.
+    let mut ratio = 1.;
+    for i in (1..t).rev() {
+        ratio *= (remap(camera[i].pdf_rev) / remap(camera[i].pdf_fwd)).powi(2);
+        if !camera[i].delta && !camera[i - 1].delta {
+            sum += ratio;
+        }
+    }
+
+    // Strategies with fewer light vertices.
+    let mut ratio = 1.;
+    for i in (0..s).rev() {
+        ratio *= (remap(light[i].pdf_rev) / remap(light[i].pdf_fwd)).powi(2);
+        let delta_before = i > 0 && light[i - 1].delta;
+        if !light[i].delta && !delta_before {
+            sum += ratio;
+        }
+    }
+
+    1. / (1. + sum)
+}
+
+/// Unit direction from one vertex towards another.
+fn direction(from: &Vertex, to: &Vertex) -> Vec3 {
+    (to.point - from.point).unit()
+}
+
+/// The BSDF at `vertex` for light arriving from `from` and leaving towards
+/// `towards`.
+fn eval(
+    vertex: &Vertex,
+    towards: &Vertex,
+    from: &Vertex,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Colour {
+    match vertex.kind {
+        VertexKind::Surface(hit) => {
+            let f = hit
+                .material
+                .eval(&hit, &direction(vertex, towards), &direction(vertex, from));
+            spectral(f, wavelengths)
+        }
+        _ => Colour::black(),
+    }
+}
+
+/// Turns a solid-angle density of sampling the direction from `from` to
+/// `to` into an area density at `to`.
+fn convert_density(from: &Vertex, pdf: f64, to: &Vertex) -> f64 {
+    let offset = to.point - from.point;
+    let distance_squared = offset.length_squared();
+    if distance_squared == 0. {
+        return 0.;
+    }
+
+    let cos_theta = match to.kind {
+        VertexKind::Camera => 1.,
+        _ => to.normal.dot(&offset.unit()).abs(),
+    };
+    pdf * cos_theta / distance_squared
+}
+
+/// Area density of `vertex` choosing `next`, having been reached from
+/// `previous`.
+fn pdf(camera: &Camera, vertex: &Vertex, previous: Option<&Vertex>, next: &Vertex) -> f64 {
+    match (vertex.kind, previous) {
+        (VertexKind::Camera, _) => {
+            let ray = Ray::new(vertex.point, next.point - vertex.point);
+            convert_density(vertex, camera.pdf_direction(&ray), next)
+        }
+        (VertexKind::Light, _) => pdf_emission(vertex, next),
+        (VertexKind::Surface(hit), Some(previous)) => {
+            let pdf =
+                hit.material
+                    .pdf(&hit, &direction(vertex, previous), &direction(vertex, next));
+            convert_density(vertex, pdf, next)
+        }
+        _ => 0.,
+    }
+}
+
+/// Area density of a light at `vertex` emitting towards `next`.
+fn pdf_emission(vertex: &Vertex, next: &Vertex) -> f64 {
+    let cos_theta = vertex.normal.dot(&direction(vertex, next));
+    if cos_theta <= 0. {
+        return 0.;
+    }
+    convert_density(vertex, cos_theta / PI, next)
+}
+
+/// Area density of a light subpath starting at `vertex`.
+fn pdf_light_origin(lights: &[&Geometry], vertex: &Vertex) -> f64 {
+    match vertex.kind {
+        VertexKind::Surface(hit) if matches!(hit.material, Material::Emissive { .. }) => {
+            1. / (lights.len() as f64 * hit.shape.area())
+        }
+        _ => 0.,
+    }
+}
+
+/// The geometric coupling between two vertices, dimmed by whatever lies
+/// between them.
+fn geometry_term<'a>(
+    scene: &'a Scene,
+    a: &Vertex<'a>,
+    b: &Vertex,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Colour {
+    let offset = b.point - a.point;
+    let distance_squared = offset.length_squared();
+    if distance_squared == 0. {
+        return Colour::black();
+    }
+
+    let direction = offset.unit();
+    let cosines = a.normal.dot(&direction).abs() * b.normal.dot(&direction).abs();
+    transmittance(scene, a, b, wavelengths) * (cosines / distance_squared)
+}
+
+/// Fraction of light that makes it between two vertices. Only interfaces
+/// can lie between them, so it's only ever dimmed by media.
+fn transmittance<'a>(
+    scene: &'a Scene,
+    a: &Vertex<'a>,
+    b: &Vertex,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Colour {
+    let towards = direction(a, b);
+    let medium = match a.kind {
+        VertexKind::Surface(hit) => scene.medium_after(&hit, a.medium, &towards),
+        _ => a.medium,
+    };
+
+    spectral(scene.transmittance(&a.point, &b.point, medium), wavelengths)
+}
@@ -0,0 +1,104 @@
+use super::{power_heuristic, sample_environment, spectral, Integrator};
+use crate::{
+    colour::Colour,
+    geometry::{Bsdf, Material},
+    medium::Medium,
+    ray::Ray,
+    sampling,
+    scene::{interior_transmittance, Scene},
+    spectrum::SampledWavelengths,
+};
+
+/// Only light reaching a surface straight from the environment, found by
+/// sampling both the environment and the material and combining the two by
+/// multiple importance sampling. Mirrors and glass are followed up to
+/// `max_bounces` times so that reflections and refractions still show up.
+pub struct DirectLighting {
+    pub max_bounces: usize,
+}
+
+impl Integrator for DirectLighting {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        self.trace(scene, ray, scene.medium_at(&ray.origin), 0, wavelengths)
+    }
+}
+
+impl DirectLighting {
+    /// The light arriving along `ray`, which starts in `medium`.
+    fn trace<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        medium: Option<&'a Medium>,
+        depth: usize,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let Some(hit) = scene.hit_test(ray) else {
+            return spectral(scene.environment.radiance(&ray.direction), wavelengths);
+        };
+        let distance = (hit.point - ray.origin).length();
+
+        let colour = if let Material::Interface = hit.material {
+            let continued_ray = Ray {
+                origin: hit.point,
+                direction: ray.direction,
+            };
+            let medium = scene.medium_after(&hit, medium, &ray.direction);
+            self.trace(scene, &continued_ray, medium, depth, wavelengths)
+        } else {
+            sampling::start_bounce(depth);
+            let material = hit.material;
+            let wo = -ray.direction.unit();
+
+            let mut colour = spectral(material.emitted(&hit, &wo), wavelengths);
+            if !material.flags().delta {
+                colour += sample_environment(scene, &hit, &wo, medium, true, wavelengths);
+            }
+
+            match material.sample(&hit, &wo, wavelengths) {
+                // Subsurface scattering is indirect light, so isn't counted.
+                Some(sample) if sample.interior.is_some() => {}
+
+                Some(sample) if sample.flags.delta && depth < self.max_bounces => {
+                    let scattered_ray = Ray {
+                        origin: hit.point,
+                        direction: sample.wi,
+                    };
+                    let medium = scene.medium_after(&hit, medium, &sample.wi);
+                    colour += self.trace(scene, &scattered_ray, medium, depth + 1, wavelengths)
+                        * spectral(sample.weight, wavelengths);
+                }
+                Some(sample) if sample.flags.delta => {}
+
+                // The other half of the light sampling estimate: the
+                // sampled direction only counts if it sees the environment.
+                Some(sample) => {
+                    let scattered_ray = Ray {
+                        origin: hit.point,
+                        direction: sample.wi,
+                    };
+                    let medium = scene.medium_after(&hit, medium, &sample.wi);
+                    let transmittance = scene.transmittance_to_environment(&scattered_ray, medium);
+                    let weight =
+                        power_heuristic(sample.pdf, scene.environment.pdf(&sample.wi.unit()));
+
+                    colour += spectral(scene.environment.radiance(&sample.wi), wavelengths)
+                        * spectral(transmittance, wavelengths)
+                        * spectral(sample.weight, wavelengths)
+                        * weight;
+                }
+
+                None => {}
+            }
+
+            colour
+        };
+
+        colour * spectral(interior_transmittance(&hit, distance), wavelengths)
+    }
+}
@@ -0,0 +1,108 @@
+use super::{
+    escaped_radiance, next_interaction, sample_environment, spectral, survival_probability,
+    Integrator, Interaction,
+};
+use crate::{
+    colour::Colour, geometry::Bsdf, ray::Ray, sampling, scene::Scene, spectrum::SampledWavelengths,
+};
+
+/// A path tracer that follows each path in a loop rather than recursing,
+/// so long paths don't grow the stack.
+pub struct IterativePathTracer {
+    pub max_bounces: usize,
+    /// Bounces before Russian roulette starts ending paths carrying little
+    /// light.
+    pub roulette_depth: usize,
+}
+
+impl Integrator for IterativePathTracer {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let mut radiance = Colour::black();
+        let mut throughput = Colour::white();
+        let mut ray = *ray;
+        let mut bsdf_pdf = None;
+        let mut medium = scene.medium_at(&ray.origin);
+        let mut bounces = 0;
+
+        while bounces <= self.max_bounces {
+            sampling::start_bounce(bounces);
+            let step = next_interaction(scene, &ray, medium, wavelengths);
+            throughput = throughput * step.transmittance;
+            medium = step.medium;
+
+            match step.interaction {
+                Interaction::Medium { medium, point } => {
+                    // Light absorbed at this point is replaced by whatever the
+                    // medium emits, and scattered light carries on.
+                    let albedo = spectral(medium.albedo(), wavelengths);
+                    let emission = spectral(medium.emission(&point), wavelengths);
+                    radiance += throughput * emission * (Colour::white() - albedo);
+                    throughput = throughput * albedo;
+
+                    ray = Ray {
+                        origin: point,
+                        direction: medium.sample_phase(&ray.direction),
+                    };
+                    bsdf_pdf = None;
+                }
+                Interaction::Escaped => {
+                    radiance +=
+                        throughput * escaped_radiance(scene, &ray.direction, bsdf_pdf, wavelengths);
+                    break;
+                }
+                Interaction::Surface(hit) => {
+                    let material = hit.material;
+                    let wo = -ray.direction.unit();
+                    radiance += throughput * spectral(material.emitted(&hit, &wo), wavelengths);
+
+                    let light_sampling = scene.light_sampling && !material.flags().delta;
+                    if light_sampling {
+                        radiance += throughput
+                            * sample_environment(scene, &hit, &wo, medium, true, wavelengths);
+                    }
+
+                    let Some(sample) = material.sample(&hit, &wo, wavelengths) else {
+                        break;
+                    };
+
+                    ray = Ray {
+                        origin: hit.point,
+                        direction: sample.wi,
+                    };
+                    throughput = throughput * spectral(sample.weight, wavelengths);
+
+                    // Light scattered beneath the surface leaves it on the
+                    // side it came from.
+                    if let Some(interior) = &sample.interior {
+                        let Some((exit, walk_throughput)) =
+                            scene.random_walk(ray, hit.shape, interior)
+                        else {
+                            break;
+                        };
+                        ray = exit;
+                        throughput = throughput * spectral(walk_throughput, wavelengths);
+                    } else {
+                        medium = scene.medium_after(&hit, medium, &sample.wi);
+                    }
+
+                    bsdf_pdf = (light_sampling && !sample.flags.delta && sample.interior.is_none())
+                        .then_some(sample.pdf);
+                }
+            }
+
+            bounces += 1;
+            let survival = survival_probability(throughput, bounces, self.roulette_depth);
+            if sampling::uniform() >= survival {
+                break;
+            }
+            throughput = throughput / survival;
+        }
+
+        radiance
+    }
+}
@@ -0,0 +1,336 @@
+use std::{cell::RefCell, f64::consts::PI, rc::Rc};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use super::Integrator;
+use crate::{
+    colour::Colour,
+    sampling::{self, SampleSource},
+    scene::Scene,
+};
+
+/// Primary sample space Metropolis light transport, after Kelemen et al.
+/// Rather than sampling every pixel evenly, each path is made from a list of
+/// random numbers which is then perturbed, and the new path kept or thrown
+/// away at random based on how much light it carries compared with the
+/// last. Once a path that carries light through a hard to find gap is
+/// found, the nearby paths that also do are explored from it.
+///
+/// Paths are made and shaded by another integrator, with every random
+/// number it asks for taken from the list. Like photon mapping, this works
+/// over the whole image at once rather than being an `Integrator` itself.
+pub struct Metropolis {
+    /// Independent paths traced first to estimate the image's overall
+    /// brightness and to start the chains from.
+    pub bootstrap_samples: usize,
+    /// Chains of mutations, run in parallel.
+    pub chains: usize,
+    pub mutations_per_pixel: usize,
+    /// The chance of each mutation being a fresh path rather than a
+    /// perturbation of the current one.
+    pub large_step_probability: f64,
+    /// How far small steps move each number, on average.
+    pub sigma: f64,
+}
+
+impl Metropolis {
+    /// Renders an image `dimensions` pixels in size, with paths from
+    /// `integrator`.
+    pub fn render(
+        &self,
+        scene: &Scene,
+        integrator: &dyn Integrator,
+        dimensions: (usize, usize),
+    ) -> Vec<Colour> {
+        let pixel_count = dimensions.0 * dimensions.1;
+        let context = Context {
+            scene,
+            integrator,
+            dimensions,
+            seed: rand::random::<u64>(),
+        };
+
+        // The brightness of each bootstrap path, which chains are started
+        // from in proportion to.
+        let weights: Vec<f64> = (0..self.bootstrap_samples)
+            .into_par_iter()
+            .map(|index| {
+                let samples = self.primary_samples(context.seed, index);
+                let (_, colour) = evaluate(&context, &samples);
+                colour.luminance()
+            })
+            .collect();
+
+        let total_weight: f64 = weights.iter().sum();
+        if total_weight <= 0. {
+            return vec![Colour::black(); pixel_count];
+        }
+        let brightness = total_weight / self.bootstrap_samples as f64;
+
+        let mutations = self.mutations_per_pixel * pixel_count;
+        let image = (0..self.chains)
+            .into_par_iter()
+            .fold(
+                || vec![Colour::black(); pixel_count],
+                |mut image, chain| {
+                    let count =
+                        mutations / self.chains + usize::from(chain < mutations % self.chains);
+                    self.run_chain(&context, chain, &weights, count, &mut image);
+                    image
+                },
+            )
+            .reduce(
+                || vec![Colour::black(); pixel_count],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    a
+                },
+            );
+
+        // Each pixel was visited in proportion to its brightness, so scale
+        // by the image's overall brightness to get back to radiance.
+        let scale = brightness / self.mutations_per_pixel as f64;
+        image.into_iter().map(|colour| colour * scale).collect()
+    }
+
+    /// Runs `count` mutations of one chain, adding the light they find to
+    /// `image`.
+    fn run_chain(
+        &self,
+        context: &Context,
+        chain: usize,
+        weights: &[f64],
+        count: usize,
+        image: &mut [Colour],
+    ) {
+        let mut rng = StdRng::seed_from_u64(
+            context.seed ^ (chain as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15),
+        );
+
+        // Start from a bootstrap path, chosen in proportion to its
+        // brightness, by replaying the numbers it was made from.
+        let total: f64 = weights.iter().sum();
+        let mut choice = rng.gen::<f64>() * total;
+        let start = weights
+            .iter()
+            .position(|&weight| {
+                choice -= weight;
+                choice < 0.
+            })
+            .unwrap_or(weights.len() - 1);
+
+        let samples = self.primary_samples(context.seed, start);
+        let (mut current_pixel, mut current) = evaluate(context, &samples);
+
+        for _ in 0..count {
+            samples.borrow_mut().start_iteration();
+            let (proposed_pixel, proposed) = evaluate(context, &samples);
+
+            let current_luminance = current.luminance();
+            let proposed_luminance = proposed.luminance();
+            let accept = if current_luminance > 0. {
+                (proposed_luminance / current_luminance).min(1.)
+            } else {
+                1.
+            };
+
+            // Both paths are counted, weighted by the chance of the chain
+            // moving to each, which lowers the noise over only counting the
+            // path it ends up on.
+            if proposed_luminance > 0. {
+                image[proposed_pixel] += proposed * (accept / proposed_luminance);
+            }
+            if current_luminance > 0. {
+                image[current_pixel] += current * ((1. - accept) / current_luminance);
+            }
+
+            if rng.gen::<f64>() < accept {
+                current_pixel = proposed_pixel;
+                current = proposed;
+                samples.borrow_mut().accept();
+            } else {
+                samples.borrow_mut().reject();
+            }
+        }
+    }
+
+    fn primary_samples(&self, seed: u64, index: usize) -> Rc<RefCell<PrimarySamples>> {
+        Rc::new(RefCell::new(PrimarySamples::new(
+            seed.wrapping_add(index as u64),
+            self.sigma,
+            self.large_step_probability,
+        )))
+    }
+}
+
+/// What every path of one render is traced with.
+struct Context<'a> {
+    scene: &'a Scene,
+    integrator: &'a dyn Integrator,
+    dimensions: (usize, usize),
+    /// Where the render's numbers start from, so that a bootstrap path can
+    /// be replayed to start a chain.
+    seed: u64,
+}
+
+/// Traces a path from the numbers in `samples`, returning the pixel it went
+/// through and the light it carries.
+fn evaluate(context: &Context, samples: &Rc<RefCell<PrimarySamples>>) -> (usize, Colour) {
+    let (width, height) = context.dimensions;
+    sampling::with_source(samples.clone(), || {
+        let x = sampling::index(width);
+        let y = sampling::index(height);
+        let colour = context.scene.sample_pixel((x, y), context.integrator);
+        (y * width + x, colour)
+    })
+}
+
+#[derive(Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    /// The iteration it was last changed on.
+    modified: usize,
+    backup: f64,
+    backup_modified: usize,
+}
+
+/// The list of numbers a path is made from, mutated lazily: each number is
+/// only brought up to date with the mutations it missed when a path first
+/// asks for it.
+struct PrimarySamples {
+    rng: StdRng,
+    samples: Vec<PrimarySample>,
+    /// The next number to hand out.
+    index: usize,
+    iteration: usize,
+    large_step: bool,
+    last_large_step: usize,
+    sigma: f64,
+    large_step_probability: f64,
+}
+
+impl PrimarySamples {
+    fn new(seed: u64, sigma: f64, large_step_probability: f64) -> Self {
+        // The first path is always a fresh one.
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            samples: Vec::new(),
+            index: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            sigma,
+            large_step_probability,
+        }
+    }
+
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.modified == self.iteration {
+                sample.value = sample.backup;
+                sample.modified = sample.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    /// Brings the number at `index` up to date with the current mutation.
+    fn mutate(&mut self, index: usize) {
+        let sample = &mut self.samples[index];
+
+        // A large step replaced every number since this one was last used.
+        if sample.modified < self.last_large_step {
+            sample.value = self.rng.gen();
+            sample.modified = self.last_large_step;
+        }
+
+        sample.backup = sample.value;
+        sample.backup_modified = sample.modified;
+
+        if self.large_step {
+            sample.value = self.rng.gen();
+        } else {
+            // The small steps it missed add up to one with their combined
+            // spread, wrapping around to stay in [0, 1).
+            let steps = (self.iteration - sample.modified) as f64;
+            let offset = normal(&mut self.rng) * self.sigma * steps.sqrt();
+            sample.value = (sample.value + offset).rem_euclid(1.);
+        }
+        sample.modified = self.iteration;
+    }
+}
+
+impl SampleSource for PrimarySamples {
+    fn uniform(&mut self) -> f64 {
+        // Numbers the path hasn't asked for before start out at random.
+        if self.index == self.samples.len() {
+            self.samples.push(PrimarySample {
+                value: self.rng.gen(),
+                modified: self.last_large_step,
+                ..Default::default()
+            });
+        }
+
+        let index = self.index;
+        self.mutate(index);
+        self.index += 1;
+
+        // Guard against wrapping landing on exactly one.
+        self.samples[index].value.min(1. - f64::EPSILON)
+    }
+}
+
+/// A number from the standard normal distribution, by the Box-Muller
+/// transform.
+fn normal(rng: &mut StdRng) -> f64 {
+    let u1 = 1. - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2. * u1.ln()).sqrt() * (2. * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(samples: &PrimarySamples) -> Vec<f64> {
+        samples.samples.iter().map(|sample| sample.value).collect()
+    }
+
+    #[test]
+    fn rejected_mutations_are_undone() {
+        for large_step_probability in [0., 0.5, 1.] {
+            let mut samples = PrimarySamples::new(7, 0.05, large_step_probability);
+            (0..6).for_each(|_| _ = samples.uniform());
+
+            for _ in 0..20 {
+                let before = values(&samples);
+
+                samples.start_iteration();
+                // The proposal asks for more numbers than the path before.
+                (0..8).for_each(|_| _ = samples.uniform());
+                assert_ne!(values(&samples)[..6], before[..6]);
+
+                samples.reject();
+                assert_eq!(values(&samples)[..6], before[..6]);
+
+                // Move on, so that later rounds start from a fresh path.
+                samples.start_iteration();
+                (0..6).for_each(|_| _ = samples.uniform());
+                samples.accept();
+            }
+        }
+    }
+}
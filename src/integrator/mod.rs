@@ -0,0 +1,248 @@
+mod ambient_occlusion;
+mod bidirectional;
+mod direct;
+mod iterative;
+mod metropolis;
+mod path;
+mod photon;
+mod whitted;
+
+use crate::{
+    colour::Colour,
+    geometry::{Bsdf, Material},
+    hit::Hit,
+    medium::Medium,
+    ray::Ray,
+    sampling,
+    scene::{interior_transmittance, Scene},
+    spectrum::SampledWavelengths,
+    vec::Vec3,
+};
+
+pub use self::{
+    ambient_occlusion::AmbientOcclusion,
+    bidirectional::Bidirectional,
+    direct::DirectLighting,
+    iterative::IterativePathTracer,
+    metropolis::Metropolis,
+    path::PathTracer,
+    photon::{PhotonMapper, ProgressivePhotonMapper, SppmPixel},
+    whitted::Whitted,
+};
+
+/// Computes the light arriving at the camera along a ray.
+pub trait Integrator: Send + Sync {
+    /// Radiance travelling back along `ray`, in RGB. When rendering
+    /// spectrally, `wavelengths` are the ones the path carries, and the
+    /// radiance is instead at each of them: every colour met along the way
+    /// is evaluated at them with `spectral`, and the result is turned back
+    /// into RGB by `SampledWavelengths::to_rgb`.
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour;
+
+    /// Like `radiance`, while also tracing light from its sources to the
+    /// camera, which can land anywhere on the image. That light is handed to
+    /// `splat` along with where it lands, in pixels from the image's top
+    /// left corner. Integrators that only trace from the camera don't splat
+    /// anything.
+    fn radiance_splatting(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+        _splat: &mut dyn FnMut((f64, f64), Colour),
+    ) -> Colour {
+        self.radiance(scene, ray, wavelengths)
+    }
+}
+
+/// Where a path next meets something in the scene.
+enum Interaction<'a> {
+    /// Light scattering or being absorbed at `point` in `medium`.
+    Medium { medium: &'a Medium, point: Vec3 },
+    /// A surface other than an interface.
+    Surface(Hit<'a>),
+    /// Nothing, so the path sees the environment.
+    Escaped,
+}
+
+/// The outcome of `next_interaction`.
+struct Step<'a> {
+    interaction: Interaction<'a>,
+    /// Light lost to absorbing dielectrics on the way.
+    transmittance: Colour,
+    /// The medium the path was in when it got there.
+    medium: Option<&'a Medium>,
+}
+
+/// Follows `ray`, starting in `medium`, to where the path next interacts
+/// with the scene, passing through interfaces and sampling a distance to
+/// travel through each medium on the way. Crossing an interface isn't a
+/// bounce, so paths can pass through any number of them. Paths leaving the
+/// scene escape straight to the environment, as the scene's medium only
+/// fills the space among its shapes.
+fn next_interaction<'a>(
+    scene: &'a Scene,
+    ray: &Ray,
+    mut medium: Option<&'a Medium>,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Step<'a> {
+    let mut ray = Ray::new(ray.origin, ray.direction.unit());
+    let mut transmittance = Colour::white();
+
+    loop {
+        let Some(hit) = scene.hit_test(&ray) else {
+            return Step {
+                interaction: Interaction::Escaped,
+                transmittance,
+                medium,
+            };
+        };
+        let distance = (hit.point - ray.origin).length();
+
+        if let Some(medium) = medium {
+            if let Some(travelled) = medium.sample_distance(&ray, distance) {
+                let point = ray.at(travelled);
+                return Step {
+                    interaction: Interaction::Medium { medium, point },
+                    transmittance,
+                    medium: Some(medium),
+                };
+            }
+        }
+
+        transmittance =
+            transmittance * spectral(interior_transmittance(&hit, distance), wavelengths);
+
+        if !matches!(hit.material, Material::Interface) {
+            return Step {
+                interaction: Interaction::Surface(hit),
+                transmittance,
+                medium,
+            };
+        }
+        medium = scene.medium_after(&hit, medium, &ray.direction);
+        ray = Ray::new(hit.point, ray.direction);
+    }
+}
+
+/// Estimates light arriving directly from the environment at a hit, by
+/// sampling a direction towards it and testing for occluders. With `mis`
+/// set, it's weighted against the material sampling the same direction, for
+/// integrators that also count light found by bounced rays. `medium` is the
+/// one the path reached the hit through.
+fn sample_environment<'a>(
+    scene: &'a Scene,
+    hit: &Hit<'a>,
+    wo: &Vec3,
+    medium: Option<&'a Medium>,
+    mis: bool,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Colour {
+    let (direction, radiance, pdf) = scene.environment.sample(sampling::uniform_pair());
+    if pdf <= 0. {
+        return Colour::black();
+    }
+
+    let bsdf = hit.material.eval(hit, wo, &direction);
+    let cos_theta = direction.dot(&hit.normal).abs();
+    if cos_theta <= 0. || bsdf.luminance() <= 0. {
+        return Colour::black();
+    }
+
+    let shadow_ray = Ray {
+        origin: hit.point,
+        direction,
+    };
+    let medium = scene.medium_after(hit, medium, &direction);
+    let transmittance = scene.transmittance_to_environment(&shadow_ray, medium);
+    let weight = if mis {
+        power_heuristic(pdf, hit.material.pdf(hit, wo, &direction))
+    } else {
+        1.
+    };
+
+    spectral(radiance, wavelengths)
+        * spectral(transmittance, wavelengths)
+        * spectral(bsdf, wavelengths)
+        * (cos_theta * weight / pdf)
+}
+
+/// Radiance from the environment reaching a ray that escaped the scene.
+/// `bsdf_pdf` is the density with which the last bounce chose the ray's
+/// direction, when it also sampled the environment directly; the light is
+/// then weighted against that so it isn't counted twice.
+fn escaped_radiance(
+    scene: &Scene,
+    direction: &Vec3,
+    bsdf_pdf: Option<f64>,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Colour {
+    let radiance = spectral(scene.environment.radiance(direction), wavelengths);
+    match bsdf_pdf {
+        Some(pdf) => radiance * power_heuristic(pdf, scene.environment.pdf(&direction.unit())),
+        None => radiance,
+    }
+}
+
+/// `colour`, from the scene, as a path carrying `wavelengths` sees it:
+/// evaluated at each of them, or unchanged for paths carrying RGB. Each
+/// colour is evaluated on its own before being multiplied into the path, so
+/// light is multiplied wavelength by wavelength rather than in RGB.
+fn spectral(colour: Colour, wavelengths: Option<&SampledWavelengths>) -> Colour {
+    match wavelengths {
+        Some(wavelengths) => wavelengths.evaluate(colour),
+        None => colour,
+    }
+}
+
+/// The chance of a path carrying `throughput` surviving Russian roulette
+/// after `bounces` bounces. Paths are always kept for the first
+/// `roulette_depth` bounces, and after that in proportion to how much light
+/// they could still carry; survivors are divided by this to make up for the
+/// ones that were ended, so the result stays unbiased.
+fn survival_probability(throughput: Colour, bounces: usize, roulette_depth: usize) -> f64 {
+    if bounces < roulette_depth {
+        1.
+    } else {
+        throughput.max_component().min(0.95)
+    }
+}
+
+/// The power heuristic with an exponent of two, weighting a sample taken
+/// with density `pdf` against another strategy with density `other`.
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b <= 0. {
+        0.
+    } else {
+        a / (a + b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_survive_until_the_roulette_depth() {
+        let dim = Colour::new(0.01, 0.02, 0.);
+
+        for bounces in 0..3 {
+            assert_eq!(survival_probability(dim, bounces, 3), 1.);
+        }
+        assert_eq!(survival_probability(dim, 3, 3), 0.02);
+    }
+
+    #[test]
+    fn survival_follows_the_brightest_channel() {
+        assert_eq!(survival_probability(Colour::new(0.1, 0.3, 0.2), 5, 3), 0.3);
+        assert_eq!(survival_probability(Colour::new(0.2, 0.9, 0.4), 5, 3), 0.9);
+        assert_eq!(survival_probability(Colour::new(2., 0.5, 0.), 5, 3), 0.95);
+        assert_eq!(survival_probability(Colour::black(), 5, 3), 0.);
+    }
+}
@@ -0,0 +1,169 @@
+use super::{
+    escaped_radiance, next_interaction, sample_environment, spectral, survival_probability,
+    Integrator, Interaction,
+};
+use crate::{
+    colour::Colour, geometry::Bsdf, hit::Hit, medium::Medium, ray::Ray, sampling, scene::Scene,
+    spectrum::SampledWavelengths,
+};
+
+/// A recursive path tracer, following one bounce of each path per call.
+pub struct PathTracer {
+    pub max_bounces: usize,
+    /// Bounces before Russian roulette starts ending paths carrying little
+    /// light.
+    pub roulette_depth: usize,
+}
+
+impl Integrator for PathTracer {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let path = Path {
+            bounces: 0,
+            throughput: Colour::white(),
+            bsdf_pdf: None,
+            medium: scene.medium_at(&ray.origin),
+        };
+        self.ray_colour(scene, ray, path, wavelengths)
+    }
+}
+
+/// The state of a path carried from one bounce to the next.
+#[derive(Clone, Copy)]
+struct Path<'a> {
+    bounces: usize,
+    /// The fraction of light arriving at the current ray's end that makes
+    /// it back to the camera, used for Russian roulette.
+    throughput: Colour,
+    /// The density with which the previous bounce chose this ray's
+    /// direction, when it also sampled the environment directly. Light from
+    /// the environment is then weighted against that by multiple importance
+    /// sampling so it isn't counted twice.
+    bsdf_pdf: Option<f64>,
+    /// The medium the current ray starts in.
+    medium: Option<&'a Medium>,
+}
+
+impl PathTracer {
+    fn ray_colour<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        path: Path<'a>,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        if path.bounces > self.max_bounces {
+            return Colour::black();
+        }
+
+        sampling::start_bounce(path.bounces);
+        let step = next_interaction(scene, ray, path.medium, wavelengths);
+        let path = Path {
+            medium: step.medium,
+            ..path
+        };
+        let colour = match step.interaction {
+            Interaction::Medium { medium, point } => {
+                let scattered_ray = Ray {
+                    origin: point,
+                    direction: medium.sample_phase(&ray.direction),
+                };
+
+                // Light absorbed at this point is replaced by whatever the
+                // medium emits, and scattered light carries on.
+                let albedo = spectral(medium.albedo(), wavelengths);
+                let emitted =
+                    spectral(medium.emission(&point), wavelengths) * (Colour::white() - albedo);
+                emitted + self.bounce(scene, &scattered_ray, path, albedo, None, wavelengths)
+            }
+            Interaction::Surface(hit) => self.shade(scene, ray, &hit, path, wavelengths),
+            Interaction::Escaped => {
+                escaped_radiance(scene, &ray.direction, path.bsdf_pdf, wavelengths)
+            }
+        };
+
+        colour * step.transmittance
+    }
+
+    fn shade<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        hit: &Hit<'a>,
+        path: Path<'a>,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let material = hit.material;
+        let wo = -ray.direction.unit();
+
+        let mut colour = spectral(material.emitted(hit, &wo), wavelengths);
+
+        let light_sampling = scene.light_sampling && !material.flags().delta;
+        if light_sampling {
+            colour += sample_environment(scene, hit, &wo, path.medium, true, wavelengths);
+        }
+
+        let Some(sample) = material.sample(hit, &wo, wavelengths) else {
+            return colour;
+        };
+
+        let mut scattered_ray = Ray {
+            origin: hit.point,
+            direction: sample.wi,
+        };
+        let mut weight = spectral(sample.weight, wavelengths);
+        let mut medium = scene.medium_after(hit, path.medium, &sample.wi);
+
+        // Light scattered beneath the surface leaves it on the side it came
+        // from.
+        if let Some(interior) = &sample.interior {
+            let Some((exit, throughput)) = scene.random_walk(scattered_ray, hit.shape, interior)
+            else {
+                return colour;
+            };
+            scattered_ray = exit;
+            weight = weight * spectral(throughput, wavelengths);
+            medium = path.medium;
+        }
+
+        // Directions that light sampling could also have chosen are weighted
+        // by multiple importance sampling if they escape.
+        let bsdf_pdf = (light_sampling && !sample.flags.delta && sample.interior.is_none())
+            .then_some(sample.pdf);
+
+        let path = Path { medium, ..path };
+        colour + self.bounce(scene, &scattered_ray, path, weight, bsdf_pdf, wavelengths)
+    }
+
+    /// Follows `path` along `ray` after a bounce scaling its light by
+    /// `weight`, unless Russian roulette ends it.
+    fn bounce<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        path: Path<'a>,
+        weight: Colour,
+        bsdf_pdf: Option<f64>,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let bounces = path.bounces + 1;
+        let throughput = path.throughput * weight;
+
+        let survival = survival_probability(throughput, bounces, self.roulette_depth);
+        if sampling::uniform() >= survival {
+            return Colour::black();
+        }
+
+        let path = Path {
+            bounces,
+            throughput: throughput / survival,
+            bsdf_pdf,
+            medium: path.medium,
+        };
+        self.ray_colour(scene, ray, path, wavelengths) * (weight / survival)
+    }
+}
@@ -0,0 +1,279 @@
+use std::{cmp::Ordering, collections::BinaryHeap, ops::Range};
+
+use super::Photon;
+use crate::vec::Vec3;
+
+/// Photons arranged as a balanced kd-tree for finding those near a point.
+/// The tree is implicit in the order of the photons: each range's middle
+/// photon splits the rest along its axis, with the smaller ones before it
+/// and the larger ones after.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    /// The axis each photon splits its range along, by index.
+    axes: Vec<usize>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+
+        Self { photons, axes }
+    }
+
+    /// Calls `f` with every photon within `radius` of `point`.
+    pub fn within(&self, point: &Vec3, radius: f64, mut f: impl FnMut(&Photon)) {
+        self.visit(
+            0..self.photons.len(),
+            point,
+            radius * radius,
+            &mut |photon, _| {
+                f(photon);
+                None
+            },
+        );
+    }
+
+    /// The `count` photons closest to `point`, as long as they're within
+    /// `max_radius`, with the squared distance to the furthest one found.
+    pub fn nearest(&self, point: &Vec3, count: usize, max_radius: f64) -> (Vec<&Photon>, f64) {
+        let mut heap = BinaryHeap::with_capacity(count + 1);
+
+        self.visit(
+            0..self.photons.len(),
+            point,
+            max_radius * max_radius,
+            &mut |photon, distance_squared| {
+                heap.push(Neighbour {
+                    distance_squared,
+                    photon,
+                });
+                if heap.len() > count {
+                    heap.pop();
+                }
+
+                // Once enough are found, only closer photons are of interest.
+                (heap.len() == count).then(|| heap.peek().unwrap().distance_squared)
+            },
+        );
+
+        let furthest = heap.peek().map_or(0., |n| n.distance_squared);
+        (heap.into_iter().map(|n| n.photon).collect(), furthest)
+    }
+
+    /// Calls `f` with each photon in `range` within the squared search
+    /// radius of `point`, along with its squared distance. `f` can return a
+    /// smaller squared radius to narrow the rest of the search.
+    fn visit<'a>(
+        &'a self,
+        range: Range<usize>,
+        point: &Vec3,
+        mut radius_squared: f64,
+        f: &mut impl FnMut(&'a Photon, f64) -> Option<f64>,
+    ) -> f64 {
+        if range.is_empty() {
+            return radius_squared;
+        }
+
+        let middle = (range.start + range.end) / 2;
+        let photon = &self.photons[middle];
+        let axis = self.axes[middle];
+        let offset = component(point, axis) - component(&photon.point, axis);
+
+        // Search the side the point is on first, so the radius shrinks
+        // as quickly as possible.
+        let (near, far) = if offset < 0. {
+            (range.start..middle, middle + 1..range.end)
+        } else {
+            (middle + 1..range.end, range.start..middle)
+        };
+
+        radius_squared = self.visit(near, point, radius_squared, f);
+
+        let distance_squared = (photon.point - *point).length_squared();
+        if distance_squared <= radius_squared {
+            if let Some(narrowed) = f(photon, distance_squared) {
+                radius_squared = narrowed;
+            }
+        }
+
+        if offset * offset <= radius_squared {
+            radius_squared = self.visit(far, point, radius_squared, f);
+        }
+
+        radius_squared
+    }
+}
+
+fn build(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.is_empty() {
+        return;
+    }
+
+    // Split along whichever axis the photons are most spread out on.
+    let (min, max) = photons.iter().fold(
+        ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]),
+        |(mut min, mut max), photon| {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(component(&photon.point, axis));
+                max[axis] = max[axis].max(component(&photon.point, axis));
+            }
+            (min, max)
+        },
+    );
+    let axis = (0..3)
+        .max_by(|&a, &b| (max[a] - min[a]).total_cmp(&(max[b] - min[b])))
+        .unwrap();
+
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| {
+        component(&a.point, axis).total_cmp(&component(&b.point, axis))
+    });
+    axes[middle] = axis;
+
+    let (photons_below, photons_above) = photons.split_at_mut(middle);
+    let (axes_below, axes_above) = axes.split_at_mut(middle);
+    build(photons_below, axes_below);
+    build(&mut photons_above[1..], &mut axes_above[1..]);
+}
+
+fn component(v: &Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => v.x(),
+        1 => v.y(),
+        _ => v.z(),
+    }
+}
+
+/// A photon found by a nearest neighbour search, ordered by distance so the
+/// heap keeps the furthest on top.
+struct Neighbour<'a> {
+    distance_squared: f64,
+    photon: &'a Photon,
+}
+
+impl PartialEq for Neighbour<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance_squared == other.distance_squared
+    }
+}
+
+impl Eq for Neighbour<'_> {}
+
+impl PartialOrd for Neighbour<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbour<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::colour::Colour;
+
+    /// Photons scattered through a box, some in a tight cluster and some
+    /// stacked on the same point, each with its index as its power so that
+    /// searches can be checked photon by photon.
+    fn photons(rng: &mut StdRng) -> Vec<Photon> {
+        let mut point = |scale: f64| {
+            Vec3::new((
+                rng.gen::<f64>() * scale,
+                rng.gen::<f64>() * scale,
+                rng.gen::<f64>() * scale,
+            ))
+        };
+        let mut points: Vec<Vec3> = (0..400).map(|_| point(4.)).collect();
+        points.extend((0..100).map(|_| Vec3::new((1., 1., 1.)) + point(0.1)));
+        points.extend([Vec3::new((2., 2., 2.)); 10]);
+
+        points
+            .into_iter()
+            .enumerate()
+            .map(|(i, point)| Photon {
+                point,
+                wi: Vec3::new((0., 0., 1.)),
+                power: Colour::new(i as f64, 0., 0.),
+            })
+            .collect()
+    }
+
+    fn index(photon: &Photon) -> usize {
+        photon.power.r() as usize
+    }
+
+    fn queries(rng: &mut StdRng) -> Vec<Vec3> {
+        let mut queries: Vec<Vec3> = (0..50)
+            .map(|_| {
+                Vec3::new((
+                    rng.gen::<f64>() * 5. - 0.5,
+                    rng.gen::<f64>() * 5. - 0.5,
+                    rng.gen::<f64>() * 5. - 0.5,
+                ))
+            })
+            .collect();
+        queries.extend([Vec3::new((1.05, 1.05, 1.05)), Vec3::new((2., 2., 2.))]);
+        queries
+    }
+
+    #[test]
+    fn within_finds_exactly_the_photons_in_range() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let photons = photons(&mut rng);
+        let points: Vec<Vec3> = photons.iter().map(|photon| photon.point).collect();
+        let map = PhotonMap::new(photons);
+
+        for query in queries(&mut rng) {
+            for radius in [0., 0.05, 0.3, 1., 10.] {
+                let mut found = Vec::new();
+                map.within(&query, radius, |photon| found.push(index(photon)));
+                found.sort_unstable();
+
+                let expected: Vec<usize> = (0..points.len())
+                    .filter(|&i| (points[i] - query).length_squared() <= radius * radius)
+                    .collect();
+                assert_eq!(found, expected, "within {radius} of {query:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_finds_the_closest_photons_in_range() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let photons = photons(&mut rng);
+        let points: Vec<Vec3> = photons.iter().map(|photon| photon.point).collect();
+        let map = PhotonMap::new(photons);
+
+        for query in queries(&mut rng) {
+            for (count, max_radius) in [(1, 10.), (8, 0.5), (50, 1.), (600, 10.)] {
+                let (found, furthest) = map.nearest(&query, count, max_radius);
+
+                // Photons at the same distance can be found in any order, so
+                // compare distances.
+                let mut distances: Vec<f64> = found
+                    .iter()
+                    .map(|photon| (photon.point - query).length_squared())
+                    .collect();
+                distances.sort_by(f64::total_cmp);
+
+                let mut expected: Vec<f64> = points
+                    .iter()
+                    .map(|point| (*point - query).length_squared())
+                    .filter(|&distance| distance <= max_radius * max_radius)
+                    .collect();
+                expected.sort_by(f64::total_cmp);
+                expected.truncate(count);
+
+                assert_eq!(distances, expected, "{count} nearest {query:?}");
+                assert_eq!(furthest, expected.last().copied().unwrap_or(0.));
+            }
+        }
+    }
+}
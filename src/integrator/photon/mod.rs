@@ -0,0 +1,285 @@
+mod kd_tree;
+mod progressive;
+
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use self::kd_tree::PhotonMap;
+use super::{next_interaction, sample_environment, spectral, Integrator, Interaction};
+use crate::{
+    colour::Colour,
+    geometry::{sample_cosine_hemisphere, Bsdf, Geometry, Material},
+    hit::Hit,
+    ray::Ray,
+    sampling,
+    scene::Scene,
+    spectrum::SampledWavelengths,
+    vec::{Frame, Vec3},
+};
+
+pub use self::progressive::{ProgressivePhotonMapper, SppmPixel};
+
+/// A photon mapper. Before rendering, photons are sent out from `Emissive`
+/// surfaces and stored wherever they land on a surface that isn't a perfect
+/// mirror or glass. Camera rays are followed through mirrors and glass to
+/// the first other surface, and the light there is estimated from the
+/// photons nearest to it. Photons and camera rays alike scatter through
+/// participating media on the way, but photons are only stored on surfaces,
+/// so light in a medium is only seen where it lands on one. Light focused through glass, which a path tracer
+/// struggles to find, shows up as a dense patch of photons.
+///
+/// Photon mapping is biased, blurring lighting over the area the photons
+/// are gathered from, but becomes sharper with more photons. Light from the
+/// environment is only counted directly at the surface seen. Photons are
+/// traced in RGB, without dispersion, and their power is only evaluated at a
+/// camera path's wavelengths where they're gathered.
+pub struct PhotonMapper {
+    photon_map: PhotonMap,
+    /// How many photons were sent out, which their power is shared between.
+    photon_count: usize,
+    pub max_bounces: usize,
+    /// How many of the nearest photons to estimate light from.
+    pub neighbours: usize,
+    /// How far away photons can be gathered from.
+    pub max_radius: f64,
+}
+
+impl PhotonMapper {
+    pub fn new(
+        scene: &Scene,
+        photon_count: usize,
+        max_bounces: usize,
+        neighbours: usize,
+        max_radius: f64,
+    ) -> Self {
+        Self {
+            photon_map: PhotonMap::new(trace_photons(scene, photon_count, max_bounces)),
+            photon_count,
+            max_bounces,
+            neighbours,
+            max_radius,
+        }
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let (direct, visible) = visible_point(scene, ray, self.max_bounces, wavelengths);
+        let Some(visible) = visible else {
+            return direct;
+        };
+
+        let (photons, radius_squared) =
+            self.photon_map
+                .nearest(&visible.hit.point, self.neighbours, self.max_radius);
+        if photons.is_empty() {
+            return direct;
+        }
+
+        // Fewer photons than asked for were in reach, so they cover the whole
+        // search area.
+        let radius_squared = if photons.len() < self.neighbours {
+            self.max_radius * self.max_radius
+        } else {
+            radius_squared
+        };
+
+        let flux = photons.into_iter().fold(Colour::black(), |flux, photon| {
+            flux + visible.reflected(photon, wavelengths)
+        });
+        direct + visible.beta * flux / (PI * radius_squared * self.photon_count as f64)
+    }
+}
+
+/// A photon stored where it landed on a surface.
+struct Photon {
+    point: Vec3,
+    /// The direction it arrived from.
+    wi: Vec3,
+    /// The light it carries, as a share of all the light from `Emissive`
+    /// surfaces. This is before dividing by the number of photons sent.
+    power: Colour,
+}
+
+/// Sends out `count` photons from the scene's `Emissive` surfaces, returning
+/// every place they were stored.
+fn trace_photons(scene: &Scene, count: usize, max_bounces: usize) -> Vec<Photon> {
+    let lights: Vec<&Geometry> = scene
+        .geometries
+        .iter()
+        .filter(|geo| matches!(geo.material, Material::Emissive { .. }))
+        .collect();
+    if lights.is_empty() {
+        return Vec::new();
+    }
+
+    (0..count)
+        .into_par_iter()
+        .flat_map_iter(|_| trace_photon(scene, &lights, max_bounces))
+        .collect()
+}
+
+fn trace_photon(scene: &Scene, lights: &[&Geometry], max_bounces: usize) -> Vec<Photon> {
+    let light = lights[sampling::index(lights.len())];
+    let Material::Emissive { radiance } = light.material else {
+        unreachable!()
+    };
+
+    let (point, normal) = light.shape.sample();
+    let pdf_position = 1. / (lights.len() as f64 * light.shape.area());
+    let local = sample_cosine_hemisphere(sampling::uniform_pair());
+
+    // The cosine in the emitted light cancels with the density's.
+    let mut power = radiance * (PI / pdf_position);
+    let mut ray = Ray::new(point, Frame::from_normal(normal).to_world(&local));
+    let mut medium = scene.medium_around(&point, Some(&light.shape));
+    let mut photons = Vec::new();
+    let mut bounces = 0;
+
+    while bounces <= max_bounces {
+        let step = next_interaction(scene, &ray, medium, None);
+        power = power * step.transmittance;
+        medium = step.medium;
+
+        let hit = match step.interaction {
+            Interaction::Surface(hit) => hit,
+            Interaction::Escaped => break,
+            Interaction::Medium { medium, point } => {
+                // Photons are absorbed by the medium as they are by
+                // surfaces below, and otherwise scatter on.
+                let albedo = medium.albedo();
+                let survival = albedo.max_component().min(1.);
+                if sampling::uniform() >= survival {
+                    break;
+                }
+
+                power = power * albedo / survival;
+                ray = Ray::new(point, medium.sample_phase(&ray.direction));
+                bounces += 1;
+                continue;
+            }
+        };
+
+        let wi = -ray.direction.unit();
+        if !hit.material.flags().delta {
+            photons.push(Photon {
+                point: hit.point,
+                wi,
+                power,
+            });
+        }
+
+        let Some(sample) = hit.material.sample(&hit, &wi, None) else {
+            break;
+        };
+        if sample.interior.is_some() {
+            break;
+        }
+
+        // Photons are absorbed in proportion to how much light the surface
+        // takes, so the survivors keep carrying a similar amount.
+        let survival = sample.weight.max_component().min(1.);
+        if sampling::uniform() >= survival {
+            break;
+        }
+
+        power = power * sample.weight / survival;
+        medium = scene.medium_after(&hit, medium, &sample.wi);
+        ray = Ray::new(hit.point, sample.wi);
+        bounces += 1;
+    }
+
+    photons
+}
+
+/// The first surface a camera ray reaches that isn't a perfect mirror or
+/// glass, where light is estimated from photons.
+struct VisiblePoint<'a> {
+    hit: Hit<'a>,
+    wo: Vec3,
+    /// The fraction of light leaving the surface that reaches the camera.
+    beta: Colour,
+}
+
+impl VisiblePoint<'_> {
+    /// The light from `photon` reflected towards the camera, before dividing
+    /// by the area it was gathered from.
+    fn reflected(&self, photon: &Photon, wavelengths: Option<&SampledWavelengths>) -> Colour {
+        let f = self.hit.material.eval(&self.hit, &self.wo, &photon.wi);
+        spectral(f, wavelengths) * spectral(photon.power, wavelengths)
+    }
+}
+
+/// Follows `ray` through mirrors, glass and media to the surface it's seen
+/// on, returning the light found on the way along with the surface, if any.
+/// The light includes the environment lighting the surface directly.
+fn visible_point<'a>(
+    scene: &'a Scene,
+    ray: &Ray,
+    max_bounces: usize,
+    wavelengths: Option<&SampledWavelengths>,
+) -> (Colour, Option<VisiblePoint<'a>>) {
+    let mut ray = *ray;
+    let mut beta = Colour::white();
+    let mut light = Colour::black();
+    let mut medium = scene.medium_at(&ray.origin);
+    let mut bounces = 0;
+
+    loop {
+        sampling::start_bounce(bounces);
+        let step = next_interaction(scene, &ray, medium, wavelengths);
+        beta = beta * step.transmittance;
+        medium = step.medium;
+
+        let hit = match step.interaction {
+            Interaction::Surface(hit) => hit,
+            Interaction::Escaped => {
+                let radiance = scene.environment.radiance(&ray.direction);
+                light += beta * spectral(radiance, wavelengths);
+                return (light, None);
+            }
+            Interaction::Medium { medium, point } => {
+                let albedo = spectral(medium.albedo(), wavelengths);
+                let emission = spectral(medium.emission(&point), wavelengths);
+                light += beta * emission * (Colour::white() - albedo);
+                beta = beta * albedo;
+
+                if bounces == max_bounces {
+                    return (light, None);
+                }
+                ray = Ray::new(point, medium.sample_phase(&ray.direction));
+                bounces += 1;
+                continue;
+            }
+        };
+
+        let wo = -ray.direction.unit();
+        light += beta * spectral(hit.material.emitted(&hit, &wo), wavelengths);
+
+        if !hit.material.flags().delta {
+            light += beta * sample_environment(scene, &hit, &wo, medium, false, wavelengths);
+            return (light, Some(VisiblePoint { hit, wo, beta }));
+        }
+
+        if bounces == max_bounces {
+            return (light, None);
+        }
+        let Some(sample) = hit.material.sample(&hit, &wo, wavelengths) else {
+            return (light, None);
+        };
+        if sample.interior.is_some() {
+            return (light, None);
+        }
+
+        beta = beta * spectral(sample.weight, wavelengths);
+        medium = scene.medium_after(&hit, medium, &sample.wi);
+        ray = Ray::new(hit.point, sample.wi);
+        bounces += 1;
+    }
+}
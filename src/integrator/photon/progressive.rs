@@ -0,0 +1,134 @@
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use super::{trace_photons, visible_point, PhotonMap};
+use crate::{colour::Colour, sampling, scene::Scene, spectrum::SampledWavelengths};
+
+/// How much of the newly found photons each pass keeps when shrinking the
+/// gather radius. Smaller values shrink it faster.
+const ALPHA: f64 = 2. / 3.;
+
+/// Stochastic progressive photon mapping. Each pass traces one camera ray
+/// per pixel and a fresh set of photons, and the radius photons are gathered
+/// within shrinks from pass to pass around each pixel. The blurring of
+/// photon mapping fades as passes accumulate, so unlike `PhotonMapper` the
+/// result converges to the right answer.
+///
+/// As it needs statistics kept per pixel between passes, it's driven by
+/// `pass` over a whole image rather than being an `Integrator`.
+pub struct ProgressivePhotonMapper {
+    pub max_bounces: usize,
+    pub photons_per_pass: usize,
+    /// The radius photons are first gathered within.
+    pub initial_radius: f64,
+}
+
+/// The statistics kept for one pixel between passes.
+#[derive(Clone)]
+pub struct SppmPixel {
+    passes: usize,
+    radius: f64,
+    /// How many photons have been gathered, after shrinking.
+    photons: f64,
+    /// Light reflected from gathered photons, scaled down along with the
+    /// gather area as the radius shrinks.
+    flux: Colour,
+    /// Light found without photons, summed over passes.
+    direct: Colour,
+}
+
+impl ProgressivePhotonMapper {
+    /// Statistics for `count` pixels before any passes.
+    pub fn start(&self, count: usize) -> Vec<SppmPixel> {
+        vec![
+            SppmPixel {
+                passes: 0,
+                radius: self.initial_radius,
+                photons: 0.,
+                flux: Colour::black(),
+                direct: Colour::black(),
+            };
+            count
+        ]
+    }
+
+    /// Runs one pass over an image `width` pixels wide.
+    pub fn pass(&self, scene: &Scene, pixels: &mut [SppmPixel], width: usize) {
+        let visible: Vec<_> = (0..pixels.len())
+            .into_par_iter()
+            .map(|index| {
+                let ray = scene
+                    .camera
+                    .screen_to_world_sampled((index % width, index / width));
+                let wavelengths = scene
+                    .spectral
+                    .then(|| SampledWavelengths::sample(sampling::uniform()));
+
+                let (direct, visible) = match ray {
+                    Some(ray) => visible_point(scene, &ray, self.max_bounces, wavelengths.as_ref()),
+                    None => (Colour::black(), None),
+                };
+                (direct, visible, wavelengths)
+            })
+            .collect();
+
+        let photon_map = PhotonMap::new(trace_photons(
+            scene,
+            self.photons_per_pass,
+            self.max_bounces,
+        ));
+
+        pixels
+            .par_iter_mut()
+            .zip(visible)
+            .for_each(|(pixel, (direct, visible, wavelengths))| {
+                let to_rgb = |radiance| {
+                    let rgb = match &wavelengths {
+                        Some(wavelengths) => wavelengths.to_rgb(radiance),
+                        None => radiance,
+                    };
+                    rgb * scene.camera.exposure()
+                };
+
+                pixel.passes += 1;
+                pixel.direct += to_rgb(direct);
+
+                let Some(visible) = visible else {
+                    return;
+                };
+
+                let mut flux = Colour::black();
+                let mut gathered = 0.;
+                photon_map.within(&visible.hit.point, pixel.radius, |photon| {
+                    flux += visible.reflected(photon, wavelengths.as_ref());
+                    gathered += 1.;
+                });
+                if gathered == 0. {
+                    return;
+                }
+                let flux = to_rgb(visible.beta * flux);
+
+                let photons = pixel.photons + ALPHA * gathered;
+                let radius = pixel.radius * (photons / (pixel.photons + gathered)).sqrt();
+                let shrink = (radius / pixel.radius).powi(2);
+
+                pixel.flux = (pixel.flux + flux) * shrink;
+                pixel.photons = photons;
+                pixel.radius = radius;
+            });
+    }
+
+    /// The pixel's colour from the passes so far.
+    pub fn radiance(&self, pixel: &SppmPixel) -> Colour {
+        if pixel.passes == 0 {
+            return Colour::black();
+        }
+
+        let passes = pixel.passes as f64;
+        let photons = passes * self.photons_per_pass as f64;
+        let area = PI * pixel.radius * pixel.radius;
+
+        pixel.direct / passes + pixel.flux / (photons * area)
+    }
+}
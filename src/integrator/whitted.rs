@@ -0,0 +1,82 @@
+use super::{sample_environment, spectral, Integrator};
+use crate::{
+    colour::Colour,
+    geometry::{Bsdf, Material},
+    medium::Medium,
+    ray::Ray,
+    sampling,
+    scene::{interior_transmittance, Scene},
+    spectrum::SampledWavelengths,
+};
+
+/// A classic Whitted ray tracer: mirrors and glass are followed, and every
+/// other surface is lit only by the environment directly, with shadows but
+/// no bounced light. Media only cast shadows.
+pub struct Whitted {
+    pub max_depth: usize,
+}
+
+impl Integrator for Whitted {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        self.trace(scene, ray, scene.medium_at(&ray.origin), 0, wavelengths)
+    }
+}
+
+impl Whitted {
+    /// The light arriving along `ray`, which starts in `medium`.
+    fn trace<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        medium: Option<&'a Medium>,
+        depth: usize,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Colour {
+        let Some(hit) = scene.hit_test(ray) else {
+            return spectral(scene.environment.radiance(&ray.direction), wavelengths);
+        };
+        let distance = (hit.point - ray.origin).length();
+
+        let colour = if let Material::Interface = hit.material {
+            let continued_ray = Ray {
+                origin: hit.point,
+                direction: ray.direction,
+            };
+            let medium = scene.medium_after(&hit, medium, &ray.direction);
+            self.trace(scene, &continued_ray, medium, depth, wavelengths)
+        } else {
+            sampling::start_bounce(depth);
+            let material = hit.material;
+            let wo = -ray.direction.unit();
+
+            let mut colour = spectral(material.emitted(&hit, &wo), wavelengths);
+            if !material.flags().delta {
+                colour += sample_environment(scene, &hit, &wo, medium, false, wavelengths);
+            }
+
+            // Only perfectly specular bounces are followed, since everything
+            // else was accounted for by sampling the environment.
+            let specular = material
+                .sample(&hit, &wo, wavelengths)
+                .filter(|sample| sample.flags.delta && sample.interior.is_none());
+            if let Some(sample) = specular.filter(|_| depth < self.max_depth) {
+                let scattered_ray = Ray {
+                    origin: hit.point,
+                    direction: sample.wi,
+                };
+                let medium = scene.medium_after(&hit, medium, &sample.wi);
+                colour += self.trace(scene, &scattered_ray, medium, depth + 1, wavelengths)
+                    * spectral(sample.weight, wavelengths);
+            }
+
+            colour
+        };
+
+        colour * spectral(interior_transmittance(&hit, distance), wavelengths)
+    }
+}
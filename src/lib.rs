@@ -1,8 +1,15 @@
 pub mod camera;
 pub mod colour;
+pub mod environment;
+pub mod film;
 pub mod geometry;
 pub mod hit;
+pub mod integrator;
+pub mod medium;
 pub mod pixel;
 pub mod ray;
+pub mod sampling;
 pub mod scene;
+pub mod spectrum;
+pub mod texture;
 pub mod vec;
@@ -0,0 +1,176 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::vec::Vec3;
+
+const MAGIC: &[u8; 4] = b"VOXL";
+
+/// A dense 3D grid of values such as smoke density or temperature, sampled
+/// at voxel centres and interpolated trilinearly.
+///
+/// On disk a grid is the bytes `VOXL`, followed by the X, Y and Z
+/// resolutions as little-endian `u32`s, followed by one little-endian `f32`
+/// per voxel with X varying fastest and Z slowest. Grids are referred to by
+/// path in scene files, relative to the scene file. A grid read from a scene
+/// file is empty until `load_voxels` reads the file it refers to.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct DensityGrid {
+    path: String,
+    resolution: (usize, usize, usize),
+    values: Arc<Vec<f32>>,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn new(resolution: (usize, usize, usize), values: Vec<f32>) -> anyhow::Result<Self> {
+        let (nx, ny, nz) = resolution;
+        if nx == 0 || ny == 0 || nz == 0 || values.len() != nx * ny * nz {
+            bail!(
+                "grid of {nx}x{ny}x{nz} voxels can't hold {} values",
+                values.len()
+            );
+        }
+
+        let max = values.iter().copied().fold(0., f32::max) as f64;
+
+        Ok(Self {
+            path: String::new(),
+            resolution,
+            values: Arc::new(values),
+            max,
+        })
+    }
+
+    /// Reads a grid from `path`, relative to `scene_dir`, the directory of
+    /// the scene file referring to it.
+    pub fn load(scene_dir: &Path, path: &str) -> anyhow::Result<Self> {
+        let file = scene_dir.join(path);
+        let mut reader = BufReader::new(
+            File::open(&file).with_context(|| format!("opening '{}'", file.display()))?,
+        );
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("'{path}' is not a voxel grid");
+        }
+
+        let mut read_u32 = || -> anyhow::Result<usize> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes) as usize)
+        };
+        let resolution = (read_u32()?, read_u32()?, read_u32()?);
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let values = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        let mut grid =
+            Self::new(resolution, values).with_context(|| format!("loading '{path}'"))?;
+        grid.path = path.to_string();
+        Ok(grid)
+    }
+
+    /// Reads the voxels of a grid read from a scene file in `scene_dir`.
+    pub fn load_voxels(&mut self, scene_dir: &Path) -> anyhow::Result<()> {
+        *self = Self::load(scene_dir, &self.path)?;
+        Ok(())
+    }
+
+    /// Writes the grid in the format read by `load`, and makes `path` the
+    /// grid's path when it is saved into a scene file. Like `load`'s, `path`
+    /// is relative to `scene_dir`.
+    pub fn save(&mut self, scene_dir: &Path, path: &str) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(scene_dir.join(path))?);
+
+        writer.write_all(MAGIC)?;
+        let (nx, ny, nz) = self.resolution;
+        for n in [nx, ny, nz] {
+            writer.write_all(&(n as u32).to_le_bytes())?;
+        }
+        for value in self.values.iter() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()?;
+
+        self.path = path.to_string();
+        Ok(())
+    }
+
+    pub fn resolution(&self) -> (usize, usize, usize) {
+        self.resolution
+    }
+
+    /// The largest value in the grid, which bounds any interpolated lookup.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let (nx, ny, _) = self.resolution;
+        self.values[(z * ny + y) * nx + x] as f64
+    }
+
+    /// Trilinearly interpolated value at `p`, where the grid spans the unit
+    /// cube. Points outside the cube, or in a grid without voxels, are zero.
+    pub fn lookup(&self, p: &Vec3) -> f64 {
+        let inside = |c: f64| (0. ..=1.).contains(&c);
+        if self.values.is_empty() || !inside(p.x()) || !inside(p.y()) || !inside(p.z()) {
+            return 0.;
+        }
+
+        let (nx, ny, nz) = self.resolution;
+        let axis = |c: f64, n: usize| {
+            let x = c * n as f64 - 0.5;
+            let i = x.floor();
+            let f = x - i;
+            let i0 = (i as isize).clamp(0, n as isize - 1) as usize;
+            let i1 = (i as isize + 1).clamp(0, n as isize - 1) as usize;
+            (i0, i1, f)
+        };
+
+        let (x0, x1, fx) = axis(p.x(), nx);
+        let (y0, y1, fy) = axis(p.y(), ny);
+        let (z0, z1, fz) = axis(p.z(), nz);
+
+        let lerp = |a: f64, b: f64, t: f64| a * (1. - t) + b * t;
+        let plane = |z: usize| {
+            lerp(
+                lerp(self.voxel(x0, y0, z), self.voxel(x1, y0, z), fx),
+                lerp(self.voxel(x0, y1, z), self.voxel(x1, y1, z), fx),
+                fy,
+            )
+        };
+
+        lerp(plane(z0), plane(z1), fz)
+    }
+}
+
+impl From<String> for DensityGrid {
+    fn from(path: String) -> Self {
+        Self {
+            path,
+            resolution: (0, 0, 0),
+            values: Arc::new(Vec::new()),
+            max: 0.,
+        }
+    }
+}
+
+impl From<DensityGrid> for String {
+    fn from(grid: DensityGrid) -> Self {
+        grid.path
+    }
+}
@@ -0,0 +1,273 @@
+mod grid;
+
+use std::{f64::consts::PI, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    colour::Colour,
+    ray::Ray,
+    sampling,
+    vec::{Frame, Vec3},
+};
+
+pub use self::grid::DensityGrid;
+
+/// A participating medium such as fog, smoke or fire, which scatters,
+/// absorbs and emits light travelling through it. Rays passed to its methods
+/// must have unit-length directions so that distances are in world units.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Medium {
+    /// The same density everywhere.
+    Homogeneous {
+        /// Chance per unit distance of light interacting with the medium
+        /// (the extinction coefficient).
+        density: f64,
+
+        /// Fraction of interactions that scatter rather than absorb light.
+        #[serde(default = "Colour::white")]
+        albedo: Colour,
+
+        /// Henyey-Greenstein asymmetry: positive values scatter light
+        /// forwards, negative values back towards where it came from.
+        #[serde(default)]
+        anisotropy: f64,
+    },
+
+    /// Density varying through space, read from a voxel grid that is
+    /// stretched over the box from `min` to `max`.
+    Grid(GridMedium),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GridMedium {
+    pub grid: DensityGrid,
+    pub min: Vec3,
+    pub max: Vec3,
+
+    /// Scales the grid's values into extinction coefficients.
+    #[serde(default = "default_density")]
+    pub density: f64,
+    #[serde(default = "Colour::white")]
+    pub albedo: Colour,
+    #[serde(default)]
+    pub anisotropy: f64,
+
+    /// Radiance emitted by the absorbing part of the medium, scaled by
+    /// `emission_grid` if there is one, or by the density grid otherwise.
+    #[serde(default = "Colour::black")]
+    pub emission: Colour,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emission_grid: Option<DensityGrid>,
+}
+
+fn default_density() -> f64 {
+    1.0
+}
+
+impl Medium {
+    /// Samples how far along `ray` light travels before interacting with the
+    /// medium, returning `None` if it gets further than `max_distance`.
+    pub fn sample_distance(&self, ray: &Ray, max_distance: f64) -> Option<f64> {
+        match self {
+            Medium::Homogeneous { density, .. } => {
+                if *density <= 0. {
+                    return None;
+                }
+
+                let distance = -(1. - sampling::uniform()).ln() / density;
+                (distance < max_distance).then_some(distance)
+            }
+
+            Medium::Grid(grid) => grid.sample_distance(ray, max_distance),
+        }
+    }
+
+    /// Fraction of light that passes `distance` along `ray` without
+    /// interacting with the medium. For grids this is an unbiased estimate
+    /// rather than the exact value.
+    pub fn transmittance(&self, ray: &Ray, distance: f64) -> f64 {
+        match self {
+            Medium::Homogeneous { density, .. } => {
+                if *density <= 0. {
+                    1.
+                } else {
+                    (-density * distance).exp()
+                }
+            }
+
+            Medium::Grid(grid) => grid.transmittance(ray, distance),
+        }
+    }
+
+    pub fn albedo(&self) -> Colour {
+        match self {
+            Medium::Homogeneous { albedo, .. } => *albedo,
+            Medium::Grid(grid) => grid.albedo,
+        }
+    }
+
+    /// Radiance emitted at `point`.
+    pub fn emission(&self, point: &Vec3) -> Colour {
+        match self {
+            Medium::Homogeneous { .. } => Colour::black(),
+            Medium::Grid(grid) => grid.emission(point),
+        }
+    }
+
+    /// Picks a new direction for light travelling in `direction` that has
+    /// scattered off the medium.
+    pub fn sample_phase(&self, direction: &Vec3) -> Vec3 {
+        let g = match self {
+            Medium::Homogeneous { anisotropy, .. } => *anisotropy,
+            Medium::Grid(grid) => grid.anisotropy,
+        };
+        sample_henyey_greenstein(direction, g)
+    }
+
+    /// Reads the voxels of any grids the medium was given in a scene file in
+    /// `scene_dir`.
+    pub fn load_grids(&mut self, scene_dir: &Path) -> anyhow::Result<()> {
+        if let Medium::Grid(grid) = self {
+            grid.grid.load_voxels(scene_dir)?;
+            if let Some(emission_grid) = &mut grid.emission_grid {
+                emission_grid.load_voxels(scene_dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GridMedium {
+    /// Maps a world-space point into the grid's unit cube.
+    fn to_grid(&self, point: &Vec3) -> Vec3 {
+        let size = self.max - self.min;
+        let offset = *point - self.min;
+        Vec3::new((
+            offset.x() / size.x(),
+            offset.y() / size.y(),
+            offset.z() / size.z(),
+        ))
+    }
+
+    fn extinction(&self, point: &Vec3) -> f64 {
+        self.grid.lookup(&self.to_grid(point)) * self.density
+    }
+
+    fn emission(&self, point: &Vec3) -> Colour {
+        let p = self.to_grid(point);
+        let strength = match &self.emission_grid {
+            Some(grid) => grid.lookup(&p),
+            None => self.grid.lookup(&p),
+        };
+        self.emission * strength
+    }
+
+    /// The part of `ray` between `0..max_distance` that overlaps the grid's
+    /// box, since the medium is empty everywhere else.
+    fn overlap(&self, ray: &Ray, max_distance: f64) -> Option<(f64, f64)> {
+        let mut t0: f64 = 0.;
+        let mut t1 = max_distance;
+
+        for (origin, direction, min, max) in [
+            (
+                ray.origin.x(),
+                ray.direction.x(),
+                self.min.x(),
+                self.max.x(),
+            ),
+            (
+                ray.origin.y(),
+                ray.direction.y(),
+                self.min.y(),
+                self.max.y(),
+            ),
+            (
+                ray.origin.z(),
+                ray.direction.z(),
+                self.min.z(),
+                self.max.z(),
+            ),
+        ] {
+            let inverse = 1. / direction;
+            let (near, far) = {
+                let a = (min - origin) * inverse;
+                let b = (max - origin) * inverse;
+                if a < b {
+                    (a, b)
+                } else {
+                    (b, a)
+                }
+            };
+
+            // NaNs from rays parallel to a slab boundary leave the range alone.
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+        }
+
+        (t0 < t1).then_some((t0, t1))
+    }
+
+    /// Delta tracking: take exponential steps as if the whole grid were as
+    /// dense as its densest voxel, and accept each step as a real collision
+    /// with probability proportional to the actual density there.
+    fn sample_distance(&self, ray: &Ray, max_distance: f64) -> Option<f64> {
+        let majorant = self.grid.max() * self.density;
+        if majorant <= 0. {
+            return None;
+        }
+
+        let (mut t, t_end) = self.overlap(ray, max_distance)?;
+        loop {
+            t -= (1. - sampling::uniform()).ln() / majorant;
+            if t >= t_end {
+                return None;
+            }
+
+            if sampling::uniform() * majorant < self.extinction(&ray.at(t)) {
+                return Some(t);
+            }
+        }
+    }
+
+    /// Ratio tracking: take the same steps as delta tracking, but rather
+    /// than stopping at a collision, scale down by the chance of one.
+    fn transmittance(&self, ray: &Ray, distance: f64) -> f64 {
+        let majorant = self.grid.max() * self.density;
+        let Some((mut t, t_end)) = self.overlap(ray, distance).filter(|_| majorant > 0.) else {
+            return 1.;
+        };
+
+        let mut transmittance = 1.;
+        loop {
+            t -= (1. - sampling::uniform()).ln() / majorant;
+            if t >= t_end {
+                return transmittance;
+            }
+
+            transmittance *= 1. - self.extinction(&ray.at(t)) / majorant;
+        }
+    }
+}
+
+/// Picks a new direction for light travelling in `direction`, following the
+/// Henyey-Greenstein phase function with asymmetry `g`.
+pub fn sample_henyey_greenstein(direction: &Vec3, g: f64) -> Vec3 {
+    let g = g.clamp(-0.99, 0.99);
+    let xi = sampling::uniform_pair();
+
+    let cos_theta = if g.abs() < 1e-3 {
+        1. - 2. * xi.0
+    } else {
+        let sq = (1. - g * g) / (1. - g + 2. * g * xi.0);
+        (1. + g * g - sq * sq) / (2. * g)
+    };
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let phi = 2. * PI * xi.1;
+
+    Frame::from_normal(*direction).to_world(&Vec3::new((
+        sin_theta * phi.cos(),
+        sin_theta * phi.sin(),
+        cos_theta,
+    )))
+}
@@ -2,7 +2,7 @@ use serde::{Serialize, Deserialize};
 
 use crate::vec::Vec3;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
@@ -0,0 +1,163 @@
+use std::sync::OnceLock;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{hash, sobol::scrambled_sobol, Sampler};
+
+/// The width and height of the blue noise texture.
+const SIZE: usize = 64;
+
+/// Owen-scrambled Sobol points shared by every pixel, with each pixel
+/// shifting them by a value from a blue noise texture, after Georgiev and
+/// Fajardo's "Blue-noise Dithered Sampling". Neighbouring pixels are shifted
+/// by very different amounts, so the error that's left is spread out as
+/// fine grained noise, which looks less blotchy than white noise at the
+/// same sample count. Each dimension reads the texture at a different
+/// offset.
+pub struct BlueNoise {
+    seed: u64,
+}
+
+impl BlueNoise {
+    pub fn new() -> Self {
+        Self {
+            seed: rand::random(),
+        }
+    }
+}
+
+impl Default for BlueNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for BlueNoise {
+    fn sample(&self, pixel: (usize, usize), index: usize, dimension: usize) -> f64 {
+        static TEXTURE: OnceLock<Vec<f64>> = OnceLock::new();
+        let texture = TEXTURE.get_or_init(void_and_cluster);
+
+        let offset = hash(&[self.seed, dimension as u64]) as usize;
+        let x = (pixel.0 + offset) % SIZE;
+        let y = (pixel.1 + (offset >> 32)) % SIZE;
+
+        (scrambled_sobol(index, dimension, self.seed) + texture[y * SIZE + x]).fract()
+    }
+}
+
+/// A tiling blue noise texture made by Ulichney's void-and-cluster method,
+/// with every value in `[0, 1)` appearing once.
+fn void_and_cluster() -> Vec<f64> {
+    const SIGMA: f64 = 1.5;
+
+    let len = SIZE * SIZE;
+    let kernel: Vec<f64> = (0..len)
+        .map(|i| {
+            let (x, y) = (i % SIZE, i / SIZE);
+            let dx = x.min(SIZE - x) as f64;
+            let dy = y.min(SIZE - y) as f64;
+            (-(dx * dx + dy * dy) / (2. * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    // Start from a tenth of the pixels at random, then move the most
+    // crowded point to the emptiest space until that no longer changes
+    // anything.
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut pattern = Pattern::new(&kernel);
+    let initial = len / 10;
+    while pattern.count < initial {
+        let i = rng.gen_range(0..len);
+        if !pattern.points[i] {
+            pattern.toggle(i);
+        }
+    }
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    // Points are ranked by taking them away from the most crowded first,
+    // then by filling in the emptiest spaces.
+    let mut ranks = vec![0; len];
+    let mut removing = pattern.clone();
+    for rank in (0..initial).rev() {
+        let cluster = removing.tightest_cluster();
+        removing.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+    for rank in initial..len {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f64 + 0.5) / len as f64)
+        .collect()
+}
+
+/// A binary pattern on the texture, with how crowded each pixel is by the
+/// points around it.
+#[derive(Clone)]
+struct Pattern<'a> {
+    kernel: &'a [f64],
+    points: Vec<bool>,
+    energy: Vec<f64>,
+    count: usize,
+}
+
+impl<'a> Pattern<'a> {
+    fn new(kernel: &'a [f64]) -> Self {
+        Self {
+            kernel,
+            points: vec![false; kernel.len()],
+            energy: vec![0.; kernel.len()],
+            count: 0,
+        }
+    }
+
+    fn toggle(&mut self, i: usize) {
+        self.points[i] = !self.points[i];
+        let sign = if self.points[i] {
+            self.count += 1;
+            1.
+        } else {
+            self.count -= 1;
+            -1.
+        };
+
+        let (x, y) = (i % SIZE, i / SIZE);
+        for (j, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (j % SIZE + SIZE - x) % SIZE;
+            let dy = (j / SIZE + SIZE - y) % SIZE;
+            *energy += sign * self.kernel[dy * SIZE + dx];
+        }
+    }
+
+    /// The point with the most points around it.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, f64::gt)
+    }
+
+    /// The empty pixel with the fewest points around it.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, f64::lt)
+    }
+
+    fn extreme(&self, point: bool, better: fn(&f64, &f64) -> bool) -> usize {
+        let mut best: Option<usize> = None;
+        for (i, energy) in self.energy.iter().enumerate() {
+            if self.points[i] == point && best.is_none_or(|b| better(energy, &self.energy[b])) {
+                best = Some(i);
+            }
+        }
+        best.unwrap()
+    }
+}
@@ -0,0 +1,57 @@
+use super::{hash, to_unit, Sampler};
+
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// The Halton sequence, where each dimension is the sample's index with its
+/// digits reversed about the decimal point in the next prime base. Each
+/// pixel shifts every dimension by its own random amount, wrapping around,
+/// so that pixels don't all see the same points. Dimensions beyond the
+/// table of primes are chosen independently.
+pub struct Halton {
+    seed: u64,
+}
+
+impl Halton {
+    pub fn new() -> Self {
+        Self {
+            seed: rand::random(),
+        }
+    }
+}
+
+impl Default for Halton {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for Halton {
+    fn sample(&self, pixel: (usize, usize), index: usize, dimension: usize) -> f64 {
+        let key = [self.seed, pixel.0 as u64, pixel.1 as u64, dimension as u64];
+        let Some(&base) = PRIMES.get(dimension) else {
+            return to_unit(hash(&[hash(&key), index as u64]));
+        };
+
+        let shift = to_unit(hash(&key));
+        (radical_inverse(index as u64, base) + shift).fract()
+    }
+}
+
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let inverse_base = 1. / base as f64;
+    let mut digit_value = inverse_base;
+    let mut result = 0.;
+
+    while index > 0 {
+        result += (index % base) as f64 * digit_value;
+        index /= base;
+        digit_value *= inverse_base;
+    }
+
+    result
+}
@@ -0,0 +1,10 @@
+use super::Sampler;
+
+/// Every number chosen independently at random, as when there's no sampler.
+pub struct Independent;
+
+impl Sampler for Independent {
+    fn sample(&self, _pixel: (usize, usize), _index: usize, _dimension: usize) -> f64 {
+        rand::random()
+    }
+}
@@ -0,0 +1,160 @@
+mod blue_noise;
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+pub use self::{
+    blue_noise::BlueNoise, halton::Halton, independent::Independent, sobol::Sobol,
+    stratified::Stratified,
+};
+
+/// Where the numbers for each sample of a pixel come from.
+///
+/// A sample is a point with a dimension for every number drawn while
+/// tracing it: first its position within the pixel, then on the lens, then
+/// the choices made at each bounce, with each bounce given its own block of
+/// dimensions. Spreading a pixel's samples evenly across these dimensions,
+/// rather than choosing every number independently, leaves less noise for
+/// the same number of samples.
+pub trait Sampler: Send + Sync {
+    /// Number `dimension` of sample `index` of `pixel`, in `[0, 1)`.
+    fn sample(&self, pixel: (usize, usize), index: usize, dimension: usize) -> f64;
+}
+
+/// Somewhere other than the thread's random number generator to take the
+/// numbers for a path from.
+pub trait SampleSource {
+    /// The next number for the path, in `[0, 1)`.
+    fn uniform(&mut self) -> f64;
+
+    /// Moves on to the numbers for bounce `bounce` of the path. Sources
+    /// that hand out numbers in the order they're asked for ignore this.
+    fn start_bounce(&mut self, _bounce: usize) {}
+}
+
+/// Dimensions of each sample for choosing the camera ray: where in the
+/// pixel, where on the lens and which wavelengths.
+const CAMERA_DIMENSIONS: usize = 8;
+
+/// Dimensions of each sample for every bounce of its path.
+const BOUNCE_DIMENSIONS: usize = 16;
+
+thread_local! {
+    static SOURCE: RefCell<Option<Rc<RefCell<dyn SampleSource>>>> = const { RefCell::new(None) };
+}
+
+/// A uniform random number in `[0, 1)`.
+///
+/// Every random choice made while tracing a path draws from here, so that
+/// the numbers can be taken over by `with_source`. Otherwise they come from
+/// the thread's random number generator.
+pub fn uniform() -> f64 {
+    SOURCE.with(|source| match &*source.borrow() {
+        Some(source) => source.borrow_mut().uniform(),
+        None => rand::random(),
+    })
+}
+
+/// Two numbers from `uniform`, for sampling two-dimensional distributions.
+pub fn uniform_pair() -> (f64, f64) {
+    (uniform(), uniform())
+}
+
+/// A uniformly chosen index below `len`.
+pub fn index(len: usize) -> usize {
+    ((uniform() * len as f64) as usize).min(len - 1)
+}
+
+/// Moves `uniform` on to the numbers for bounce `bounce` of the path being
+/// traced. Every sample of a pixel then makes the same bounce's choices
+/// from the same dimensions, however many numbers the bounces before it
+/// used, which keeps them evenly spread.
+pub fn start_bounce(bounce: usize) {
+    SOURCE.with(|source| {
+        if let Some(source) = &*source.borrow() {
+            source.borrow_mut().start_bounce(bounce);
+        }
+    });
+}
+
+/// Runs `f` with every number from `uniform` on this thread taken from
+/// `source`. Metropolis light transport uses this to replay a path from the
+/// numbers it was made from, and to perturb them.
+pub fn with_source<R>(source: Rc<RefCell<dyn SampleSource>>, f: impl FnOnce() -> R) -> R {
+    let previous = SOURCE.with(|current| current.replace(Some(source)));
+    let result = f();
+    SOURCE.with(|current| current.replace(previous));
+
+    result
+}
+
+/// The numbers of one sample of a pixel, handed out a dimension at a time
+/// from the block for the camera or the current bounce. Numbers beyond the
+/// end of a block are chosen independently.
+struct PixelSample {
+    sampler: Arc<dyn Sampler>,
+    pixel: (usize, usize),
+    index: usize,
+    dimension: usize,
+    /// Where the current block of dimensions ends.
+    end: usize,
+}
+
+impl SampleSource for PixelSample {
+    fn uniform(&mut self) -> f64 {
+        if self.dimension == self.end {
+            return rand::random();
+        }
+
+        let value = self.sampler.sample(self.pixel, self.index, self.dimension);
+        self.dimension += 1;
+        value
+    }
+
+    fn start_bounce(&mut self, bounce: usize) {
+        self.dimension = CAMERA_DIMENSIONS + bounce * BOUNCE_DIMENSIONS;
+        self.end = self.dimension + BOUNCE_DIMENSIONS;
+    }
+}
+
+/// Runs `f` with every number from `uniform` on this thread taken from the
+/// dimensions of sample `index` of `pixel`, starting with the camera's.
+pub fn with_sample<R>(
+    sampler: &Arc<dyn Sampler>,
+    pixel: (usize, usize),
+    index: usize,
+    f: impl FnOnce() -> R,
+) -> R {
+    let sample = PixelSample {
+        sampler: sampler.clone(),
+        pixel,
+        index,
+        dimension: 0,
+        end: CAMERA_DIMENSIONS,
+    };
+    with_source(Rc::new(RefCell::new(sample)), f)
+}
+
+/// Mixes `values` into one well scrambled number, for samplers to make
+/// random choices that are the same every time they're asked for.
+fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e37_79b9_7f4a_7c15, |hash, &value| mix(hash ^ mix(value)))
+}
+
+/// The finaliser of SplitMix64.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// A number in `[0, 1)` from the top bits of a hash.
+fn to_unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
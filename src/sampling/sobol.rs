@@ -0,0 +1,108 @@
+use super::{hash, Sampler};
+
+/// Direction numbers for the first four Sobol dimensions, from Joe and Kuo.
+const DIRECTIONS: [[u32; 32]; 4] = [
+    van_der_corput(),
+    directions(1, 0, [1, 0, 0]),
+    directions(2, 1, [1, 3, 0]),
+    directions(3, 1, [1, 3, 1]),
+];
+
+/// Sobol points with Owen scrambling, after Burley's "Practical Hash-based
+/// Owen Scrambling". Dimensions are taken four at a time from the first
+/// four Sobol dimensions, with the samples put in a different random order
+/// for each group of four so that groups aren't correlated. Owen scrambling
+/// gives each pixel its own points while keeping how evenly they're spread.
+pub struct Sobol {
+    seed: u64,
+}
+
+impl Sobol {
+    pub fn new() -> Self {
+        Self {
+            seed: rand::random(),
+        }
+    }
+}
+
+impl Default for Sobol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for Sobol {
+    fn sample(&self, pixel: (usize, usize), index: usize, dimension: usize) -> f64 {
+        let seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        scrambled_sobol(index, dimension, seed)
+    }
+}
+
+/// Number `dimension` of point `index` of the Sobol sequence scrambled by
+/// `seed`.
+pub(super) fn scrambled_sobol(index: usize, dimension: usize, seed: u64) -> f64 {
+    let group = hash(&[seed, (dimension / 4) as u64]);
+    let index = nested_uniform_scramble(index as u32, group as u32);
+
+    let component = dimension % 4;
+    let value = DIRECTIONS[component]
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| index & (1 << bit) != 0)
+        .fold(0, |value, (_, direction)| value ^ direction);
+    let value = nested_uniform_scramble(value, hash(&[group, component as u64]) as u32);
+
+    value as f64 / (1u64 << 32) as f64
+}
+
+/// Randomly flips each bit of `x` based on the bits above it, which shuffles
+/// points between the halves, quarters and so on of `[0, 1)` without
+/// disturbing how evenly they're spread.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// A hash where each bit only depends on the bits below it.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+/// The first Sobol dimension, which reverses the index's bits.
+const fn van_der_corput() -> [u32; 32] {
+    let mut directions = [0; 32];
+    let mut bit = 0;
+    while bit < 32 {
+        directions[bit] = 1 << (31 - bit);
+        bit += 1;
+    }
+    directions
+}
+
+/// The direction numbers for a dimension with a primitive polynomial of
+/// degree `degree` and coefficients `coefficients`, starting from `initial`.
+const fn directions(degree: usize, coefficients: u32, initial: [u32; 3]) -> [u32; 32] {
+    let mut directions = [0; 32];
+    let mut bit = 0;
+    while bit < 32 {
+        if bit < degree {
+            directions[bit] = initial[bit] << (31 - bit);
+        } else {
+            let mut direction = directions[bit - degree] ^ (directions[bit - degree] >> degree);
+            let mut k = 1;
+            while k < degree {
+                if (coefficients >> (degree - 1 - k)) & 1 == 1 {
+                    direction ^= directions[bit - k];
+                }
+                k += 1;
+            }
+            directions[bit] = direction;
+        }
+        bit += 1;
+    }
+    directions
+}
@@ -0,0 +1,83 @@
+use super::{hash, to_unit, Sampler};
+
+/// Jittered stratification. Each pair of dimensions is divided into a grid
+/// of cells, one for each sample of a pixel, and every sample lands at a
+/// random point in a different cell. The cells are dealt out in a random
+/// order for each pixel and pair of dimensions, so that the pairs aren't
+/// correlated with each other.
+pub struct Stratified {
+    columns: usize,
+    rows: usize,
+    seed: u64,
+}
+
+impl Stratified {
+    pub fn new(samples_per_pixel: usize) -> Self {
+        let columns = (samples_per_pixel as f64).sqrt().ceil().max(1.) as usize;
+        let rows = samples_per_pixel.div_ceil(columns).max(1);
+
+        Self {
+            columns,
+            rows,
+            seed: rand::random(),
+        }
+    }
+}
+
+impl Sampler for Stratified {
+    fn sample(&self, pixel: (usize, usize), index: usize, dimension: usize) -> f64 {
+        let cells = self.columns * self.rows;
+        let pair = hash(&[
+            self.seed,
+            pixel.0 as u64,
+            pixel.1 as u64,
+            (dimension / 2) as u64,
+        ]);
+        let cell = permute((index % cells) as u32, cells as u32, pair as u32) as usize;
+        let jitter = to_unit(hash(&[pair, index as u64, dimension as u64]));
+
+        if dimension.is_multiple_of(2) {
+            ((cell % self.columns) as f64 + jitter) / self.columns as f64
+        } else {
+            ((cell / self.columns) as f64 + jitter) / self.rows as f64
+        }
+    }
+}
+
+/// Where `index` goes in a random shuffle of `0..len` chosen by `seed`,
+/// from Kensler's "Correlated Multi-Jittered Sampling".
+fn permute(mut index: u32, len: u32, seed: u32) -> u32 {
+    let mut mask = len - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // Shuffles within the next power of two up, trying again until the
+    // index lands inside `len`.
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+
+        if index < len {
+            return (index + seed) % len;
+        }
+    }
+}
@@ -1,47 +1,158 @@
+mod subsurface;
+
+use std::{path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    camera::Camera,
+    camera::{Camera, CameraConfig},
     colour::Colour,
-    geometry::{Geometry, Material},
+    environment::Environment,
+    film::Film,
+    geometry::{Geometry, Material, Shape},
     hit::Hit,
+    integrator::Integrator,
+    medium::Medium,
     ray::Ray,
+    sampling::{self, Sampler},
+    spectrum::SampledWavelengths,
     vec::Vec3,
 };
 
+/// The contents of a scene file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneDescription {
+    /// How the scene is viewed, unless given on the command line instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraConfig>,
+
+    #[serde(default)]
+    pub environment: Environment,
+
+    /// A medium filling all space outside of shapes. It only fills the
+    /// space among the shapes: light from the environment isn't dimmed on
+    /// its way in, and rays that miss everything see the environment
+    /// unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub medium: Option<Medium>,
+
+    pub geometries: Vec<Geometry>,
+}
+
+impl SceneDescription {
+    /// Reads the files that the scene refers to, with paths relative to
+    /// `scene_dir`, the directory of the scene file it was read from.
+    pub fn load_grids(&mut self, scene_dir: &Path) -> anyhow::Result<()> {
+        let media = self.geometries.iter_mut().map(|geo| &mut geo.medium);
+        for medium in std::iter::once(&mut self.medium).chain(media).flatten() {
+            medium.load_grids(scene_dir)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct Scene {
     pub camera: Camera,
+    pub environment: Environment,
+    pub medium: Option<Medium>,
     pub geometries: Vec<Geometry>,
+
+    /// Whether surfaces explicitly sample the environment for direct
+    /// lighting, rather than waiting for bounced rays to escape.
+    pub light_sampling: bool,
+
+    /// Whether each sample carries particular wavelengths, so that
+    /// dispersive materials split light into colours and every other colour
+    /// is multiplied wavelength by wavelength along a path.
+    pub spectral: bool,
 }
 
 impl Scene {
+    /// Takes `samples` of a pixel with numbers from `sampler`, splatting
+    /// each into `film`.
     pub fn render_pixel(
         &self,
         coord: (usize, usize),
         samples: usize,
-        max_bounces: usize,
-    ) -> crate::colour::Colour {
-        let mut colour = Colour::black();
+        sampler: &Arc<dyn Sampler>,
+        integrator: &dyn Integrator,
+        film: &mut Film,
+    ) {
+        let region = self.camera.region(coord);
+        for index in 0..samples {
+            sampling::with_sample(sampler, coord, index, || {
+                let offset = sampling::uniform_pair();
 
-        for _ in 0..samples {
-            let ray = self.camera.screen_to_world_sampled(coord);
-            colour += self.ray_colour(&ray, max_bounces as isize);
+                // Every pixel's samples trace light to the camera, so what
+                // lands in each pixel is shared between that many samples.
+                let mut splat = |position, light: Colour| {
+                    film.add_splat(position, light / samples as f64);
+                };
+                let colour = self.sample_at(coord, offset, integrator, Some(&mut splat));
+
+                let position = (coord.0 as f64 + offset.0, coord.1 as f64 + offset.1);
+                film.add_sample(position, colour, region.clone());
+            });
         }
+    }
+
+    /// The colour seen along one randomly chosen ray through a pixel. All
+    /// of it is found from the camera, so nothing is splatted elsewhere.
+    pub fn sample_pixel(&self, coord: (usize, usize), integrator: &dyn Integrator) -> Colour {
+        self.sample_at(coord, sampling::uniform_pair(), integrator, None)
+    }
+
+    /// The colour seen along a ray through `offset` within a pixel, with
+    /// light traced to the camera handed to `splat` if it's given.
+    fn sample_at(
+        &self,
+        coord: (usize, usize),
+        offset: (f64, f64),
+        integrator: &dyn Integrator,
+        splat: Option<&mut dyn FnMut((f64, f64), Colour)>,
+    ) -> Colour {
+        let Some(ray) = self.camera.screen_to_world_at(coord, offset) else {
+            return Colour::black();
+        };
+        let wavelengths = self
+            .spectral
+            .then(|| SampledWavelengths::sample(sampling::uniform()));
+        let to_rgb = |radiance: Colour| {
+            let radiance = match &wavelengths {
+                Some(wavelengths) => wavelengths.to_rgb(radiance),
+                None => radiance,
+            };
+            radiance * self.camera.exposure()
+        };
 
-        colour / (samples as f64)
+        let radiance = match splat {
+            Some(splat) => {
+                let mut splat = |position, light| splat(position, to_rgb(light));
+                integrator.radiance_splatting(self, &ray, wavelengths.as_ref(), &mut splat)
+            }
+            None => integrator.radiance(self, &ray, wavelengths.as_ref()),
+        };
+        to_rgb(radiance)
     }
 
-    pub fn hit_test(&self, ray: &Ray) -> Option<Hit> {
+    pub fn hit_test(&self, ray: &Ray) -> Option<Hit<'_>> {
         let mut best_t: f64 = f64::INFINITY;
         let mut best_hit = None;
 
         for geo in &self.geometries {
-            if let Some((t, normal, front_face)) = geo.shape.hit_test(ray, (0.001)..best_t) {
-                if t <= best_t {
-                    best_t = t;
+            if let Some(intersection) = geo.shape.hit_test(ray, (0.001)..best_t) {
+                if intersection.t <= best_t {
+                    best_t = intersection.t;
                     best_hit = Some(Hit {
-                        material: geo.material,
-                        point: ray.at(t),
-                        normal,
-                        front_face,
+                        shape: &geo.shape,
+                        material: &geo.material,
+                        medium: geo.medium.as_ref(),
+                        point: ray.at(intersection.t),
+                        normal: intersection.normal,
+                        front_face: intersection.front_face,
+                        uv: intersection.uv,
+                        dpdu: intersection.dpdu,
+                        dpdv: intersection.dpdv,
                     });
                 }
             }
@@ -50,100 +161,136 @@ impl Scene {
         best_hit
     }
 
-    fn ray_colour(&self, ray: &Ray, max_bounces: isize) -> Colour {
-        if max_bounces < 0 {
-            return Colour::black();
-        }
+    /// The medium filling space at `point`, for starting a path there.
+    pub fn medium_at(&self, point: &Vec3) -> Option<&Medium> {
+        self.medium_around(point, None)
+    }
 
-        let hit = self.hit_test(&ray);
+    /// The medium a path is in after leaving `hit` in `direction`, having
+    /// arrived through `medium`. It only changes when the path passes
+    /// through the surface: into the shape's own medium, or out into
+    /// whatever surrounds the shape.
+    pub fn medium_after<'a>(
+        &'a self,
+        hit: &Hit<'a>,
+        medium: Option<&'a Medium>,
+        direction: &Vec3,
+    ) -> Option<&'a Medium> {
+        // The normal faces the side the path arrived from.
+        let crossed = direction.dot(&hit.normal) < 0.;
+        let thin_walled = matches!(
+            hit.material,
+            Material::Dialectric {
+                thin_walled: true,
+                ..
+            }
+        );
 
-        if let Some(hit) = hit {
-            match hit.material {
-                Material::ScreenSpaceGradient => {
-                    let a = (ray.direction.unit().y() + 1.0) * 0.5;
-                    Colour::new(1.0, 1.0, 1.0) * (1.0 - a) + Colour::new(0.5, 0.7, 1.0) * a
-                }
+        if !crossed || thin_walled {
+            medium
+        } else if hit.front_face {
+            hit.medium
+        } else {
+            self.medium_around(&hit.point, Some(hit.shape))
+        }
+    }
 
-                Material::NormalSpaceGradient => {
-                    Colour::new(
-                        hit.normal.x() + 1.,
-                        hit.normal.y() + 1.,
-                        hit.normal.z() + 1.,
-                    ) * 0.5
-                }
+    /// The medium inside the innermost shape around `point`, other than
+    /// `excluding`, or the scene's medium outside of every shape. Shapes are
+    /// convex, so the innermost is the smallest. Interfaces without a medium
+    /// don't bound anything.
+    ///
+    /// Light emitted from a point on a shape starts in the medium around it
+    /// excluding that shape.
+    pub fn medium_around(&self, point: &Vec3, excluding: Option<&Shape>) -> Option<&Medium> {
+        self.geometries
+            .iter()
+            .filter(|geo| !excluding.is_some_and(|shape| std::ptr::eq(shape, &geo.shape)))
+            .filter(|geo| geo.medium.is_some() || !matches!(geo.material, Material::Interface))
+            .filter(|geo| geo.shape.contains(point))
+            .min_by(|a, b| a.shape.area().total_cmp(&b.shape.area()))
+            .map_or(self.medium.as_ref(), |geo| geo.medium.as_ref())
+    }
 
-                Material::SolidColour { colour } => colour,
-                Material::Diffuse { colour, albedo } => {
-                    let reflected_direction = Vec3::random_on_hemisphere(&hit.normal);
-                    let reflected_ray = Ray {
-                        origin: hit.point,
-                        direction: reflected_direction,
-                    };
-                    let reflected_colour =
-                        self.ray_colour(&reflected_ray, max_bounces - 1) * albedo;
-                    colour * reflected_colour
-                }
-                Material::Lambertian { colour, albedo } => {
-                    let reflected_direction = Vec3::random_on_hemisphere(&hit.normal);
-                    let reflected_ray = Ray {
-                        origin: hit.point,
-                        direction: reflected_direction + Vec3::random_unit_vector(),
-                    };
-                    let reflected_colour =
-                        self.ray_colour(&reflected_ray, max_bounces - 1) * albedo;
-                    colour * reflected_colour
-                }
-                Material::Metal { tint, scatter } => {
-                    let reflected_direction =
-                        ray.direction.reflect(&hit.normal) + Vec3::random_unit_vector() * scatter;
-
-                    // absorb rays that get scattered into the material
-                    if reflected_direction.dot(&hit.normal) < 0. {
-                        return Colour::black();
-                    }
-
-                    let reflected_ray = Ray {
-                        origin: hit.point,
-                        direction: reflected_direction,
-                    };
-                    let reflected_colour = self.ray_colour(&reflected_ray, max_bounces - 1);
-                    tint * reflected_colour
-                }
+    /// Fraction of light that makes it between two points, starting in
+    /// `medium` at `from`: none if anything but interfaces lies between them,
+    /// and otherwise whatever the media on the way let through.
+    pub fn transmittance<'a>(
+        &'a self,
+        from: &Vec3,
+        to: &Vec3,
+        mut medium: Option<&'a Medium>,
+    ) -> Colour {
+        let offset = *to - *from;
+        let distance = offset.length();
+        let mut ray = Ray::new(*from, offset / distance);
+        let mut travelled = 0.;
+        let mut transmittance = Colour::white();
 
-                Material::Dialectric { ior } => {
-                    let refraction_ratio = if hit.front_face { 1.0 / ior } else { ior };
+        loop {
+            let hit = self
+                .hit_test(&ray)
+                .filter(|hit| travelled + (hit.point - ray.origin).length() < distance - 0.001);
+            let reached = hit.as_ref().map_or(distance, |hit| {
+                travelled + (hit.point - ray.origin).length()
+            });
 
-                    let unit_direction = ray.direction.unit();
+            if let Some(medium) = medium {
+                transmittance = transmittance * medium.transmittance(&ray, reached - travelled);
+            }
 
-                    let cos_theta = f64::min(-unit_direction.dot(&hit.normal), 1.0);
-                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let Some(hit) = hit else {
+                return transmittance;
+            };
+            if !matches!(hit.material, Material::Interface) {
+                return Colour::black();
+            }
 
-                    let cannot_refract = refraction_ratio * sin_theta > 1.0;
+            medium = self.medium_after(&hit, medium, &ray.direction);
+            travelled = reached;
+            ray = Ray::new(hit.point, ray.direction);
+        }
+    }
 
-                    let outgoing_direction = if cannot_refract {
-                        ray.direction.reflect(&hit.normal)
-                    } else {
-                        refract(unit_direction, hit.normal, refraction_ratio)
-                    };
+    /// Fraction of light that makes it along `ray`, starting in `medium`, to
+    /// the environment, passing through interfaces and media but stopping
+    /// at any other surface. Once past the last surface the ray has left the
+    /// scene, so the scene's medium doesn't dim the environment itself.
+    pub fn transmittance_to_environment<'a>(
+        &'a self,
+        ray: &Ray,
+        mut medium: Option<&'a Medium>,
+    ) -> Colour {
+        let mut transmittance = Colour::white();
+        let mut ray = Ray::new(ray.origin, ray.direction.unit());
 
-                    let outgoing_ray = Ray {
-                        origin: hit.point,
-                        direction: outgoing_direction,
-                    };
+        while let Some(hit) = self.hit_test(&ray) {
+            if !matches!(hit.material, Material::Interface) {
+                return Colour::black();
+            }
 
-                    self.ray_colour(&outgoing_ray, max_bounces - 1)
-                }
+            if let Some(medium) = medium {
+                let distance = (hit.point - ray.origin).length();
+                transmittance = transmittance * medium.transmittance(&ray, distance);
             }
-        } else {
-            Colour::new(1., 0., 1.)
+            medium = self.medium_after(&hit, medium, &ray.direction);
+            ray = Ray::new(hit.point, ray.direction);
         }
+
+        transmittance
     }
 }
 
-fn refract(uv: Vec3, n: Vec3, refraction_ratio: f64) -> Vec3 {
-    let cos_theta = f64::min(-uv.dot(&n), 1.0);
-    let r_out_perp = (uv + n * cos_theta) * refraction_ratio;
-    let r_out_parallel = n * -((1.0 - r_out_perp.length_squared()).abs()).sqrt();
-
-    r_out_perp + r_out_parallel
+/// Beer-Lambert attenuation for light that travelled `distance` inside an
+/// absorbing dielectric to reach `hit`. Thin-walled dielectrics have no
+/// inside to absorb light in.
+pub fn interior_transmittance(hit: &Hit, distance: f64) -> Colour {
+    match *hit.material {
+        Material::Dialectric {
+            absorption,
+            thin_walled: false,
+            ..
+        } if !hit.front_face => (absorption * -distance).exp(),
+        _ => Colour::white(),
+    }
 }
@@ -0,0 +1,106 @@
+use crate::{
+    colour::Colour,
+    geometry::{fresnel_schlick, refract, Interior, Shape},
+    medium::sample_henyey_greenstein,
+    ray::Ray,
+    sampling,
+};
+
+use super::Scene;
+
+/// Give up on walks that wander for this long, as they would contribute
+/// almost nothing.
+const MAX_WALK_STEPS: usize = 1024;
+
+/// Closest a walk's surface is counted as being ahead of it, small enough
+/// not to miss the surface after scattering right beside it.
+const WALK_TOLERANCE: f64 = 1e-7;
+
+impl Scene {
+    /// Follows light that has refracted into `shape`, made of a subsurface
+    /// material, as it scatters around inside until it leaves through the
+    /// surface of `shape` again. Other shapes inside it are passed straight
+    /// through. Returns the ray leaving the surface and the fraction of each
+    /// colour that survived, or `None` if the light was lost.
+    ///
+    /// Each colour has its own mean free path, so every step picks a colour
+    /// at random to sample the distance with, and weights the result by the
+    /// combined chance of any colour having picked it.
+    pub fn random_walk(
+        &self,
+        entry: Ray,
+        shape: &Shape,
+        interior: &Interior,
+    ) -> Option<(Ray, Colour)> {
+        let mut ray = Ray::new(entry.origin, entry.direction.unit());
+        let mut throughput = [1.; 3];
+
+        for _ in 0..MAX_WALK_STEPS {
+            // Only the walk's own surface is a way out. Scattering can leave
+            // the walk closer to it than the scene's hit tolerance, so it's
+            // intersected with a tighter one.
+            let hit = shape.hit_test(&ray, WALK_TOLERANCE..f64::INFINITY)?;
+            let boundary = hit.t;
+
+            // Choosing colours in proportion to how much of them is left keeps
+            // the weights from drifting apart over long walks.
+            let total: f64 = throughput.iter().sum();
+            if total <= 0. {
+                return None;
+            }
+            let probabilities = throughput.map(|t| t / total);
+
+            let mut choice = sampling::uniform();
+            let channel = (0..3)
+                .find(|&c| {
+                    choice -= probabilities[c];
+                    choice < 0.
+                })
+                .unwrap_or(2);
+
+            let sigma = interior.extinction[channel];
+            let distance = -(1. - sampling::uniform()).ln() / sigma;
+            let transmittance = |c: usize, d: f64| (-interior.extinction[c] * d).exp();
+
+            if distance < boundary {
+                let pdf: f64 = (0..3)
+                    .map(|c| probabilities[c] * interior.extinction[c] * transmittance(c, distance))
+                    .sum();
+
+                for (c, t) in throughput.iter_mut().enumerate() {
+                    let scattering = interior.albedo[c] * interior.extinction[c];
+                    *t *= scattering * transmittance(c, distance) / pdf;
+                }
+
+                let origin = ray.at(distance);
+                let direction = sample_henyey_greenstein(&ray.direction, interior.anisotropy);
+                ray = Ray::new(origin, direction);
+                continue;
+            }
+
+            let escape: f64 = (0..3)
+                .map(|c| probabilities[c] * transmittance(c, boundary))
+                .sum();
+            for (c, t) in throughput.iter_mut().enumerate() {
+                *t *= transmittance(c, boundary) / escape;
+            }
+
+            // Leave through the surface, or reflect back inside it.
+            let wo = -ray.direction;
+            let cos_theta = wo.dot(&hit.normal).min(1.);
+            let refracted = refract(&wo, &hit.normal, interior.ior)
+                .filter(|_| sampling::uniform() >= fresnel_schlick(cos_theta, interior.ior));
+
+            let point = ray.at(hit.t);
+            let Some(direction) = refracted else {
+                ray = Ray::new(point, ray.direction.reflect(&hit.normal));
+                continue;
+            };
+            let exit = Ray::new(point, direction);
+            let [r, g, b] = throughput;
+            return Some((exit, Colour::new(r, g, b)));
+        }
+
+        None
+    }
+}
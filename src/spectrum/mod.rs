@@ -0,0 +1,284 @@
+use std::{cell::Cell, sync::OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::colour::Colour;
+
+/// The range of visible wavelengths sampled, in nanometres.
+pub const LAMBDA_MIN: f64 = 380.;
+pub const LAMBDA_MAX: f64 = 720.;
+
+/// How many wavelengths each camera sample carries: one for each channel of
+/// a `Colour`, so that paths can carry their light at each wavelength in
+/// place of RGB.
+const WAVELENGTH_COUNT: usize = 3;
+
+/// The wavelengths carried by one camera sample, chosen by hero-wavelength
+/// sampling: one wavelength picked at random, and the rest spaced evenly
+/// through the visible range from it.
+///
+/// A path carrying wavelengths holds its light at each of them in the
+/// channels of a `Colour`. Every colour the path meets, such as an albedo, a
+/// Fresnel reflectance or an emitter's radiance, is turned into a spectrum
+/// and evaluated at the wavelengths by `evaluate`, so light is multiplied
+/// wavelength by wavelength along the path. `to_rgb` then turns the result
+/// back into RGB.
+pub struct SampledWavelengths {
+    lambdas: [f64; WAVELENGTH_COUNT],
+    secondaries_terminated: Cell<bool>,
+}
+
+impl SampledWavelengths {
+    pub fn sample(xi: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + xi * range;
+
+        let mut lambdas = [0.; WAVELENGTH_COUNT];
+        for (i, lambda) in lambdas.iter_mut().enumerate() {
+            let offset = i as f64 * range / WAVELENGTH_COUNT as f64;
+            *lambda = LAMBDA_MIN + (hero - LAMBDA_MIN + offset) % range;
+        }
+
+        Self {
+            lambdas,
+            secondaries_terminated: Cell::new(false),
+        }
+    }
+
+    /// The wavelength that decides the path when wavelength-dependent
+    /// materials are hit.
+    pub fn hero(&self) -> f64 {
+        self.lambdas[0]
+    }
+
+    /// Drops all but the hero wavelength, for when the path has taken a
+    /// direction that only makes sense at that wavelength, such as through a
+    /// dispersive prism.
+    pub fn terminate_secondaries(&self) {
+        self.secondaries_terminated.set(true);
+    }
+
+    /// How many of the wavelengths the path still carries light at.
+    fn active(&self) -> usize {
+        if self.secondaries_terminated.get() {
+            1
+        } else {
+            WAVELENGTH_COUNT
+        }
+    }
+
+    /// `colour` turned into a spectrum and evaluated at each wavelength,
+    /// one per channel. Spectra are relative to that of white, which is one
+    /// everywhere, so reflectances stay between zero and one and a grey is
+    /// the same at every wavelength.
+    pub fn evaluate(&self, colour: Colour) -> Colour {
+        let basis = SpectralBasis::get();
+        let coefficients = basis.coefficients(colour);
+        let [a, b, c] = self
+            .lambdas
+            .map(|lambda| basis.evaluate(&coefficients, lambda));
+
+        Colour::new(a, b, c)
+    }
+
+    /// Converts the light a path carried at each wavelength, relative to
+    /// white as `evaluate` gives it, into the RGB colour it contributes, by
+    /// integrating against the CIE colour matching functions. Evaluating a
+    /// colour at random wavelengths and converting it back gives the
+    /// original colour on average.
+    pub fn to_rgb(&self, radiance: Colour) -> Colour {
+        let basis = SpectralBasis::get();
+        let values = [radiance.r(), radiance.g(), radiance.b()];
+
+        let active = self.active();
+        let pdf = 1. / (LAMBDA_MAX - LAMBDA_MIN);
+
+        let mut xyz = [0.; 3];
+        for (&lambda, value) in self.lambdas.iter().zip(values).take(active) {
+            let value = value * basis.evaluate(&basis.white, lambda) / pdf;
+            let cmf = cie_xyz(lambda);
+            for (sum, c) in xyz.iter_mut().zip(cmf) {
+                *sum += value * c / active as f64;
+            }
+        }
+
+        xyz_to_linear_srgb(xyz)
+    }
+}
+
+/// A wavelength-dependent index of refraction, for materials that split
+/// white light into colours.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Dispersion {
+    /// `n = a + b / λ²`, with λ in micrometres.
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+
+    /// `n² = 1 + Σ bᵢλ² / (λ² - cᵢ)`, with λ in micrometres.
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+
+    // common glasses and gems
+    Bk7,
+    Diamond,
+}
+
+impl Dispersion {
+    /// Index of refraction at `lambda` nanometres.
+    pub fn ior(&self, lambda: f64) -> f64 {
+        let um = lambda / 1000.;
+        let um2 = um * um;
+
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / um2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * um2 / (um2 - c[i])).sum();
+                (1. + sum).sqrt()
+            }
+            Dispersion::Bk7 => Dispersion::Sellmeier {
+                b: [1.03961212, 0.231792344, 1.01046945],
+                c: [0.00600069867, 0.0200179144, 103.560653],
+            }
+            .ior(lambda),
+            Dispersion::Diamond => Dispersion::Sellmeier {
+                b: [0.3306, 4.3356, 0.],
+                c: [0.030625, 0.011236, 0.],
+            }
+            .ior(lambda),
+        }
+    }
+}
+
+/// Turns RGB colours into spectra, as a mix of three box functions covering
+/// the blue, green and red parts of the visible range. The mix is chosen so
+/// that converting the spectrum back to RGB gives the original colour.
+struct SpectralBasis {
+    /// Inverse of the matrix whose columns are the RGB colours of each box.
+    rgb_to_coefficients: [[f64; 3]; 3],
+    /// The mix making up white, which other spectra are relative to.
+    white: [f64; 3],
+}
+
+const BAND_EDGES: [f64; 4] = [LAMBDA_MIN, 490., 590., LAMBDA_MAX];
+
+impl SpectralBasis {
+    fn get() -> &'static SpectralBasis {
+        static BASIS: OnceLock<SpectralBasis> = OnceLock::new();
+        BASIS.get_or_init(SpectralBasis::new)
+    }
+
+    fn new() -> Self {
+        // The RGB colour of each band, integrated in 1nm steps. The band
+        // order is blue, green, red, so reverse it to get RGB columns.
+        let mut columns = [[0.; 3]; 3];
+        for (band, column) in columns.iter_mut().rev().enumerate() {
+            let mut xyz = [0.; 3];
+            let mut lambda = BAND_EDGES[band] + 0.5;
+            while lambda < BAND_EDGES[band + 1] {
+                for (sum, c) in xyz.iter_mut().zip(cie_xyz(lambda)) {
+                    *sum += c;
+                }
+                lambda += 1.;
+            }
+
+            let rgb = xyz_to_linear_srgb(xyz);
+            *column = [rgb.r(), rgb.g(), rgb.b()];
+        }
+
+        let matrix = [
+            [columns[0][0], columns[1][0], columns[2][0]],
+            [columns[0][1], columns[1][1], columns[2][1]],
+            [columns[0][2], columns[1][2], columns[2][2]],
+        ];
+
+        // White's weights are the sums of the rows. Dividing each row by
+        // its sum makes white come out as one in every band.
+        let mut rgb_to_coefficients = invert(&matrix);
+        let white = rgb_to_coefficients.map(|row| row.iter().sum::<f64>());
+        for (row, scale) in rgb_to_coefficients.iter_mut().zip(white) {
+            *row = row.map(|m| m / scale);
+        }
+
+        Self {
+            rgb_to_coefficients,
+            white,
+        }
+    }
+
+    /// Weights of the red, green and blue boxes making up `colour`, relative
+    /// to white's.
+    fn coefficients(&self, colour: Colour) -> [f64; 3] {
+        let rgb = [colour.r(), colour.g(), colour.b()];
+        let m = &self.rgb_to_coefficients;
+        [0, 1, 2].map(|i| (0..3).map(|j| m[i][j] * rgb[j]).sum())
+    }
+
+    fn evaluate(&self, coefficients: &[f64; 3], lambda: f64) -> f64 {
+        if lambda < BAND_EDGES[1] {
+            coefficients[2]
+        } else if lambda < BAND_EDGES[2] {
+            coefficients[1]
+        } else {
+            coefficients[0]
+        }
+    }
+}
+
+/// The CIE 1931 standard observer colour matching functions, using the
+/// multi-lobe Gaussian fit from Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if lambda < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+fn xyz_to_linear_srgb(xyz: [f64; 3]) -> Colour {
+    let [x, y, z] = xyz;
+    Colour::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+fn invert(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+
+    [
+        [
+            cofactor(1, 2, 1, 2) / det,
+            -cofactor(0, 2, 1, 2) / det,
+            cofactor(0, 1, 1, 2) / det,
+        ],
+        [
+            -cofactor(1, 2, 0, 2) / det,
+            cofactor(0, 2, 0, 2) / det,
+            -cofactor(0, 1, 0, 2) / det,
+        ],
+        [
+            cofactor(1, 2, 0, 1) / det,
+            -cofactor(0, 2, 0, 1) / det,
+            cofactor(0, 1, 0, 1) / det,
+        ],
+    ]
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::colour::Colour;
+
+/// An image loaded from disk and sampled with (u, v) texture coordinates in
+/// the range [0, 1]. Scene files refer to textures by path; the pixels are
+/// loaded once when the scene is deserialised and shared between clones.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ImageTexture {
+    path: String,
+    width: usize,
+    height: usize,
+    texels: Arc<Vec<Colour>>,
+}
+
+impl ImageTexture {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let image = image::open(path)?.into_rgb32f();
+        let (width, height) = image.dimensions();
+
+        let texels = image
+            .pixels()
+            .map(|p| Colour::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+
+        Ok(Self {
+            path: path.to_string(),
+            width: width as usize,
+            height: height as usize,
+            texels: Arc::new(texels),
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn texel(&self, x: usize, y: usize) -> Colour {
+        self.texels[y * self.width + x]
+    }
+
+    /// Bilinearly filtered lookup. Coordinates wrap, so tiling textures can be
+    /// used with uvs outside [0, 1]. `v = 0` is the bottom of the image.
+    pub fn sample(&self, uv: (f64, f64)) -> Colour {
+        let x = uv.0.rem_euclid(1.) * self.width as f64 - 0.5;
+        let y = (1. - uv.1.rem_euclid(1.)) * self.height as f64 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let wrap = |i: f64, n: usize| (i as isize).rem_euclid(n as isize) as usize;
+        let (x0, x1) = (wrap(x0, self.width), wrap(x0 + 1., self.width));
+        let (y0, y1) = (wrap(y0, self.height), wrap(y0 + 1., self.height));
+
+        let top = self.texel(x0, y0) * (1. - fx) + self.texel(x1, y0) * fx;
+        let bottom = self.texel(x0, y1) * (1. - fx) + self.texel(x1, y1) * fx;
+
+        top * (1. - fy) + bottom * fy
+    }
+
+    /// Samples the texture as a greyscale value, e.g. for height maps.
+    pub fn sample_scalar(&self, uv: (f64, f64)) -> f64 {
+        self.sample(uv).luminance()
+    }
+}
+
+impl TryFrom<String> for ImageTexture {
+    type Error = anyhow::Error;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        Self::load(&path)
+    }
+}
+
+impl From<ImageTexture> for String {
+    fn from(texture: ImageTexture) -> Self {
+        texture.path
+    }
+}
+
+/// A value in [0, 1] that is either the same everywhere or read from a
+/// greyscale texture. Scene files give either a number or a texture path.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Factor {
+    Constant(f64),
+    Texture(ImageTexture),
+}
+
+impl Factor {
+    pub fn evaluate(&self, uv: (f64, f64)) -> f64 {
+        match self {
+            Factor::Constant(value) => *value,
+            Factor::Texture(texture) => texture.sample_scalar(uv),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use super::Vec3;
+
+/// An orthonormal basis used to move directions in and out of a local
+/// shading space, where the normal is +Z.
+#[derive(Copy, Clone, Debug)]
+pub struct Frame {
+    pub s: Vec3,
+    pub t: Vec3,
+    pub n: Vec3,
+}
+
+impl Frame {
+    /// Builds a frame around `normal`, with `s` following `tangent` as closely
+    /// as possible. Falls back to an arbitrary tangent when `tangent` is zero
+    /// or parallel to the normal.
+    pub fn new(normal: Vec3, tangent: Vec3) -> Self {
+        let n = normal.unit();
+        let s = tangent - n * n.dot(&tangent);
+
+        let s = if s.length_squared() > 1e-12 {
+            s.unit()
+        } else {
+            let helper = if n.x().abs() > 0.9 {
+                Vec3::new((0., 1., 0.))
+            } else {
+                Vec3::new((1., 0., 0.))
+            };
+            helper.cross(&n).unit()
+        };
+
+        let t = n.cross(&s);
+
+        Self { s, t, n }
+    }
+
+    pub fn from_normal(normal: Vec3) -> Self {
+        Self::new(normal, Vec3::new((0., 0., 0.)))
+    }
+
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new((v.dot(&self.s), v.dot(&self.t), v.dot(&self.n)))
+    }
+
+    pub fn to_world(&self, v: &Vec3) -> Vec3 {
+        self.s * v.x() + self.t * v.y() + self.n * v.z()
+    }
+}
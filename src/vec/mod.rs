@@ -1,7 +1,14 @@
+mod frame;
+
+use std::f64::consts::PI;
+
 use clap::builder::TypedValueParser;
-use rand::{distributions::Uniform, prelude::Distribution, thread_rng};
 use serde::{Deserialize, Serialize};
 
+use crate::sampling;
+
+pub use self::frame::Frame;
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Vec3(f64, f64, f64);
 
@@ -48,13 +55,12 @@ impl Vec3 {
     }
 
     fn random(low: f64, high: f64) -> Vec3 {
-        let mut rng = thread_rng();
-        let distribution = Uniform::new(low, high);
+        let range = high - low;
 
         Vec3::new((
-            distribution.sample(&mut rng),
-            distribution.sample(&mut rng),
-            distribution.sample(&mut rng),
+            low + sampling::uniform() * range,
+            low + sampling::uniform() * range,
+            low + sampling::uniform() * range,
         ))
     }
 
@@ -67,13 +73,25 @@ impl Vec3 {
         }
     }
 
+    /// A uniformly chosen direction. Made from two numbers rather than by
+    /// rejection, so that it always takes the same dimensions of a sampler.
     pub fn random_unit_vector() -> Vec3 {
-        Self::random_in_unit_square().unit()
+        Self::on_unit_sphere(sampling::uniform_pair())
+    }
+
+    /// Maps two uniform numbers in `[0, 1)` to a direction, spreading them
+    /// evenly over the sphere.
+    pub fn on_unit_sphere((u, v): (f64, f64)) -> Vec3 {
+        let z = 1. - 2. * u;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * PI * v;
+
+        Vec3::new((r * phi.cos(), r * phi.sin(), z))
     }
 
     pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
         let on_unit_sphere = Self::random_unit_vector();
-        if on_unit_sphere.dot(&normal) > 0.0 {
+        if on_unit_sphere.dot(normal) > 0.0 {
             // In the same hemisphere as the normal
             on_unit_sphere
         } else {
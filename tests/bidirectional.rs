@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use raytacer::{
+    camera::{Camera, CameraConfig},
+    colour::Colour,
+    environment::Environment,
+    film::{Film, Filter},
+    geometry::{Geometry, Material, Shape},
+    integrator::Bidirectional,
+    sampling::{self, Sampler},
+    scene::Scene,
+    vec::Vec3,
+};
+
+const SIZE: (usize, usize) = (24, 16);
+
+fn geometry(shape: Shape, material: Material) -> Geometry {
+    Geometry {
+        shape,
+        material,
+        medium: None,
+    }
+}
+
+/// A glass ball casting a small light into a caustic on a white floor, in
+/// the dark.
+fn caustic() -> Scene {
+    Scene {
+        camera: Camera::new(
+            CameraConfig::Perspective {
+                look_from: Vec3::new((0., 2., 2.5)),
+                look_at: Vec3::new((0., 0., 0.)),
+                up: Vec3::new((0., 1., 0.)),
+                fov_degrees: 40.,
+                defocus_angle: 0.,
+                focus_dist: None,
+            },
+            SIZE,
+        )
+        .unwrap(),
+        environment: Environment::Constant {
+            colour: Colour::black(),
+        },
+        medium: None,
+        geometries: vec![
+            geometry(
+                Shape::Cuboid {
+                    min: Vec3::new((-5., -1., -5.)),
+                    max: Vec3::new((5., 0., 5.)),
+                },
+                Material::Lambertian {
+                    colour: Colour::white(),
+                    albedo: 0.8,
+                },
+            ),
+            geometry(
+                Shape::Sphere {
+                    centre: Vec3::new((0., 1.4, 0.)),
+                    radius: 0.5,
+                },
+                Material::Dialectric {
+                    ior: 1.5,
+                    dispersion: None,
+                    tint: Colour::white(),
+                    thin_walled: false,
+                    absorption: Colour::black(),
+                },
+            ),
+            geometry(
+                Shape::Sphere {
+                    centre: Vec3::new((0.2, 4., 0.)),
+                    radius: 0.1,
+                },
+                Material::Emissive {
+                    radiance: Colour::new(100., 100., 100.),
+                },
+            ),
+        ],
+        light_sampling: false,
+        spectral: false,
+    }
+}
+
+/// Numbers that look random but are the same on every run, so that the
+/// renders compared are too.
+struct Seeded(u64);
+
+impl Sampler for Seeded {
+    fn sample(&self, pixel: (usize, usize), index: usize, dimension: usize) -> f64 {
+        let values = [
+            self.0,
+            pixel.0 as u64,
+            pixel.1 as u64,
+            index as u64,
+            dimension as u64,
+        ];
+        let hash = values.iter().fold(0, |hash, &value| mix(hash ^ mix(value)));
+        (hash >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The finaliser of SplitMix64.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Renders the whole image, joining light subpaths to the camera.
+fn render_splatting(
+    scene: &Scene,
+    integrator: &Bidirectional,
+    samples: usize,
+    seed: u64,
+) -> Vec<Colour> {
+    let sampler: Arc<dyn Sampler> = Arc::new(Seeded(seed));
+    let mut film = Film::new(SIZE, Filter::Box, 0.5);
+    for y in 0..SIZE.1 {
+        for x in 0..SIZE.0 {
+            scene.render_pixel((x, y), samples, &sampler, integrator, &mut film);
+        }
+    }
+    film.into_image()
+}
+
+/// Renders each pixel from its own camera subpaths alone.
+fn render_from_camera(
+    scene: &Scene,
+    integrator: &Bidirectional,
+    samples: usize,
+    seed: u64,
+) -> Vec<Colour> {
+    let sampler: Arc<dyn Sampler> = Arc::new(Seeded(seed));
+    (0..SIZE.1)
+        .flat_map(|y| (0..SIZE.0).map(move |x| (x, y)))
+        .map(|coord| {
+            let total = (0..samples).fold(Colour::black(), |total, index| {
+                let colour = sampling::with_sample(&sampler, coord, index, || {
+                    scene.sample_pixel(coord, integrator)
+                });
+                total + colour
+            });
+            total / samples as f64
+        })
+        .collect()
+}
+
+fn mean(image: &[Colour]) -> f64 {
+    image.iter().map(Colour::luminance).sum::<f64>() / image.len() as f64
+}
+
+/// The mean squared difference between two independent renders, twice the
+/// variance of each pixel.
+fn noise(a: &[Colour], b: &[Colour]) -> f64 {
+    let squared = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| (a.luminance() - b.luminance()).powi(2));
+    squared.sum::<f64>() / a.len() as f64
+}
+
+#[test]
+fn joining_light_subpaths_to_the_camera_lowers_the_noise_of_caustics() {
+    let scene = caustic();
+    let integrator = Bidirectional { max_bounces: 5 };
+
+    // Even with four times the samples, the light through the glass is
+    // hard to find from the camera alone. The renders are seeded, as light
+    // reflected off the ball onto the floor and from there through the ball
+    // can only be found from the camera, and now and then a splatting
+    // render, with a quarter of the samples to average it over, finds some.
+    let splatting = [1, 2].map(|seed| render_splatting(&scene, &integrator, 64, seed));
+    let from_camera = [3, 4].map(|seed| render_from_camera(&scene, &integrator, 256, seed));
+
+    let noise_splatting = noise(&splatting[0], &splatting[1]);
+    let noise_from_camera = noise(&from_camera[0], &from_camera[1]);
+    assert!(
+        noise_splatting < noise_from_camera,
+        "noise {noise_splatting} with light subpaths joined to the camera, \
+        {noise_from_camera} without"
+    );
+
+    // Both converge to the same image, so the means of both pairs of
+    // renders differ by no more than their noise allows.
+    let pixels = 2. * splatting[0].len() as f64;
+    let mean_splatting = (mean(&splatting[0]) + mean(&splatting[1])) / 2.;
+    let mean_from_camera = (mean(&from_camera[0]) + mean(&from_camera[1])) / 2.;
+    let deviation = ((noise_splatting + noise_from_camera) / 2. / pixels).sqrt();
+    assert!(
+        (mean_splatting - mean_from_camera).abs() < 4. * deviation,
+        "mean {mean_splatting} with light subpaths joined to the camera, \
+        {mean_from_camera} without, give or take {deviation}"
+    );
+}
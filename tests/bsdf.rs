@@ -0,0 +1,348 @@
+use std::f64::consts::PI;
+
+use raytacer::{
+    colour::Colour,
+    geometry::{Bsdf, Fresnel, Material, Principled, Shape},
+    hit::Hit,
+    texture::{Factor, ImageTexture},
+    vec::{Frame, Vec3},
+};
+
+fn sphere() -> Shape {
+    Shape::Sphere {
+        centre: Vec3::new((0., 0., 0.)),
+        radius: 1.,
+    }
+}
+
+/// A hit with a tilted normal and a tangent frame around it.
+fn hit<'a>(shape: &'a Shape, material: &'a Material) -> Hit<'a> {
+    let normal = Vec3::new((0.2, 0.1, 1.)).unit();
+    let x = Vec3::new((1., 0., 0.));
+    let dpdu = x - normal * normal.dot(&x);
+
+    Hit {
+        shape,
+        material,
+        medium: None,
+        point: Vec3::new((0., 0., 1.)),
+        normal,
+        front_face: true,
+        uv: (0.3, 0.6),
+        dpdu,
+        dpdv: normal.cross(&dpdu),
+    }
+}
+
+/// A texture of one colour, written to a temporary file for loading.
+fn flat_texture(name: &str, rgb: [u8; 3]) -> ImageTexture {
+    let path = std::env::temp_dir().join(format!("raytacer_bsdf_{name}.png"));
+    image::RgbImage::from_pixel(4, 4, image::Rgb(rgb))
+        .save(&path)
+        .unwrap();
+
+    let texture = ImageTexture::load(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    texture
+}
+
+fn lambertian() -> Material {
+    Material::Lambertian {
+        colour: Colour::new(0.8, 0.5, 0.2),
+        albedo: 0.9,
+    }
+}
+
+fn conductor() -> Material {
+    Material::Conductor {
+        fresnel: Fresnel::Gold,
+        roughness: 0.4,
+        anisotropy: 0.3,
+    }
+}
+
+fn materials() -> Vec<(&'static str, Material)> {
+    let principled = |metallic: f64, clearcoat: f64, sheen: f64| {
+        serde_yaml::from_str::<Principled>(&format!(
+            "{{metallic: {metallic}, clearcoat: {clearcoat}, sheen: {sheen}, roughness: 0.4}}"
+        ))
+        .unwrap()
+    };
+
+    vec![
+        ("lambertian", lambertian()),
+        (
+            "diffuse",
+            Material::Diffuse {
+                colour: Colour::new(0.3, 0.6, 0.9),
+                albedo: 0.7,
+            },
+        ),
+        (
+            "fuzzy metal",
+            Material::Metal {
+                tint: Colour::new(0.9, 0.8, 0.7),
+                scatter: 0.4,
+            },
+        ),
+        ("conductor", conductor()),
+        ("principled", Material::Principled(principled(0., 0., 0.))),
+        (
+            "principled metal with clearcoat and sheen",
+            Material::Principled(principled(0.6, 0.8, 0.5)),
+        ),
+        (
+            "mix",
+            Material::Mix {
+                a: Box::new(lambertian()),
+                b: Box::new(conductor()),
+                factor: Factor::Constant(0.3),
+            },
+        ),
+        (
+            "mix with glass",
+            Material::Mix {
+                a: Box::new(Material::Dialectric {
+                    ior: 1.5,
+                    dispersion: None,
+                    tint: Colour::white(),
+                    thin_walled: false,
+                    absorption: Colour::black(),
+                }),
+                b: Box::new(lambertian()),
+                factor: Factor::Constant(0.6),
+            },
+        ),
+        (
+            "coated",
+            Material::Coated {
+                base: Box::new(lambertian()),
+                coat_ior: 1.5,
+                coat_roughness: 0.2,
+            },
+        ),
+        (
+            "normal mapped",
+            Material::NormalMapped {
+                base: Box::new(lambertian()),
+                normal_map: flat_texture("normal", [190, 100, 220]),
+                strength: 1.,
+            },
+        ),
+        (
+            "normal mapped coating",
+            Material::NormalMapped {
+                base: Box::new(Material::Coated {
+                    base: Box::new(conductor()),
+                    coat_ior: 1.5,
+                    coat_roughness: 0.1,
+                }),
+                normal_map: flat_texture("coated_normal", [100, 150, 230]),
+                strength: 0.5,
+            },
+        ),
+    ]
+}
+
+fn views() -> [Vec3; 3] {
+    [
+        Vec3::new((0.2, 0.1, 1.)).unit(),
+        Vec3::new((0.5, -0.3, 0.8)).unit(),
+        Vec3::new((-0.9, 0.2, 0.3)).unit(),
+    ]
+}
+
+#[test]
+fn sampled_pdf_matches_pdf() {
+    let shape = sphere();
+    for (name, material) in materials() {
+        let hit = hit(&shape, &material);
+        for wo in views() {
+            for _ in 0..2000 {
+                let Some(sample) = material.sample(&hit, &wo, None) else {
+                    continue;
+                };
+                if sample.flags.delta {
+                    continue;
+                }
+
+                let pdf = material.pdf(&hit, &wo, &sample.wi);
+                assert!(
+                    (sample.pdf - pdf).abs() <= 1e-9 * pdf.max(1.),
+                    "{name}: sampled pdf {} but pdf gives {pdf}",
+                    sample.pdf
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn sampled_weight_is_brdf_times_cosine_over_pdf() {
+    let shape = sphere();
+    for (name, material) in materials() {
+        let hit = hit(&shape, &material);
+        for wo in views() {
+            for _ in 0..2000 {
+                let Some(sample) = material.sample(&hit, &wo, None) else {
+                    continue;
+                };
+                if sample.flags.delta {
+                    continue;
+                }
+
+                let cos_theta = sample.wi.dot(&hit.normal).abs();
+                let expected = material.eval(&hit, &wo, &sample.wi) * (cos_theta / sample.pdf);
+                for (actual, expected) in [
+                    (sample.weight.r(), expected.r()),
+                    (sample.weight.g(), expected.g()),
+                    (sample.weight.b(), expected.b()),
+                ] {
+                    assert!(
+                        (actual - expected).abs() <= 1e-6 * expected.abs().max(1.),
+                        "{name}: weight {actual} but eval gives {expected}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn white_lambertian_reflects_everything() {
+    let material = Material::Lambertian {
+        colour: Colour::white(),
+        albedo: 1.,
+    };
+    let shape = sphere();
+    let hit = hit(&shape, &material);
+    let wo = views()[1];
+
+    // Every sample carries all of the light on.
+    for _ in 0..100 {
+        let sample = material.sample(&hit, &wo, None).unwrap();
+        assert!((sample.weight.g() - 1.).abs() < 1e-12);
+    }
+
+    // And the BRDF times cosine integrates to one over the hemisphere.
+    let (steps_z, steps_phi) = (400, 400);
+    let frame = Frame::from_normal(hit.normal);
+    let mut reflected = 0.;
+    for i in 0..steps_z {
+        let cos_theta = (i as f64 + 0.5) / steps_z as f64;
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        for j in 0..steps_phi {
+            let phi = 2. * PI * (j as f64 + 0.5) / steps_phi as f64;
+            let local = Vec3::new((sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta));
+            let wi = frame.to_world(&local);
+            reflected += material.eval(&hit, &wo, &wi).g() * cos_theta;
+        }
+    }
+    reflected *= 2. * PI / (steps_z * steps_phi) as f64;
+
+    assert!((reflected - 1.).abs() < 1e-4, "reflected {reflected}");
+}
+
+fn principled(yaml: &str) -> Material {
+    Material::Principled(serde_yaml::from_str(yaml).unwrap())
+}
+
+fn principled_materials() -> Vec<Material> {
+    [
+        "{roughness: 0.3}",
+        "{roughness: 0.5, anisotropy: 0.6, ior: 1.33}",
+        "{roughness: 0.2, specular: 1, ior: 2.4}",
+        "{metallic: 0.5, roughness: 0.25, clearcoat: 1, clearcoat_roughness: 0.1}",
+        "{roughness: 0.7, sheen: 1, sheen_tint: 0.2}",
+        "{roughness: 0.3, transmission: 0.7, ior: 1.7}",
+    ]
+    .into_iter()
+    .map(principled)
+    .collect()
+}
+
+#[test]
+fn principled_reflections_match_eval_and_pdf() {
+    let shape = sphere();
+    for material in principled_materials() {
+        let hit = hit(&shape, &material);
+        for wo in views() {
+            for _ in 0..2000 {
+                let Some(sample) = material.sample(&hit, &wo, None) else {
+                    continue;
+                };
+                // Transmission is only ever sampled.
+                if sample.wi.dot(&hit.normal) <= 0. {
+                    continue;
+                }
+
+                let pdf = material.pdf(&hit, &wo, &sample.wi);
+                assert!((sample.pdf - pdf).abs() <= 1e-9 * pdf.max(1.));
+
+                let expected =
+                    material.eval(&hit, &wo, &sample.wi) * (sample.wi.dot(&hit.normal) / pdf);
+                for (actual, expected) in [
+                    (sample.weight.r(), expected.r()),
+                    (sample.weight.g(), expected.g()),
+                    (sample.weight.b(), expected.b()),
+                ] {
+                    assert!((actual - expected).abs() <= 1e-6 * expected.abs().max(1.));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn white_principled_materials_do_not_create_light() {
+    let shape = sphere();
+    for material in principled_materials() {
+        let Material::Principled(principled) = material else {
+            unreachable!()
+        };
+        let material = Material::Principled(Principled {
+            base_colour: Colour::white(),
+            ..principled
+        });
+        let hit = hit(&shape, &material);
+
+        for wo in views() {
+            let count = 20000;
+            let mut reflected = Colour::black();
+            for _ in 0..count {
+                if let Some(sample) = material.sample(&hit, &wo, None) {
+                    reflected += sample.weight;
+                }
+            }
+            let albedo = reflected / count as f64;
+            for channel in [albedo.r(), albedo.g(), albedo.b()] {
+                assert!(channel <= 1.02, "albedo {channel}");
+            }
+        }
+    }
+}
+
+#[test]
+fn principled_reflectance_follows_its_ior() {
+    let shape = sphere();
+    for (ior, specular, f0) in [(1.5, 0.5, 0.04), (2., 0.5, 1. / 9.), (2., 0.25, 1. / 18.)] {
+        let material = principled(&format!(
+            "{{base_colour: [0, 0, 0], roughness: 0.05, ior: {ior}, specular: {specular}}}"
+        ));
+        let hit = hit(&shape, &material);
+
+        // A nearly smooth surface seen head on reflects f0 of the light.
+        let count = 2000;
+        let mut reflected = 0.;
+        for _ in 0..count {
+            if let Some(sample) = material.sample(&hit, &hit.normal, None) {
+                reflected += sample.weight.g();
+            }
+        }
+        let reflectance = reflected / count as f64;
+        assert!(
+            (reflectance - f0).abs() < 0.03 * f0,
+            "ior {ior}: expected {f0}, got {reflectance}"
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use raytacer::{
+    camera::{Camera, CameraConfig, StereoLayout},
+    vec::Vec3,
+};
+
+fn assert_same_direction(a: &Vec3, b: &Vec3) {
+    assert!((a.unit() - b.unit()).length() < 1e-9, "{a:?} != {b:?}");
+}
+
+#[test]
+fn middle_pixel_looks_at_look_at() {
+    let look_from = Vec3::new((1., 2., 3.));
+    let look_at = Vec3::new((-2., 0.5, -4.));
+
+    for defocus_angle in [0., 2.] {
+        let camera = Camera::new(
+            CameraConfig::Perspective {
+                look_from,
+                look_at,
+                up: Vec3::new((0., 1., 0.)),
+                fov_degrees: 50.,
+                defocus_angle,
+                focus_dist: None,
+            },
+            (9, 5),
+        )
+        .unwrap();
+
+        // Through a defocused lens, the ray still passes through the point
+        // in focus.
+        let ray = camera.screen_to_world((4, 2)).unwrap();
+        assert_same_direction(&(look_at - ray.origin), &ray.direction);
+    }
+}
+
+#[test]
+fn orthographic_rays_are_parallel_across_view_width() {
+    let look_from = Vec3::new((0., 1., 5.));
+    let look_at = Vec3::new((0., 0., 0.));
+    let camera = Camera::new(
+        CameraConfig::Orthographic {
+            look_from,
+            look_at,
+            up: Vec3::new((0., 1., 0.)),
+            view_width: 4.,
+        },
+        (8, 6),
+    )
+    .unwrap();
+
+    for coord in [(0, 0), (7, 0), (3, 2), (7, 5)] {
+        let ray = camera.screen_to_world(coord).unwrap();
+        assert_same_direction(&ray.direction, &(look_at - look_from));
+    }
+
+    let left = camera.screen_to_world_at((0, 3), (0., 0.5)).unwrap();
+    let right = camera.screen_to_world_at((7, 3), (1., 0.5)).unwrap();
+    let width = (right.origin - left.origin).length();
+    assert!((width - 4.).abs() < 1e-9, "{width} != 4");
+
+    let top = camera.screen_to_world_at((4, 0), (0.5, 0.)).unwrap();
+    let bottom = camera.screen_to_world_at((4, 5), (0.5, 1.)).unwrap();
+    let height = (bottom.origin - top.origin).length();
+    assert!((height - 3.).abs() < 1e-9, "{height} != 3");
+}
+
+/// A panoramic camera at the origin looking down -Z.
+fn panoramic(config: fn(Vec3, Vec3, Vec3) -> CameraConfig, size: (usize, usize)) -> Camera {
+    let config = config(
+        Vec3::new((0., 0., 0.)),
+        Vec3::new((0., 0., -1.)),
+        Vec3::new((0., 1., 0.)),
+    );
+    Camera::new(config, size).unwrap()
+}
+
+#[test]
+fn middle_of_an_equirectangular_image_looks_forward() {
+    let camera = panoramic(
+        |look_from, look_at, up| CameraConfig::Equirectangular {
+            look_from,
+            look_at,
+            up,
+        },
+        (8, 4),
+    );
+
+    let ray = camera.screen_to_world_at((3, 1), (1., 1.)).unwrap();
+    assert_same_direction(&ray.direction, &Vec3::new((0., 0., -1.)));
+    let ray = camera.screen_to_world_at((3, 0), (1., 0.)).unwrap();
+    assert_same_direction(&ray.direction, &Vec3::new((0., 1., 0.)));
+}
+
+#[test]
+fn each_cubemap_face_looks_down_its_axis() {
+    let camera = panoramic(
+        |look_from, look_at, up| CameraConfig::Cubemap {
+            look_from,
+            look_at,
+            up,
+        },
+        (24, 4),
+    );
+
+    // Right, left, up, down, forward and back.
+    let axes = [
+        (1., 0., 0.),
+        (-1., 0., 0.),
+        (0., 1., 0.),
+        (0., -1., 0.),
+        (0., 0., -1.),
+        (0., 0., 1.),
+    ];
+    for (face, axis) in axes.into_iter().enumerate() {
+        let ray = camera
+            .screen_to_world_at((face * 4 + 1, 1), (1., 1.))
+            .unwrap();
+        assert_same_direction(&ray.direction, &Vec3::new(axis));
+    }
+}
+
+#[test]
+fn fisheye_sees_nothing_outside_its_circle() {
+    let camera = panoramic(
+        |look_from, look_at, up| CameraConfig::Fisheye {
+            look_from,
+            look_at,
+            up,
+            fov_degrees: 180.,
+            mapping: Default::default(),
+        },
+        (8, 6),
+    );
+
+    let ray = camera.screen_to_world_at((3, 2), (1., 1.)).unwrap();
+    assert_same_direction(&ray.direction, &Vec3::new((0., 0., -1.)));
+
+    // The circle fits the image's height, leaving its corners and the ends
+    // of the middle row outside.
+    assert!(camera.screen_to_world_at((3, 0), (1., 0.01)).is_some());
+    for (coord, offset) in [
+        ((0, 0), (0.5, 0.5)),
+        ((7, 5), (0.5, 0.5)),
+        ((0, 3), (0.5, 0.)),
+    ] {
+        assert!(camera.screen_to_world_at(coord, offset).is_none());
+    }
+}
+
+#[test]
+fn stereo_needs_a_pixel_for_each_eye() {
+    let stereo = |layout| CameraConfig::Stereo {
+        camera: Box::new(CameraConfig::Perspective {
+            look_from: Vec3::new((0., 0., 0.)),
+            look_at: Vec3::new((0., 0., -1.)),
+            up: Vec3::new((0., 1., 0.)),
+            fov_degrees: 90.,
+            defocus_angle: 0.,
+            focus_dist: None,
+        }),
+        interpupillary_distance: 0.064,
+        convergence: Default::default(),
+        convergence_distance: None,
+        layout,
+    };
+
+    assert!(Camera::new(stereo(StereoLayout::SideBySide), (1, 4)).is_err());
+    assert!(Camera::new(stereo(StereoLayout::TopBottom), (4, 1)).is_err());
+
+    // The smallest images that can be split still see something in every
+    // pixel.
+    for (layout, size) in [
+        (StereoLayout::SideBySide, (2, 1)),
+        (StereoLayout::TopBottom, (1, 2)),
+    ] {
+        let camera = Camera::new(stereo(layout), size).unwrap();
+        assert!(camera.screen_to_world((0, 0)).is_some());
+        assert!(camera.screen_to_world((size.0 - 1, size.1 - 1)).is_some());
+    }
+}
+
+fn physical(
+    focal_length_mm: f64,
+    sensor_width_mm: f64,
+    f_stop: f64,
+    focus_dist: f64,
+    shutter_speed: Option<f64>,
+) -> CameraConfig {
+    CameraConfig::Physical {
+        look_from: Vec3::new((0., 0., 0.)),
+        look_at: Vec3::new((0., 0., -1.)),
+        up: Vec3::new((0., 1., 0.)),
+        focal_length_mm,
+        sensor_width_mm,
+        f_stop,
+        aperture_blades: 0,
+        aperture_rotation_degrees: 0.,
+        focus_dist: Some(focus_dist),
+        shutter_speed,
+        iso: 100.,
+    }
+}
+
+#[test]
+fn standard_lens_sees_about_forty_degrees_across_full_frame() {
+    let camera = Camera::new(physical(50., 36., 2., 1e9, None), (12, 8)).unwrap();
+
+    let left = camera.screen_to_world_at((0, 4), (0., 0.)).unwrap();
+    let right = camera.screen_to_world_at((11, 4), (1., 0.)).unwrap();
+    let cos = left.direction.unit().dot(&right.direction.unit());
+    let fov = cos.acos().to_degrees();
+
+    // Twice the angle whose tangent is half the sensor over the focal
+    // length.
+    assert!((fov - 39.6).abs() < 0.05, "{fov}° across");
+}
+
+#[test]
+fn exposure_follows_saturation_based_sensitivity() {
+    // Sunny 16: at f/16, a shutter of one over the ISO.
+    let camera = Camera::new(physical(50., 36., 16., 10., Some(0.01)), (4, 4)).unwrap();
+
+    // ISO 12232 has the sensor saturate at 78 / ISO lux seconds, and a lens
+    // passes 0.65 of the scene's luminance through to the sensor over the
+    // square of the f-stop.
+    let saturating_luminance = 78. / 100. / 0.65 * 16. * 16. / 0.01;
+    let exposure = camera.exposure();
+    assert!(
+        (exposure * saturating_luminance - 1.).abs() < 1e-9,
+        "{exposure} * {saturating_luminance} != 1"
+    );
+
+    let unexposed = Camera::new(physical(50., 36., 16., 10., None), (4, 4)).unwrap();
+    assert_eq!(unexposed.exposure(), 1.);
+}
+
+#[test]
+fn physical_cameras_need_a_lens_that_can_focus() {
+    assert!(Camera::new(physical(50., 36., 0., 10., None), (4, 4)).is_err());
+    assert!(Camera::new(physical(50., 0., 2., 10., None), (4, 4)).is_err());
+    assert!(Camera::new(physical(50., 36., 2., 0.05, None), (4, 4)).is_err());
+    assert!(Camera::new(physical(50., 36., 2., 0.051, None), (4, 4)).is_ok());
+}
+
+#[test]
+fn split_images_have_a_region_for_each_view() {
+    let cubemap = panoramic(
+        |look_from, look_at, up| CameraConfig::Cubemap {
+            look_from,
+            look_at,
+            up,
+        },
+        (27, 4),
+    );
+    // Faces four and a half pixels wide, each with the pixels whose middles
+    // are on it.
+    assert_eq!(cubemap.region((0, 1)), Some((0..4, 0..4)));
+    assert_eq!(cubemap.region((4, 3)), Some((4..9, 0..4)));
+    assert_eq!(cubemap.region((26, 0)), Some((22..27, 0..4)));
+
+    let stereo = |camera, layout| CameraConfig::Stereo {
+        camera: Box::new(camera),
+        interpupillary_distance: 0.064,
+        convergence: Default::default(),
+        convergence_distance: None,
+        layout,
+    };
+    let perspective = CameraConfig::Perspective {
+        look_from: Vec3::new((0., 0., 0.)),
+        look_at: Vec3::new((0., 0., -1.)),
+        up: Vec3::new((0., 1., 0.)),
+        fov_degrees: 90.,
+        defocus_angle: 0.,
+        focus_dist: None,
+    };
+    let camera = Camera::new(
+        stereo(perspective.clone(), StereoLayout::SideBySide),
+        (9, 4),
+    )
+    .unwrap();
+    assert_eq!(camera.region((3, 2)), Some((0..4, 0..4)));
+    assert_eq!(camera.region((8, 2)), Some((4..9, 0..4)));
+
+    let camera = Camera::new(stereo(perspective, StereoLayout::TopBottom), (9, 4)).unwrap();
+    assert_eq!(camera.region((3, 2)), Some((0..9, 2..4)));
+
+    // Each eye's cubemap faces, with the column left over on the last.
+    let cubemap = CameraConfig::Cubemap {
+        look_from: Vec3::new((0., 0., 0.)),
+        look_at: Vec3::new((0., 0., -1.)),
+        up: Vec3::new((0., 1., 0.)),
+    };
+    let camera = Camera::new(stereo(cubemap, StereoLayout::SideBySide), (25, 2)).unwrap();
+    assert_eq!(camera.region((1, 0)), Some((0..2, 0..2)));
+    assert_eq!(camera.region((13, 0)), Some((12..14, 0..2)));
+    assert_eq!(camera.region((24, 1)), Some((22..25, 0..2)));
+
+    // One view, with nothing to keep samples from crossing.
+    let equirectangular = panoramic(
+        |look_from, look_at, up| CameraConfig::Equirectangular {
+            look_from,
+            look_at,
+            up,
+        },
+        (8, 4),
+    );
+    assert_eq!(equirectangular.region((1, 1)), None);
+}
@@ -0,0 +1,122 @@
+use raytacer::environment::{Distribution1D, Distribution2D};
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "expected {expected}, got {actual}"
+    );
+}
+
+#[test]
+fn one_dimensional_pdf_follows_the_weights() {
+    let distribution = Distribution1D::new(vec![1., 3., 0., 4.]);
+
+    assert_close(distribution.pdf(0), 0.5, 1e-12);
+    assert_close(distribution.pdf(1), 1.5, 1e-12);
+    assert_close(distribution.pdf(2), 0., 1e-12);
+    assert_close(distribution.pdf(3), 2., 1e-12);
+
+    let integral: f64 = (0..distribution.len())
+        .map(|i| distribution.pdf(i) / distribution.len() as f64)
+        .sum();
+    assert_close(integral, 1., 1e-12);
+}
+
+#[test]
+fn one_dimensional_samples_land_in_proportion_to_the_pdf() {
+    let distribution = Distribution1D::new(vec![1., 3., 0., 4.]);
+    let count = 8000;
+
+    let mut histogram = [0; 4];
+    for i in 0..count {
+        let (index, offset) = distribution.sample((i as f64 + 0.5) / count as f64);
+        assert!((0. ..1.).contains(&offset));
+        histogram[index] += 1;
+    }
+
+    for (index, hits) in histogram.into_iter().enumerate() {
+        let expected = distribution.pdf(index) / distribution.len() as f64;
+        assert_close(hits as f64 / count as f64, expected, 1e-3);
+    }
+}
+
+#[test]
+fn all_zero_weights_fall_back_to_uniform() {
+    let distribution = Distribution1D::new(vec![0.; 5]);
+
+    for i in 0..5 {
+        assert_close(distribution.pdf(i), 1., 1e-12);
+    }
+    assert_eq!(distribution.sample(0.5).0, 2);
+}
+
+fn map() -> (Distribution2D, usize, usize) {
+    let (width, height) = (8, 4);
+    let weights: Vec<f64> = (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            // A bright spot, a dark corner and a smooth ramp elsewhere.
+            match (x, y) {
+                (5, 1) => 40.,
+                (0, 3) => 0.,
+                _ => 1. + x as f64 * 0.5 + y as f64,
+            }
+        })
+        .collect();
+
+    (Distribution2D::new(&weights, width, height), width, height)
+}
+
+#[test]
+fn two_dimensional_pdf_integrates_to_one() {
+    let (distribution, width, height) = map();
+
+    let mut integral = 0.;
+    for y in 0..height {
+        for x in 0..width {
+            let centre = (
+                (x as f64 + 0.5) / width as f64,
+                (y as f64 + 0.5) / height as f64,
+            );
+            integral += distribution.pdf(centre) / (width * height) as f64;
+        }
+    }
+
+    assert_close(integral, 1., 1e-12);
+}
+
+#[test]
+fn two_dimensional_samples_agree_with_pdf() {
+    let (distribution, width, height) = map();
+    let side = 400;
+
+    let mut histogram = vec![0; width * height];
+    for i in 0..side {
+        for j in 0..side {
+            let xi = (
+                (i as f64 + 0.5) / side as f64,
+                (j as f64 + 0.5) / side as f64,
+            );
+            let (point, pdf) = distribution.sample(xi);
+
+            assert_close(pdf, distribution.pdf(point), 1e-12);
+
+            let x = (point.0 * width as f64) as usize;
+            let y = (point.1 * height as f64) as usize;
+            histogram[y * width + x] += 1;
+        }
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let centre = (
+                (x as f64 + 0.5) / width as f64,
+                (y as f64 + 0.5) / height as f64,
+            );
+            let expected = distribution.pdf(centre) / (width * height) as f64;
+            let actual = histogram[y * width + x] as f64 / (side * side) as f64;
+            assert_close(actual, expected, 2e-3);
+        }
+    }
+    assert_eq!(histogram[3 * width], 0);
+}
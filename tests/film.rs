@@ -0,0 +1,108 @@
+use std::f64::consts::PI;
+
+use raytacer::{
+    colour::Colour,
+    film::{Film, Filter},
+};
+
+const FILTERS: [Filter; 5] = [
+    Filter::Box,
+    Filter::Tent,
+    Filter::Gaussian,
+    Filter::Mitchell,
+    Filter::Lanczos,
+];
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{a} != {b}");
+}
+
+fn assert_same_image(a: &[Colour], b: &[Colour]) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b) {
+        assert_close(a.r(), b.r());
+        assert_close(a.g(), b.g());
+        assert_close(a.b(), b.b());
+    }
+}
+
+#[test]
+fn filters_fall_to_zero_at_their_radius() {
+    for filter in FILTERS {
+        let radius = filter.default_radius();
+        assert!(filter.evaluate((0., 0.), radius) > 0.);
+        for offset in [(radius, 0.), (0., -radius), (radius + 0.1, 0.2)] {
+            assert_eq!(
+                filter.evaluate(offset, radius),
+                0.,
+                "{filter:?} at {offset:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn mitchell_and_lanczos_match_their_formulas() {
+    // With B = C = 1/3, the cubic is 8/9 in the middle and 1/18 halfway
+    // out.
+    assert_close(Filter::Mitchell.evaluate((0., 0.), 2.), 64. / 81.);
+    assert_close(Filter::Mitchell.evaluate((1., 0.), 2.), 4. / 81.);
+    assert_close(Filter::Mitchell.evaluate((-1., 1.), 2.), 1. / 324.);
+
+    // sinc(1/2) sinc(1/6), and zero at every whole pixel.
+    assert_close(Filter::Lanczos.evaluate((0.5, 0.), 3.), 6. / (PI * PI));
+    assert_close(Filter::Lanczos.evaluate((0., 0.), 3.), 1.);
+    assert_close(Filter::Lanczos.evaluate((2., 0.), 3.), 0.);
+}
+
+/// Samples spread over a 7x5 image, with every colour different.
+fn samples() -> Vec<((f64, f64), Colour)> {
+    (0..35 * 4)
+        .map(|i| {
+            let x = (i as f64 * 0.618_034).fract() * 7.;
+            let y = (i as f64 * 0.754_878).fract() * 5.;
+            let colour = Colour::new(x / 7., y / 5., (i % 9) as f64 / 9.);
+            ((x, y), colour)
+        })
+        .collect()
+}
+
+#[test]
+fn tiles_merge_into_the_same_image() {
+    for filter in FILTERS {
+        let radius = filter.default_radius();
+
+        let mut whole = Film::new((7, 5), filter, radius);
+        for &(position, colour) in &samples() {
+            whole.add_sample(position, colour, None);
+        }
+
+        // Each tile takes the samples inside its rows.
+        let mut tiled = Film::new((7, 5), filter, radius);
+        for rows in [0..2, 2..3, 3..5] {
+            let mut tile = tiled.tile(rows.clone());
+            for &(position, colour) in &samples() {
+                if rows.contains(&(position.1 as usize)) {
+                    tile.add_sample(position, colour, None);
+                }
+            }
+            tiled.merge(&tile);
+        }
+
+        assert_same_image(&whole.into_image(), &tiled.into_image());
+    }
+}
+
+#[test]
+fn samples_stay_in_their_region() {
+    let mut film = Film::new((8, 2), Filter::Tent, 2.);
+
+    // Just left of the middle, in the left half of a side by side image.
+    film.add_sample((3.9, 1.), Colour::white(), Some((0..4, 0..2)));
+    let image = film.into_image();
+
+    for row in image.chunks(8) {
+        assert!(row[3].r() > 0.);
+        assert!(row[4..].iter().all(|colour| colour.r() == 0.));
+    }
+}
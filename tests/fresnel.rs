@@ -0,0 +1,65 @@
+use std::f64::consts::FRAC_PI_4;
+
+use raytacer::geometry::{fresnel_dielectric, fresnel_schlick};
+
+const AIR_TO_GLASS: f64 = 1. / 1.5;
+const GLASS_TO_AIR: f64 = 1.5;
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "expected {expected}, got {actual}"
+    );
+}
+
+#[test]
+fn glass_reflects_four_percent_at_normal_incidence() {
+    assert_close(fresnel_dielectric(1., AIR_TO_GLASS), 0.04, 1e-9);
+    assert_close(fresnel_schlick(1., AIR_TO_GLASS), 0.04, 1e-9);
+}
+
+#[test]
+fn water_reflects_two_percent_at_normal_incidence() {
+    assert_close(fresnel_dielectric(1., 1. / 1.333), 0.0204, 1e-4);
+    assert_close(fresnel_schlick(1., 1. / 1.333), 0.0204, 1e-4);
+}
+
+#[test]
+fn glass_at_forty_five_degrees() {
+    assert_close(
+        fresnel_dielectric(FRAC_PI_4.cos(), AIR_TO_GLASS),
+        0.0502,
+        1e-4,
+    );
+}
+
+#[test]
+fn reflectance_is_total_at_grazing_angles() {
+    assert_close(fresnel_dielectric(0., AIR_TO_GLASS), 1., 1e-9);
+    assert_close(fresnel_schlick(0., AIR_TO_GLASS), 1., 1e-9);
+}
+
+#[test]
+fn total_internal_reflection_beyond_critical_angle() {
+    let critical = (1. / GLASS_TO_AIR).asin();
+
+    assert_close(
+        fresnel_dielectric((critical + 0.01).cos(), GLASS_TO_AIR),
+        1.,
+        0.,
+    );
+    assert!(fresnel_dielectric((critical - 0.01).cos(), GLASS_TO_AIR) < 1.);
+}
+
+#[test]
+fn schlick_stays_close_to_exact_fresnel() {
+    for degrees in 0..80 {
+        let cos_theta = (degrees as f64).to_radians().cos();
+
+        assert_close(
+            fresnel_schlick(cos_theta, AIR_TO_GLASS),
+            fresnel_dielectric(cos_theta, AIR_TO_GLASS),
+            0.02,
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use raytacer::{
+    camera::{Camera, CameraConfig},
+    colour::Colour,
+    environment::Environment,
+    geometry::{Geometry, Material, Shape},
+    integrator::{Integrator, IterativePathTracer, PathTracer},
+    medium::Medium,
+    ray::Ray,
+    scene::{interior_transmittance, Scene},
+    vec::Vec3,
+};
+
+fn fog(density: f64) -> Medium {
+    Medium::Homogeneous {
+        density,
+        albedo: Colour::white(),
+        anisotropy: 0.,
+    }
+}
+
+fn sphere(radius: f64, material: Material, medium: Option<Medium>) -> Geometry {
+    Geometry {
+        shape: Shape::Sphere {
+            centre: Vec3::new((0., 0., -3.)),
+            radius,
+        },
+        material,
+        medium,
+    }
+}
+
+fn glass(thin_walled: bool) -> Material {
+    Material::Dialectric {
+        ior: 1.5,
+        dispersion: None,
+        tint: Colour::white(),
+        thin_walled,
+        absorption: Colour::black(),
+    }
+}
+
+fn scene(medium: Option<Medium>, geometries: Vec<Geometry>) -> Scene {
+    Scene {
+        camera: Camera::new(
+            CameraConfig::Perspective {
+                look_from: Vec3::new((0., 0., 0.)),
+                look_at: Vec3::new((0., 0., -1.)),
+                up: Vec3::new((0., 1., 0.)),
+                fov_degrees: 90.,
+                defocus_angle: 0.,
+                focus_dist: None,
+            },
+            (4, 4),
+        )
+        .unwrap(),
+        environment: Environment::Constant {
+            colour: Colour::new(0.5, 0.6, 0.7),
+        },
+        medium,
+        geometries,
+        light_sampling: true,
+        spectral: false,
+    }
+}
+
+/// A fog bank in hazy air, with a glass ball inside it.
+fn nested() -> Scene {
+    scene(
+        Some(fog(0.01)),
+        vec![
+            sphere(2., Material::Interface, Some(fog(1.))),
+            sphere(0.5, glass(false), None),
+        ],
+    )
+}
+
+fn same(medium: Option<&Medium>, expected: Option<&Medium>) -> bool {
+    match (medium, expected) {
+        (Some(medium), Some(expected)) => std::ptr::eq(medium, expected),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn medium_at_finds_the_innermost_shape() {
+    let scene = nested();
+    let bank = scene.geometries[0].medium.as_ref();
+
+    assert!(same(
+        scene.medium_at(&Vec3::new((0., 0., 0.))),
+        scene.medium.as_ref()
+    ));
+    assert!(same(scene.medium_at(&Vec3::new((0., 0., -1.2))), bank));
+    assert!(same(scene.medium_at(&Vec3::new((0., 0., -3.))), None));
+}
+
+#[test]
+fn crossing_surfaces_changes_the_medium() {
+    let scene = nested();
+    let air = scene.medium.as_ref();
+    let bank = scene.geometries[0].medium.as_ref();
+    let forward = Vec3::new((0., 0., -1.));
+
+    // Into the fog bank through its interface, and into the glass.
+    let ray = Ray::new(Vec3::new((0., 0., 0.)), forward);
+    let hit = scene.hit_test(&ray).unwrap();
+    assert!(same(scene.medium_after(&hit, air, &forward), bank));
+    assert!(same(scene.medium_after(&hit, air, &-forward), air));
+
+    let ray = Ray::new(hit.point, forward);
+    let hit = scene.hit_test(&ray).unwrap();
+    assert!(same(scene.medium_after(&hit, bank, &forward), None));
+    assert!(same(scene.medium_after(&hit, bank, &-forward), bank));
+
+    // Out of the glass back into the fog, and out of the fog.
+    let ray = Ray::new(Vec3::new((0., 0., -3.)), forward);
+    let hit = scene.hit_test(&ray).unwrap();
+    assert!(same(scene.medium_after(&hit, None, &forward), bank));
+
+    let ray = Ray::new(hit.point, forward);
+    let hit = scene.hit_test(&ray).unwrap();
+    assert!(same(scene.medium_after(&hit, bank, &forward), air));
+}
+
+#[test]
+fn thin_walled_glass_keeps_the_medium() {
+    let scene = scene(Some(fog(0.01)), vec![sphere(0.5, glass(true), None)]);
+    let forward = Vec3::new((0., 0., -1.));
+
+    let hit = scene
+        .hit_test(&Ray::new(Vec3::new((0., 0., 0.)), forward))
+        .unwrap();
+    let haze = scene.medium.as_ref();
+    assert!(same(scene.medium_after(&hit, haze, &forward), haze));
+}
+
+#[test]
+fn only_solid_glass_absorbs_inside() {
+    for (thin_walled, expected) in [(false, (-0.5_f64).exp()), (true, 1.)] {
+        let absorbing = Material::Dialectric {
+            ior: 1.5,
+            dispersion: None,
+            tint: Colour::white(),
+            thin_walled,
+            absorption: Colour::new(1., 1., 1.),
+        };
+        let scene = scene(None, vec![sphere(0.5, absorbing, None)]);
+
+        // Leaving the ball from its centre, through the back of its surface.
+        let ray = Ray::new(Vec3::new((0., 0., -3.)), Vec3::new((0., 0., -1.)));
+        let hit = scene.hit_test(&ray).unwrap();
+        assert!(!hit.front_face);
+
+        let transmittance = interior_transmittance(&hit, 0.5);
+        assert!(
+            (transmittance.g() - expected).abs() < 1e-12,
+            "{thin_walled}"
+        );
+    }
+}
+
+#[test]
+fn scene_medium_doesnt_dim_the_environment() {
+    let scene = scene(Some(fog(5.)), Vec::new());
+    let ray = Ray::new(Vec3::new((0., 0., 0.)), Vec3::new((0.3, 0.4, -1.)));
+
+    let transmittance = scene.transmittance_to_environment(&ray, scene.medium.as_ref());
+    assert_eq!(transmittance.r(), 1.);
+
+    let path = PathTracer {
+        max_bounces: 4,
+        roulette_depth: 3,
+    };
+    let iterative = IterativePathTracer {
+        max_bounces: 4,
+        roulette_depth: 3,
+    };
+    for integrator in [&path as &dyn Integrator, &iterative] {
+        let radiance = integrator.radiance(&scene, &ray, None);
+        assert_eq!((radiance.r(), radiance.g(), radiance.b()), (0.5, 0.6, 0.7));
+    }
+}
+
+#[test]
+fn media_between_surfaces_dim_the_environment() {
+    let scene = nested();
+    let ray = Ray::new(Vec3::new((0.6, 2.5, -3.)), Vec3::new((0., -1., 0.)));
+
+    // Down through the haze and across the fog bank, missing the glass. The
+    // haze beyond the fog bank is outside the scene, so doesn't count.
+    let chord = 2. * (4_f64 - 0.36).sqrt();
+    let expected = (-(chord + 0.01 * (2.5 - chord / 2.))).exp();
+    let transmittance = scene.transmittance_to_environment(&ray, scene.medium.as_ref());
+
+    assert!(
+        (transmittance.r() - expected).abs() < 1e-9,
+        "{} != {expected}",
+        transmittance.r()
+    );
+}
@@ -0,0 +1,142 @@
+use std::f64::consts::PI;
+
+use raytacer::{
+    colour::Colour,
+    geometry::{Fresnel, Ggx},
+    vec::Vec3,
+};
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "expected {expected}, got {actual}"
+    );
+}
+
+fn direction(theta: f64, phi: f64) -> Vec3 {
+    Vec3::new((
+        theta.sin() * phi.cos(),
+        theta.sin() * phi.sin(),
+        theta.cos(),
+    ))
+}
+
+/// Integrates `f` over the upper hemisphere with the midpoint rule, in
+/// `phi` and in `s` where `1 - cos_theta = s³`, which puts most of the steps
+/// near the normal where smooth distributions peak.
+fn integrate_hemisphere(f: impl Fn(&Vec3) -> f64) -> f64 {
+    let (steps_s, steps_phi) = (600, 200);
+
+    let mut total = 0.;
+    for i in 0..steps_s {
+        let s = (i as f64 + 0.5) / steps_s as f64;
+        let cos_theta = 1. - s.powi(3);
+        let jacobian = 3. * s * s;
+        for j in 0..steps_phi {
+            let phi = 2. * PI * (j as f64 + 0.5) / steps_phi as f64;
+            total += f(&direction(cos_theta.acos(), phi)) * jacobian;
+        }
+    }
+
+    total * 2. * PI / (steps_s * steps_phi) as f64
+}
+
+fn distributions() -> [Ggx; 3] {
+    [Ggx::new(0.3, 0.), Ggx::new(0.6, 0.), Ggx::new(0.5, 0.8)]
+}
+
+fn views() -> [Vec3; 3] {
+    [direction(0., 0.), direction(0.7, 0.4), direction(1.3, 2.)]
+}
+
+#[test]
+fn visible_normal_pdf_integrates_to_one() {
+    for ggx in distributions() {
+        for wo in views() {
+            assert_close(integrate_hemisphere(|h| ggx.pdf_h(&wo, h)), 1., 1e-3);
+        }
+    }
+}
+
+#[test]
+fn visible_normal_samples_follow_the_pdf() {
+    let side = 200;
+
+    for ggx in distributions() {
+        for wo in views() {
+            // Compare the sampled and integrated means of a few functions of
+            // the normal, which differ if the samples aren't spread as the
+            // density says.
+            let moments = |h: &Vec3| [h.x(), h.y(), h.z(), h.z() * h.z()];
+
+            let mut sampled = [0.; 4];
+            for i in 0..side {
+                for j in 0..side {
+                    let xi = (
+                        (i as f64 + 0.5) / side as f64,
+                        (j as f64 + 0.5) / side as f64,
+                    );
+                    let h = ggx.sample_h(&wo, xi);
+                    assert_close(h.length(), 1., 1e-9);
+
+                    for (total, value) in sampled.iter_mut().zip(moments(&h)) {
+                        *total += value / (side * side) as f64;
+                    }
+                }
+            }
+
+            for (k, sampled) in sampled.into_iter().enumerate() {
+                let expected = integrate_hemisphere(|h| moments(h)[k] * ggx.pdf_h(&wo, h));
+                assert_close(sampled, expected, 1e-3);
+            }
+        }
+    }
+}
+
+#[test]
+fn sampled_reflection_weight_matches_brdf_over_pdf() {
+    let fresnel = Fresnel::Schlick {
+        f0: Colour::new(0.9, 0.6, 0.3),
+    };
+
+    for ggx in distributions() {
+        for wo in views() {
+            for xi in [(0.1, 0.2), (0.5, 0.5), (0.8, 0.9), (0.3, 0.7)] {
+                let Some((wi, weight)) = ggx.sample_reflection(&fresnel, &wo, xi) else {
+                    continue;
+                };
+
+                let expected = ggx.eval_reflection(&fresnel, &wo, &wi)
+                    * (wi.z() / ggx.pdf_reflection(&wo, &wi));
+                assert_close(weight.r(), expected.r(), 1e-6);
+                assert_close(weight.g(), expected.g(), 1e-6);
+                assert_close(weight.b(), expected.b(), 1e-6);
+            }
+        }
+    }
+}
+
+/// Reflectance at normal incidence of the measured conductors, against the
+/// linear F0 values tabulated in Real-Time Rendering (4th edition, table
+/// 9.2). Those are averaged over the sRGB primaries rather than sampled at
+/// one wavelength, so only agree to a few percent.
+#[test]
+fn conductor_presets_match_published_reflectance() {
+    let presets = [
+        (Fresnel::Gold, (1.000, 0.782, 0.344)),
+        (Fresnel::Copper, (0.955, 0.638, 0.538)),
+        (Fresnel::Aluminium, (0.913, 0.922, 0.924)),
+        (Fresnel::Silver, (0.972, 0.960, 0.915)),
+    ];
+
+    for (fresnel, (r, g, b)) in presets {
+        let f0 = fresnel.evaluate(1.);
+        assert_close(f0.r(), r, 0.04);
+        assert_close(f0.g(), g, 0.04);
+        assert_close(f0.b(), b, 0.04);
+
+        // And every conductor reflects everything at grazing angles.
+        let grazing = fresnel.evaluate(0.);
+        assert_close(grazing.g(), 1., 1e-9);
+    }
+}
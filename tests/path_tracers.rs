@@ -0,0 +1,137 @@
+use std::{cell::RefCell, rc::Rc};
+
+use raytacer::{
+    camera::{Camera, CameraConfig},
+    colour::Colour,
+    environment::Environment,
+    geometry::{Geometry, Material, Shape},
+    integrator::{Integrator, IterativePathTracer, PathTracer},
+    medium::Medium,
+    ray::Ray,
+    sampling::{self, SampleSource},
+    scene::Scene,
+    vec::Vec3,
+};
+
+/// A small generator, so that both tracers can be handed the same numbers.
+struct Lcg(u64);
+
+impl SampleSource for Lcg {
+    fn uniform(&mut self) -> f64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn sphere(centre: (f64, f64, f64), radius: f64, material: Material) -> Geometry {
+    Geometry {
+        shape: Shape::Sphere {
+            centre: Vec3::new(centre),
+            radius,
+        },
+        material,
+        medium: None,
+    }
+}
+
+/// A diffuse ground, an absorbing glass ball and a fog bank under a sky.
+fn scene() -> Scene {
+    let fog = Geometry {
+        medium: Some(Medium::Homogeneous {
+            density: 2.,
+            albedo: Colour::new(0.9, 0.8, 0.7),
+            anisotropy: 0.3,
+        }),
+        ..sphere((0.6, 0., -1.), 0.4, Material::Interface)
+    };
+
+    Scene {
+        camera: Camera::new(
+            CameraConfig::Perspective {
+                look_from: Vec3::new((0., 0., 0.)),
+                look_at: Vec3::new((0., 0., -1.)),
+                up: Vec3::new((0., 1., 0.)),
+                fov_degrees: 90.,
+                defocus_angle: 0.,
+                focus_dist: None,
+            },
+            (8, 8),
+        )
+        .unwrap(),
+        environment: Environment::Gradient {
+            bottom: Colour::white(),
+            top: Colour::new(0.5, 0.7, 1.),
+        },
+        medium: None,
+        geometries: vec![
+            sphere(
+                (0., -100.5, -1.),
+                100.,
+                Material::Lambertian {
+                    colour: Colour::new(0.8, 0.8, 0.),
+                    albedo: 0.5,
+                },
+            ),
+            sphere(
+                (-0.6, 0., -1.),
+                0.4,
+                Material::Dialectric {
+                    ior: 1.5,
+                    dispersion: None,
+                    tint: Colour::white(),
+                    thin_walled: false,
+                    absorption: Colour::new(0.5, 0.2, 0.1),
+                },
+            ),
+            fog,
+        ],
+        light_sampling: true,
+        spectral: false,
+    }
+}
+
+fn mean_radiance(integrator: &dyn Integrator, scene: &Scene, ray: &Ray, samples: u64) -> Colour {
+    let total = (0..samples).fold(Colour::black(), |total, seed| {
+        let source = Rc::new(RefCell::new(Lcg(seed)));
+        total + sampling::with_source(source, || integrator.radiance(scene, ray, None))
+    });
+    total * (1. / samples as f64)
+}
+
+#[test]
+fn recursive_and_iterative_tracers_agree() {
+    let scene = scene();
+    let recursive = PathTracer {
+        max_bounces: 8,
+        roulette_depth: 3,
+    };
+    let iterative = IterativePathTracer {
+        max_bounces: 8,
+        roulette_depth: 3,
+    };
+
+    for target in [
+        (0., -0.3, -1.),
+        (-0.6, 0., -1.),
+        (0.6, 0., -1.),
+        (0., 0.5, -1.),
+    ] {
+        let ray = Ray::new(Vec3::new((0., 0., 0.)), Vec3::new(target));
+        let recursive = mean_radiance(&recursive, &scene, &ray, 4000);
+        let iterative = mean_radiance(&iterative, &scene, &ray, 4000);
+
+        for (a, b) in [
+            (recursive.r(), iterative.r()),
+            (recursive.g(), iterative.g()),
+            (recursive.b(), iterative.b()),
+        ] {
+            assert!(
+                (a - b).abs() <= 0.02 * a.max(b).max(0.1),
+                "towards {target:?}: recursive {a}, iterative {b}"
+            );
+        }
+    }
+}
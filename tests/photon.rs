@@ -0,0 +1,107 @@
+use std::f64::consts::PI;
+
+use raytacer::{
+    camera::{Camera, CameraConfig},
+    colour::Colour,
+    environment::Environment,
+    geometry::{Geometry, Material, Shape},
+    integrator::{Integrator, PhotonMapper},
+    ray::Ray,
+    scene::Scene,
+    vec::Vec3,
+};
+
+/// Half the width of the floor and the ceiling.
+const HALF_WIDTH: f64 = 5.;
+/// How far the ceiling is above the floor.
+const HEIGHT: f64 = 1.;
+const ALBEDO: f64 = 0.5;
+
+/// A grey floor under a glowing ceiling of the same size, in the dark.
+fn lit_floor() -> Scene {
+    let slab = |bottom: f64, top: f64| Shape::Cuboid {
+        min: Vec3::new((-HALF_WIDTH, bottom, -HALF_WIDTH)),
+        max: Vec3::new((HALF_WIDTH, top, HALF_WIDTH)),
+    };
+
+    Scene {
+        camera: Camera::new(
+            CameraConfig::Perspective {
+                look_from: Vec3::new((0., HEIGHT / 2., 0.)),
+                look_at: Vec3::new((0., 0., 0.)),
+                up: Vec3::new((0., 0., -1.)),
+                fov_degrees: 90.,
+                defocus_angle: 0.,
+                focus_dist: None,
+            },
+            (4, 4),
+        )
+        .unwrap(),
+        environment: Environment::Constant {
+            colour: Colour::black(),
+        },
+        medium: None,
+        geometries: vec![
+            Geometry {
+                shape: slab(-1., 0.),
+                material: Material::Lambertian {
+                    colour: Colour::white(),
+                    albedo: ALBEDO,
+                },
+                medium: None,
+            },
+            Geometry {
+                shape: slab(HEIGHT, HEIGHT + 0.01),
+                material: Material::Emissive {
+                    radiance: Colour::white(),
+                },
+                medium: None,
+            },
+        ],
+        light_sampling: false,
+        spectral: false,
+    }
+}
+
+/// The fraction of the light leaving a point on the floor that would reach
+/// the ceiling, so also the fraction of the sky the ceiling covers as seen
+/// from there. It's found as the sum of the rectangles of ceiling with a
+/// corner above the point.
+fn view_factor(x: f64, z: f64) -> f64 {
+    let corner = |a: f64, b: f64| {
+        let (a, b) = (a / HEIGHT, b / HEIGHT);
+        let (root_a, root_b) = ((1. + a * a).sqrt(), (1. + b * b).sqrt());
+        (a / root_a * (b / root_a).atan() + b / root_b * (a / root_b).atan()) / (2. * PI)
+    };
+
+    let (left, right) = (HALF_WIDTH + x, HALF_WIDTH - x);
+    let (near, far) = (HALF_WIDTH + z, HALF_WIDTH - z);
+    corner(left, near) + corner(left, far) + corner(right, near) + corner(right, far)
+}
+
+#[test]
+fn photons_on_a_diffuse_floor_give_the_light_it_reflects() {
+    let scene = lit_floor();
+    let integrator = PhotonMapper::new(&scene, 1_000_000, 0, 2000, 0.8);
+
+    // Far enough apart that no photon is gathered at two of them.
+    let points = [-2., -1., 0., 1., 2.].map(|x| (x, 0.3 * x));
+    let mut total_ratio = 0.;
+    for (x, z) in points {
+        let ray = Ray::new(Vec3::new((x, HEIGHT / 2., z)), Vec3::new((0., -1., 0.)));
+        let radiance = integrator.radiance(&scene, &ray, None).g();
+
+        // The ceiling gives off one unit of radiance towards the floor,
+        // which reflects a share of it, evenly in every direction.
+        let expected = ALBEDO * view_factor(x, z);
+        let ratio = radiance / expected;
+        assert!(
+            (ratio - 1.).abs() < 0.08,
+            "radiance {radiance} at ({x}, {z}), expected {expected}"
+        );
+        total_ratio += ratio;
+    }
+
+    let ratio = total_ratio / points.len() as f64;
+    assert!((ratio - 1.).abs() < 0.03, "off by {ratio} on average");
+}
@@ -0,0 +1,141 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use raytacer::sampling::{self, Halton, SampleSource, Sampler, Sobol, Stratified};
+
+/// Hands out the same number every time.
+struct Constant(f64);
+
+impl SampleSource for Constant {
+    fn uniform(&mut self) -> f64 {
+        self.0
+    }
+}
+
+#[test]
+fn with_source_restores_the_previous_source() {
+    let outer = sampling::with_source(Rc::new(RefCell::new(Constant(0.25))), || {
+        let inner = sampling::with_source(Rc::new(RefCell::new(Constant(0.75))), || {
+            sampling::uniform()
+        });
+        (inner, sampling::uniform())
+    });
+    assert_eq!(outer, (0.75, 0.25));
+
+    // Back to the thread's generator, which doesn't repeat itself.
+    let numbers: Vec<f64> = (0..4).map(|_| sampling::uniform()).collect();
+    assert!(numbers.windows(2).any(|pair| pair[0] != pair[1]));
+    assert!(numbers.iter().all(|&number| (0. ..1.).contains(&number)));
+}
+
+/// Hands out each dimension's number, so that tests can see which one was
+/// asked for.
+struct Dimensions;
+
+impl Sampler for Dimensions {
+    fn sample(&self, _pixel: (usize, usize), _index: usize, dimension: usize) -> f64 {
+        dimension as f64 / 1000.
+    }
+}
+
+#[test]
+fn bounces_start_at_the_same_dimension_every_time() {
+    let sampler: Arc<dyn Sampler> = Arc::new(Dimensions);
+    let starts = |camera: usize, first_bounce: usize| {
+        sampling::with_sample(&sampler, (0, 0), 0, || {
+            (0..camera).for_each(|_| _ = sampling::uniform());
+            sampling::start_bounce(0);
+            let first = sampling::uniform();
+            (1..first_bounce).for_each(|_| _ = sampling::uniform());
+            sampling::start_bounce(1);
+            (first, sampling::uniform())
+        })
+    };
+
+    let starts = [starts(2, 3), starts(5, 1), starts(0, 9)];
+    assert!(starts.iter().all(|&start| start == starts[0]));
+    assert!(starts[0].0 > 0. && starts[0].1 > starts[0].0);
+}
+
+/// The pair of numbers the path through each of `count` samples of `pixel`
+/// starts `bounce` with, where each sample draws a different number of
+/// numbers before it.
+fn bounce_pairs(
+    sampler: &Arc<dyn Sampler>,
+    pixel: (usize, usize),
+    bounce: usize,
+    count: usize,
+) -> Vec<(f64, f64)> {
+    (0..count)
+        .map(|index| {
+            sampling::with_sample(sampler, pixel, index, || {
+                for earlier in 0..bounce {
+                    sampling::start_bounce(earlier);
+                    (0..index % 4).for_each(|_| _ = sampling::uniform());
+                }
+                sampling::start_bounce(bounce);
+                sampling::uniform_pair()
+            })
+        })
+        .collect()
+}
+
+/// Whether every one of `count` equal intervals of `[0, 1)` holds exactly one
+/// of `values`.
+fn stratified(values: impl Iterator<Item = f64>, count: usize) -> bool {
+    let mut filled = vec![false; count];
+    for value in values {
+        let stratum = (value * count as f64) as usize;
+        if filled[stratum] {
+            return false;
+        }
+        filled[stratum] = true;
+    }
+    filled.into_iter().all(|filled| filled)
+}
+
+/// Whether every cell of a `side` by `side` grid holds exactly one of
+/// `points`.
+fn stratified_2d(points: &[(f64, f64)], side: usize) -> bool {
+    let cells = points.iter().map(|&(x, y)| {
+        let cell = (y * side as f64) as usize * side + (x * side as f64) as usize;
+        (cell as f64 + 0.5) / (side * side) as f64
+    });
+    stratified(cells, side * side)
+}
+
+#[test]
+fn stratified_samples_fill_every_cell_of_each_pixel() {
+    let sampler: Arc<dyn Sampler> = Arc::new(Stratified::new(16));
+    for pixel in [(0, 0), (5, 3)] {
+        for bounce in [0, 3] {
+            let pairs = bounce_pairs(&sampler, pixel, bounce, 16);
+            assert!(stratified_2d(&pairs, 4), "{pixel:?} bounce {bounce}");
+        }
+    }
+}
+
+#[test]
+fn sobol_samples_fill_every_cell_of_each_pixel() {
+    let sampler: Arc<dyn Sampler> = Arc::new(Sobol::new());
+    for pixel in [(0, 0), (5, 3)] {
+        for bounce in [0, 3] {
+            let pairs = bounce_pairs(&sampler, pixel, bounce, 16);
+            assert!(stratified_2d(&pairs, 4), "{pixel:?} bounce {bounce}");
+            assert!(stratified(pairs.iter().map(|pair| pair.0), 16));
+            assert!(stratified(pairs.iter().map(|pair| pair.1), 16));
+        }
+    }
+}
+
+#[test]
+fn halton_samples_fill_every_interval_of_each_pixel() {
+    // The first bounce takes its pair from the dimensions in bases 23 and
+    // 29, so that many samples land once in each interval of that size.
+    let sampler: Arc<dyn Sampler> = Arc::new(Halton::new());
+    for pixel in [(0, 0), (5, 3)] {
+        let pairs = bounce_pairs(&sampler, pixel, 0, 23);
+        assert!(stratified(pairs.iter().map(|pair| pair.0), 23), "{pixel:?}");
+        let pairs = bounce_pairs(&sampler, pixel, 0, 29);
+        assert!(stratified(pairs.iter().map(|pair| pair.1), 29), "{pixel:?}");
+    }
+}
@@ -0,0 +1,104 @@
+use std::f64::consts::PI;
+
+use raytacer::{colour::Colour, environment::Sky, vec::Vec3};
+
+fn sky(sun_elevation: f64) -> Sky {
+    Sky {
+        sun_elevation,
+        sun_azimuth: 30.,
+        turbidity: 3.,
+        ground_albedo: Colour::new(0.3, 0.3, 0.3),
+        intensity: 0.1,
+        sun_disc: true,
+    }
+}
+
+/// Points spread evenly over `[0, 1)²`.
+fn grid(count: usize) -> impl Iterator<Item = (f64, f64)> {
+    (0..count).flat_map(move |i| {
+        (0..count).map(move |j| {
+            (
+                (i as f64 + 0.5) / count as f64,
+                (j as f64 + 0.5) / count as f64,
+            )
+        })
+    })
+}
+
+fn channels(colour: Colour) -> [f64; 3] {
+    [colour.r(), colour.g(), colour.b()]
+}
+
+#[test]
+fn radiance_is_finite_and_non_negative_everywhere() {
+    for elevation in [-10., 0., 5., 30., 60., 90.] {
+        let sky = sky(elevation);
+        for xi in grid(64) {
+            let radiance = sky.radiance(&Vec3::on_unit_sphere(xi));
+            for channel in channels(radiance) {
+                assert!(
+                    channel.is_finite() && channel >= 0.,
+                    "{channel} at elevation {elevation}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn below_the_horizon_is_a_flat_ground() {
+    let sky = sky(40.);
+    let ground = sky.radiance(&Vec3::new((0., -1., 0.)));
+    for xi in grid(32) {
+        let direction = Vec3::on_unit_sphere(xi);
+        if direction.y() < 0. {
+            assert_eq!(channels(sky.radiance(&direction)), channels(ground));
+        }
+    }
+
+    // The ground only reflects the light falling on it, so a black one is
+    // dark.
+    let black_ground = Sky {
+        ground_albedo: Colour::black(),
+        ..sky
+    };
+    assert_eq!(
+        channels(black_ground.radiance(&Vec3::new((0.3, -0.5, 0.2)))),
+        [0.; 3]
+    );
+}
+
+#[test]
+fn a_set_sun_leaves_the_ground_dimmer_than_the_sky_lit_by_it() {
+    // Light on the ground comes from the sky above it, so with a grey ground
+    // and the sun down it can't outshine the brightest part of the sky.
+    let sky = sky(-5.);
+    let ground = sky.radiance(&Vec3::new((0., -1., 0.)));
+    let brightest = grid(64)
+        .map(Vec3::on_unit_sphere)
+        .filter(|direction| direction.y() > 0.)
+        .map(|direction| sky.radiance(&direction).luminance())
+        .fold(0., f64::max);
+    assert!(ground.luminance() < brightest);
+}
+
+#[test]
+fn samples_report_the_same_pdf_and_radiance_as_evaluating_them() {
+    for elevation in [-10., 2., 45.] {
+        let sky = sky(elevation);
+        let mut inverse_pdf_sum = 0.;
+        let count = 128;
+
+        for xi in grid(count) {
+            let (direction, radiance, pdf) = sky.sample(xi);
+            assert!((direction.length() - 1.).abs() < 1e-9);
+            assert!((pdf - sky.pdf(&direction)).abs() <= 1e-9 * pdf);
+            assert_eq!(channels(radiance), channels(sky.radiance(&direction)));
+            inverse_pdf_sum += 1. / pdf;
+        }
+
+        // Averaging 1/pdf over the samples estimates the area of the sphere.
+        let area = inverse_pdf_sum / (count * count) as f64;
+        assert!((area - 4. * PI).abs() < 0.05 * 4. * PI, "{area}");
+    }
+}
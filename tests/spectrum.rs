@@ -0,0 +1,104 @@
+use raytacer::{
+    colour::Colour,
+    spectrum::{cie_xyz, Dispersion, SampledWavelengths},
+};
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{actual} is not within {tolerance} of {expected}"
+    );
+}
+
+#[test]
+fn glasses_match_their_catalogue_indices() {
+    // Schott's BK7 at the helium d, hydrogen F and hydrogen C lines.
+    assert_close(Dispersion::Bk7.ior(587.6), 1.5168, 1e-4);
+    assert_close(Dispersion::Bk7.ior(486.1), 1.5224, 1e-4);
+    assert_close(Dispersion::Bk7.ior(656.3), 1.5143, 1e-4);
+
+    // Diamond at the sodium D line.
+    assert_close(Dispersion::Diamond.ior(589.3), 2.417, 1e-3);
+}
+
+#[test]
+fn cauchy_and_sellmeier_follow_their_formulas() {
+    let cauchy = Dispersion::Cauchy {
+        a: 1.5046,
+        b: 0.0042,
+    };
+    assert_close(cauchy.ior(550.), 1.5046 + 0.0042 / 0.55 / 0.55, 1e-12);
+    assert_close(cauchy.ior(550.), 1.51848, 1e-5);
+
+    // A single term with no resonance is a constant index.
+    let sellmeier = Dispersion::Sellmeier {
+        b: [1.25, 0., 0.],
+        c: [0., 0., 0.],
+    };
+    for lambda in [400., 550., 700.] {
+        assert_close(sellmeier.ior(lambda), 1.5, 1e-12);
+    }
+
+    // Normal dispersion: blue bends more than red.
+    for dispersion in [cauchy, Dispersion::Bk7, Dispersion::Diamond] {
+        assert!(dispersion.ior(450.) > dispersion.ior(650.));
+    }
+}
+
+#[test]
+fn colour_matching_functions_peak_in_the_right_places() {
+    let [_, y, _] = cie_xyz(555.);
+    assert_close(y, 1., 0.01);
+
+    let [x, _, _] = cie_xyz(600.);
+    assert_close(x, 1.06, 0.02);
+
+    let [_, _, z] = cie_xyz(450.);
+    assert_close(z, 1.75, 0.05);
+
+    // Nothing is seen beyond the visible range.
+    for lambda in [300., 800.] {
+        for value in cie_xyz(lambda) {
+            assert!(value.abs() < 0.01, "{value} at {lambda}nm");
+        }
+    }
+}
+
+/// The average RGB colour of `colour` evaluated over evenly spread hero
+/// wavelengths.
+fn round_trip(colour: Colour) -> Colour {
+    let count = 512;
+    (0..count)
+        .map(|i| {
+            let wavelengths = SampledWavelengths::sample((i as f64 + 0.5) / count as f64);
+            wavelengths.to_rgb(wavelengths.evaluate(colour))
+        })
+        .fold(Colour::black(), |sum, rgb| sum + rgb)
+        / count as f64
+}
+
+#[test]
+fn whites_and_greys_are_flat_spectra() {
+    for grey in [1., 0.5, 0.18] {
+        let wavelengths = SampledWavelengths::sample(0.3);
+        let values = wavelengths.evaluate(Colour::new(grey, grey, grey));
+        for value in [values.r(), values.g(), values.b()] {
+            assert_close(value, grey, 1e-9);
+        }
+    }
+}
+
+#[test]
+fn colours_survive_a_round_trip_through_wavelengths() {
+    for colour in [
+        Colour::new(1., 1., 1.),
+        Colour::new(0.5, 0.5, 0.5),
+        Colour::new(0.8, 0.3, 0.1),
+        Colour::new(0.1, 0.6, 0.9),
+    ] {
+        let rgb = round_trip(colour);
+        assert_close(rgb.r(), colour.r(), 0.01);
+        assert_close(rgb.g(), colour.g(), 0.01);
+        assert_close(rgb.b(), colour.b(), 0.01);
+    }
+}
@@ -0,0 +1,108 @@
+use raytacer::{
+    camera::{Camera, CameraConfig},
+    colour::Colour,
+    environment::Environment,
+    geometry::{Geometry, Interior, Material, Shape},
+    ray::Ray,
+    scene::Scene,
+    vec::Vec3,
+};
+
+const THICKNESS: f64 = 1.;
+
+/// A wide slab of white subsurface material, with a ball of something else
+/// buried in the middle of it.
+fn slab() -> Scene {
+    let subsurface = Material::Subsurface {
+        albedo: Colour::white(),
+        mean_free_path: Colour::new(0.2, 0.2, 0.2),
+        ior: 1.4,
+        anisotropy: 0.,
+    };
+    let diffuse = Material::Lambertian {
+        colour: Colour::white(),
+        albedo: 0.5,
+    };
+
+    Scene {
+        camera: Camera::new(
+            CameraConfig::Perspective {
+                look_from: Vec3::new((0., 2., 0.)),
+                look_at: Vec3::new((0., 0., 0.)),
+                up: Vec3::new((0., 0., -1.)),
+                fov_degrees: 90.,
+                defocus_angle: 0.,
+                focus_dist: None,
+            },
+            (4, 4),
+        )
+        .unwrap(),
+        environment: Environment::Constant {
+            colour: Colour::white(),
+        },
+        medium: None,
+        geometries: vec![
+            Geometry {
+                shape: Shape::Cuboid {
+                    min: Vec3::new((-1000., -THICKNESS / 2., -1000.)),
+                    max: Vec3::new((1000., THICKNESS / 2., 1000.)),
+                },
+                material: subsurface,
+                medium: None,
+            },
+            Geometry {
+                shape: Shape::Sphere {
+                    centre: Vec3::new((0., 0., 0.)),
+                    radius: 0.2,
+                },
+                material: diffuse,
+                medium: None,
+            },
+        ],
+        light_sampling: false,
+        spectral: false,
+    }
+}
+
+#[test]
+fn a_white_slab_reflects_or_transmits_all_light_entering_it() {
+    let scene = slab();
+    let slab = &scene.geometries[0].shape;
+    let interior = Interior::new(Colour::new(0.2, 0.2, 0.2), Colour::white(), 0., 1.4);
+
+    let count = 4000;
+    let (mut reflected, mut transmitted) = (0., 0.);
+    for _ in 0..count {
+        let entry = Ray::new(
+            Vec3::new((0., THICKNESS / 2., 0.)),
+            Vec3::new((0., -1., 0.)),
+        );
+        let Some((exit, throughput)) = scene.random_walk(entry, slab, &interior) else {
+            continue;
+        };
+
+        // Only the faces of the slab are ways out, not the ball inside it.
+        let height = exit.origin.y();
+        assert!(
+            (height.abs() - THICKNESS / 2.).abs() < 1e-6,
+            "left the slab at height {height}"
+        );
+        assert!(exit.direction.y() * height > 0., "left heading back in");
+
+        if height > 0. {
+            reflected += throughput.luminance();
+        } else {
+            transmitted += throughput.luminance();
+        }
+    }
+
+    let (reflected, transmitted) = (reflected / count as f64, transmitted / count as f64);
+    assert!(
+        reflected > 0.1 && transmitted > 0.1,
+        "R = {reflected}, T = {transmitted}"
+    );
+    assert!(
+        (reflected + transmitted - 1.).abs() < 0.01,
+        "R = {reflected}, T = {transmitted}"
+    );
+}
@@ -0,0 +1,54 @@
+use raytacer::{geometry::Shape, ray::Ray, vec::Vec3};
+
+fn assert_tangent_to_normal(shape: &Shape, ray: &Ray) {
+    let hit = shape.hit_test(ray, 0.001..f64::INFINITY).unwrap();
+
+    for tangent in [hit.dpdu, hit.dpdv] {
+        assert!(tangent.length() > 0.);
+        assert!(
+            tangent.unit().dot(&hit.normal).abs() < 1e-9,
+            "{tangent:?} is not tangent to {:?}",
+            hit.normal
+        );
+    }
+}
+
+#[test]
+fn sphere_tangents_are_orthogonal_to_the_normal() {
+    let sphere = Shape::Sphere {
+        centre: Vec3::new((0., 0., -3.)),
+        radius: 1.5,
+    };
+
+    for direction in [
+        (0., 0., -1.),
+        (0.3, 0.2, -1.),
+        (-0.4, 0.1, -1.),
+        (0.1, -0.45, -1.),
+    ] {
+        let ray = Ray::new(Vec3::new((0., 0., 0.)), Vec3::new(direction));
+        assert_tangent_to_normal(&sphere, &ray);
+    }
+
+    // Straight down onto the pole, where the parameterisation degenerates.
+    let ray = Ray::new(Vec3::new((0., 5., -3.)), Vec3::new((0., -1., 0.)));
+    assert_tangent_to_normal(&sphere, &ray);
+}
+
+#[test]
+fn cuboid_tangents_are_orthogonal_to_the_normal() {
+    let cuboid = Shape::Cuboid {
+        min: Vec3::new((-1., -2., -3.)),
+        max: Vec3::new((1., 2., 3.)),
+    };
+
+    for (origin, direction) in [
+        ((5., 0.5, 0.5), (-1., 0., 0.)),
+        ((0.5, -5., 0.5), (0., 1., 0.)),
+        ((0.5, 0.5, 10.), (0., 0., -1.)),
+        ((0., 0., 0.), (0.2, 0.3, 1.)),
+    ] {
+        let ray = Ray::new(Vec3::new(origin), Vec3::new(direction));
+        assert_tangent_to_normal(&cuboid, &ray);
+    }
+}
@@ -0,0 +1,76 @@
+use raytacer::{
+    medium::{DensityGrid, Medium},
+    scene::SceneDescription,
+    vec::Vec3,
+};
+
+fn ramp() -> DensityGrid {
+    // Two voxels along X, with values 0 and 1, repeated through Y and Z.
+    DensityGrid::new((2, 2, 2), vec![0., 1., 0., 1., 0., 1., 0., 1.]).unwrap()
+}
+
+#[test]
+fn rejects_mismatched_value_count() {
+    assert!(DensityGrid::new((2, 2, 2), vec![0.; 7]).is_err());
+}
+
+#[test]
+fn round_trips_through_a_file() {
+    let dir = std::env::temp_dir();
+    let path = "raytacer_round_trip.vox";
+
+    let mut grid = ramp();
+    grid.save(&dir, path).unwrap();
+    let loaded = DensityGrid::load(&dir, path).unwrap();
+    std::fs::remove_file(dir.join(path)).unwrap();
+
+    assert_eq!(loaded.resolution(), (2, 2, 2));
+    assert_eq!(loaded.max(), 1.);
+    for x in 0..2 {
+        assert_eq!(loaded.voxel(x, 1, 1), grid.voxel(x, 1, 1));
+    }
+}
+
+#[test]
+fn interpolates_between_voxel_centres() {
+    let grid = ramp();
+
+    assert_eq!(grid.lookup(&Vec3::new((0.25, 0.5, 0.5))), 0.);
+    assert_eq!(grid.lookup(&Vec3::new((0.5, 0.5, 0.5))), 0.5);
+    assert_eq!(grid.lookup(&Vec3::new((0.75, 0.5, 0.5))), 1.);
+    assert_eq!(grid.lookup(&Vec3::new((0.625, 0.1, 0.9))), 0.75);
+}
+
+#[test]
+fn is_empty_outside_the_unit_cube() {
+    let grid = ramp();
+
+    assert_eq!(grid.lookup(&Vec3::new((1.01, 0.5, 0.5))), 0.);
+    assert_eq!(grid.lookup(&Vec3::new((0.5, -0.01, 0.5))), 0.);
+}
+
+#[test]
+fn scene_files_find_grids_beside_them() {
+    let dir = std::env::temp_dir().join("raytacer_scene_grids");
+    std::fs::create_dir_all(&dir).unwrap();
+    ramp().save(&dir, "smoke.vox").unwrap();
+
+    let yaml = "
+        geometries:
+          - shape: !Cuboid { min: [0, 0, 0], max: [1, 1, 1] }
+            material: !Interface
+            medium: !Grid { grid: smoke.vox, min: [0, 0, 0], max: [1, 1, 1] }
+    ";
+    let mut description: SceneDescription = serde_yaml::from_str(yaml).unwrap();
+    description.load_grids(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let Some(Medium::Grid(medium)) = &description.geometries[0].medium else {
+        panic!("not a grid medium");
+    };
+    assert_eq!(medium.grid.resolution(), (2, 2, 2));
+
+    // The scene file keeps the path as it was written.
+    let saved = serde_yaml::to_string(&description).unwrap();
+    assert!(saved.contains("grid: smoke.vox"), "{saved}");
+}